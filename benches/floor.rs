@@ -0,0 +1,81 @@
+//! Compares the reference floor span renderer with the fixed-point one.
+//!
+//! Run with `cargo bench --bench floor` (add `--features simd` for the batched version).
+
+use std::{
+    f64::consts::PI,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use rayguy::{
+    camera::Camera,
+    consts::FOV,
+    floor::{self, FloorRow, FloorTextures},
+    map::Map,
+    math::Vec2,
+    projection::Projection,
+    texture::test_textures,
+};
+
+const RESOLUTIONS: [(usize, usize); 3] = [(160, 144), (640, 480), (1920, 1080)];
+
+// Runs `f` repeatedly for about `budget` and returns the average time per run
+fn measure(budget: Duration, mut f: impl FnMut()) -> Duration {
+    // Warm up caches and let the CPU clock up
+    for _ in 0..3 {
+        f();
+    }
+    let mut runs = 0;
+    let start = Instant::now();
+    while start.elapsed() < budget {
+        f();
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    let map = Map::test_level();
+    let textures = test_textures();
+    let floor_textures = FloorTextures::validate(&textures, &map).expect("test textures are valid");
+    let camera = Camera::new(Vec2::new(22., 11.5), PI, FOV.to_radians());
+
+    println!(
+        "{:>10} {:>14} {:>14} {:>8} {:>10}",
+        "resolution", "reference", "fast", "speedup", "mismatch"
+    );
+    for (width, height) in RESOLUTIONS {
+        let projection = Projection::new(width, height, 1., &camera);
+        let rows: Vec<_> = (0..height)
+            .filter_map(|y| FloorRow::new(y, &projection, &camera))
+            .collect();
+        let mut reference = vec![0u32; width * rows.len()];
+        let mut fast = vec![0u32; width * rows.len()];
+
+        let budget = Duration::from_secs(1);
+        let reference_time = measure(budget, || {
+            for (row, pixels) in rows.iter().zip(reference.chunks_exact_mut(width)) {
+                floor::draw_span(black_box(row), pixels, &map, &textures);
+            }
+            black_box(&reference);
+        });
+        let fast_time = measure(budget, || {
+            for (row, pixels) in rows.iter().zip(fast.chunks_exact_mut(width)) {
+                floor::draw_span_fast(black_box(row), pixels, &floor_textures);
+            }
+            black_box(&fast);
+        });
+
+        // Pixels that sample exactly on a texel edge can round to either side, this axis aligned pose has many
+        let mismatched = reference.iter().zip(&fast).filter(|(a, b)| a != b).count();
+        println!(
+            "{:>10} {:>11.1?}/f {:>11.1?}/f {:>7.2}x {:>9.3}%",
+            format!("{width}x{height}"),
+            reference_time,
+            fast_time,
+            reference_time.as_secs_f64() / fast_time.as_secs_f64(),
+            mismatched as f64 / reference.len() as f64 * 100.,
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Sound mixing with positional effects.
+//!
+//! A [`Mixer`] plays [`Sound`]s on two buses, effects and music. Positional sounds get quieter with distance,
+//! are panned between the left and right channel by where they are relative to the listener's view direction
+//! and sound muffled when walls are in the way, found by walking the grid from the listener to the sound with
+//! the same DDA the renderer uses. [`Audio`] mixes into an [`AudioBackend`] at the pace of the simulation,
+//! output devices plug in there. [`NullBackend`] throws the samples away and [`OfflineBackend`] collects them,
+//! for running headless and in tests.
+
+use std::{f32::consts::FRAC_PI_4, sync::Arc};
+
+use crate::{map::Map, math::Vec2, ray::Ray};
+
+/// Frames per second of everything the mixer handles.
+pub const SAMPLE_RATE: u32 = 44_100;
+
+/// Mono samples in `-1..1` at [`SAMPLE_RATE`], cheap to clone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    samples: Arc<[f32]>,
+}
+
+impl Sound {
+    pub fn new(samples: Vec<f32>) -> Self {
+        Self {
+            samples: samples.into(),
+        }
+    }
+
+    /// Converts mono samples recorded at `rate` to [`SAMPLE_RATE`] with linear interpolation.
+    pub fn resampled(samples: &[f32], rate: u32) -> Self {
+        if rate == SAMPLE_RATE || samples.is_empty() {
+            return Self::new(samples.to_vec());
+        }
+        let step = rate as f64 / SAMPLE_RATE as f64;
+        let len = (samples.len() as f64 / step).ceil() as usize;
+        let resampled = (0..len)
+            .map(|i| {
+                let pos = i as f64 * step;
+                let (index, t) = (pos as usize, pos.fract() as f32);
+                let a = samples[index.min(samples.len() - 1)];
+                let b = samples[(index + 1).min(samples.len() - 1)];
+                a + (b - a) * t
+            })
+            .collect();
+        Self::new(resampled)
+    }
+
+    /// Sine wave, handy as a placeholder and for tests.
+    pub fn tone(frequency: f32, seconds: f32, volume: f32) -> Self {
+        let len = (seconds * SAMPLE_RATE as f32) as usize;
+        let step = std::f32::consts::TAU * frequency / SAMPLE_RATE as f32;
+        Self::new((0..len).map(|i| (i as f32 * step).sin() * volume).collect())
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / SAMPLE_RATE as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Bus {
+    #[default]
+    Effects,
+    Music,
+}
+
+/// Where a sound comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Source {
+    /// Everywhere, centered between both channels, like music.
+    #[default]
+    Ambient,
+    /// A point on the map.
+    At(Vec2),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayOptions {
+    pub bus: Bus,
+    pub source: Source,
+    pub volume: f32,
+    pub looping: bool,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        Self {
+            bus: Bus::default(),
+            source: Source::default(),
+            volume: 1.,
+            looping: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioSettings {
+    pub effects_volume: f32,
+    pub music_volume: f32,
+    /// Sounds closer than this many cells play at full volume.
+    pub reference_distance: f64,
+    /// Sounds further away than this many cells are silent.
+    pub max_distance: f64,
+    /// Volume multiplier for every wall between the listener and a sound.
+    pub wall_gain: f32,
+    /// How much of the high frequencies passes through each wall, 1 keeps all of them and lower values muffle
+    /// more.
+    pub wall_filter: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            effects_volume: 1.,
+            music_volume: 0.6,
+            reference_distance: 1.,
+            max_distance: 24.,
+            wall_gain: 0.5,
+            wall_filter: 0.3,
+        }
+    }
+}
+
+/// Handle of a playing sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+#[derive(Debug, Clone)]
+struct Voice {
+    id: VoiceId,
+    sound: Sound,
+    options: PlayOptions,
+    position: usize,
+    // Channel gains reached at the end of the last mix and the ones to ramp to, ramping avoids clicks
+    gains: [f32; 2],
+    target: [f32; 2],
+    // One pole low-pass filter, 1 lets everything through
+    filter: f32,
+    filtered: f32,
+}
+
+/// Gains of the left and right channel and the low-pass coefficient of a sound at `pos`.
+fn spatialize(
+    settings: &AudioSettings,
+    map: &Map,
+    listener: Vec2,
+    dir: Vec2,
+    pos: Vec2,
+) -> ([f32; 2], f32) {
+    let offset = pos - listener;
+    let distance = offset.length();
+    if distance >= settings.max_distance {
+        return ([0., 0.], 1.);
+    }
+    let attenuation =
+        (settings.reference_distance / distance.max(settings.reference_distance)) as f32;
+
+    // Walls between listener and sound, not counting the one the sound is in (e.g. a door)
+    let walls = if distance > 0. {
+        let (cell_x, cell_y) = (pos.x.floor() as i32, pos.y.floor() as i32);
+        Ray::new(listener, offset / distance)
+            .take_while(|step| step.distance < distance)
+            .filter(|step| (step.x, step.y) != (cell_x, cell_y) && map.is_wall(step.x, step.y))
+            .count() as i32
+    } else {
+        0
+    };
+    let gain = attenuation * settings.wall_gain.powi(walls);
+    let filter = settings.wall_filter.powi(walls);
+
+    // -1 fully left, 1 fully right. The camera plane points right, which is dir turned clockwise.
+    let right = -dir.perpendicular();
+    let pan = if distance > 0. {
+        (offset / distance).dot(right.normalize()) as f32
+    } else {
+        0.
+    };
+    // Equal power, so a sound moving across doesn't get quieter in the middle
+    let angle = (pan + 1.) * FRAC_PI_4;
+    ([gain * angle.cos(), gain * angle.sin()], filter)
+}
+
+/// Plays sounds and mixes them into stereo frames.
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    pub settings: AudioSettings,
+    voices: Vec<Voice>,
+    next_id: u64,
+}
+
+impl Mixer {
+    pub fn new(settings: AudioSettings) -> Self {
+        Self {
+            settings,
+            voices: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Starts `sound`. Positional sounds stay silent until the next [`Mixer::update`].
+    pub fn play(&mut self, sound: &Sound, options: PlayOptions) -> VoiceId {
+        let id = VoiceId(self.next_id);
+        self.next_id += 1;
+        let gains = match options.source {
+            Source::Ambient => [FRAC_PI_4.cos(), FRAC_PI_4.sin()],
+            Source::At(_) => [0., 0.],
+        };
+        self.voices.push(Voice {
+            id,
+            sound: sound.clone(),
+            options,
+            position: 0,
+            gains,
+            target: gains,
+            filter: 1.,
+            filtered: 0.,
+        });
+        id
+    }
+
+    pub fn stop(&mut self, id: VoiceId) {
+        self.voices.retain(|voice| voice.id != id);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    /// Moves a positional sound, takes effect with the next [`Mixer::update`].
+    pub fn set_source(&mut self, id: VoiceId, source: Source) {
+        if let Some(voice) = self.voices.iter_mut().find(|voice| voice.id == id) {
+            voice.options.source = source;
+        }
+    }
+
+    /// Recomputes volume, panning and occlusion of every sound for a listener at `pos` looking along `dir`,
+    /// usually the camera. Call once per tick.
+    pub fn update(&mut self, map: &Map, pos: Vec2, dir: Vec2) {
+        for voice in &mut self.voices {
+            if let Source::At(source) = voice.options.source {
+                (voice.target, voice.filter) = spatialize(&self.settings, map, pos, dir, source);
+            }
+        }
+    }
+
+    /// Overwrites `out` with interleaved stereo frames (left, right, left, ...) of everything playing and
+    /// advances the sounds. Sounds that ended are removed.
+    pub fn mix(&mut self, out: &mut [f32]) {
+        out.fill(0.);
+        let frames = out.len() / 2;
+        for voice in &mut self.voices {
+            let samples = voice.sound.samples();
+            let bus_volume = match voice.options.bus {
+                Bus::Effects => self.settings.effects_volume,
+                Bus::Music => self.settings.music_volume,
+            };
+            let volume = voice.options.volume * bus_volume;
+            for (frame, out) in out.chunks_exact_mut(2).enumerate() {
+                if voice.position >= samples.len() {
+                    if !voice.options.looping || samples.is_empty() {
+                        break;
+                    }
+                    voice.position = 0;
+                }
+                let sample = samples[voice.position];
+                voice.position += 1;
+                voice.filtered += (sample - voice.filtered) * voice.filter;
+                let t = (frame + 1) as f32 / frames as f32;
+                for (channel, out) in out.iter_mut().enumerate() {
+                    let gain =
+                        voice.gains[channel] + (voice.target[channel] - voice.gains[channel]) * t;
+                    *out += voice.filtered * gain * volume;
+                }
+            }
+            voice.gains = voice.target;
+        }
+        self.voices
+            .retain(|voice| voice.options.looping || voice.position < voice.sound.samples().len());
+    }
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new(AudioSettings::default())
+    }
+}
+
+/// Where mixed audio goes, e.g. a sound card.
+pub trait AudioBackend {
+    /// Receives interleaved stereo frames at [`SAMPLE_RATE`], as many as were simulated since the last call.
+    fn write(&mut self, samples: &[f32]);
+}
+
+/// Discards everything.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullBackend;
+
+impl AudioBackend for NullBackend {
+    fn write(&mut self, _samples: &[f32]) {}
+}
+
+/// Collects everything in memory.
+#[derive(Debug, Clone, Default)]
+pub struct OfflineBackend {
+    pub samples: Vec<f32>,
+}
+
+impl AudioBackend for OfflineBackend {
+    fn write(&mut self, samples: &[f32]) {
+        self.samples.extend_from_slice(samples);
+    }
+}
+
+/// A mixer feeding a backend at the pace of the simulation.
+#[derive(Debug, Clone)]
+pub struct Audio<B> {
+    pub mixer: Mixer,
+    pub backend: B,
+    buffer: Vec<f32>,
+    // Fraction of a frame left over from the last update
+    remainder: f64,
+}
+
+impl<B: AudioBackend> Audio<B> {
+    pub fn new(mixer: Mixer, backend: B) -> Self {
+        Self {
+            mixer,
+            backend,
+            buffer: Vec::new(),
+            remainder: 0.,
+        }
+    }
+
+    /// Mixes `dt` seconds of audio into the backend.
+    pub fn update(&mut self, dt: f64) {
+        let frames = dt * SAMPLE_RATE as f64 + self.remainder;
+        self.remainder = frames.fract();
+        self.buffer.resize(frames as usize * 2, 0.);
+        self.mixer.mix(&mut self.buffer);
+        self.backend.write(&self.buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Peak of each channel
+    fn peaks(samples: &[f32]) -> [f32; 2] {
+        samples.chunks_exact(2).fold([0f32; 2], |[l, r], frame| {
+            [l.max(frame[0].abs()), r.max(frame[1].abs())]
+        })
+    }
+
+    fn mix_once(mixer: &mut Mixer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.; frames * 2];
+        mixer.mix(&mut out);
+        out
+    }
+
+    // Tone at `pos` heard from (1.5, 1.5) looking towards +x, after ramping to the target gains
+    fn heard(map: &Map, pos: Vec2) -> [f32; 2] {
+        let mut mixer = Mixer::default();
+        let tone = Sound::tone(440., 1., 1.);
+        mixer.play(
+            &tone,
+            PlayOptions {
+                source: Source::At(pos),
+                ..Default::default()
+            },
+        );
+        mixer.update(map, Vec2::new(1.5, 1.5), Vec2::new(1., 0.));
+        mix_once(&mut mixer, 512);
+        peaks(&mix_once(&mut mixer, 512))
+    }
+
+    #[test]
+    fn attenuates_and_pans() {
+        let map = Map::new(16, 16);
+        let ahead = heard(&map, Vec2::new(3.5, 1.5));
+        assert!((ahead[0] - ahead[1]).abs() < 1e-3);
+        let far = heard(&map, Vec2::new(9.5, 1.5));
+        assert!(far[0] < ahead[0] * 0.5);
+        assert_eq!(heard(&map, Vec2::new(1.5, 1.5 + 30.)), [0., 0.]);
+
+        // The camera plane points to -y when looking towards +x
+        let right = heard(&map, Vec2::new(1.5, 0.5));
+        assert!(right[1] > 0.9 && right[0] < 1e-3, "{right:?}");
+        let left = heard(&map, Vec2::new(1.5, 3.5));
+        assert!(left[0] > 0.4 && left[1] < 1e-3, "{left:?}");
+    }
+
+    #[test]
+    fn walls_muffle() {
+        let mut map = Map::new(16, 16);
+        let open = heard(&map, Vec2::new(5.5, 1.5));
+        map.set(3, 1, 1);
+        let one = heard(&map, Vec2::new(5.5, 1.5));
+        map.set(4, 1, 1);
+        let two = heard(&map, Vec2::new(5.5, 1.5));
+        assert!(
+            open[0] > one[0] && one[0] > two[0],
+            "{open:?} {one:?} {two:?}"
+        );
+        // The wall the sound is in doesn't count, only the one at (3, 1) is in the way
+        let mut one_wall = Map::new(16, 16);
+        one_wall.set(3, 1, 1);
+        assert_eq!(
+            heard(&map, Vec2::new(4.5, 1.5)),
+            heard(&one_wall, Vec2::new(4.5, 1.5))
+        );
+    }
+
+    #[test]
+    fn buses_loops_and_ending() {
+        let mut mixer = Mixer::default();
+        let click = Sound::new(vec![1.; 100]);
+        let effect = mixer.play(&click, PlayOptions::default());
+        let music = mixer.play(
+            &click,
+            PlayOptions {
+                bus: Bus::Music,
+                looping: true,
+                ..Default::default()
+            },
+        );
+        mixer.settings.music_volume = 0.;
+        let out = mix_once(&mut mixer, 150);
+        assert!((out[0] - FRAC_PI_4.cos()).abs() < 1e-6);
+        assert_eq!(out[2 * 120], 0.);
+        assert!(!mixer.is_playing(effect) && mixer.is_playing(music));
+        mixer.stop(music);
+        assert_eq!(peaks(&mix_once(&mut mixer, 10)), [0., 0.]);
+    }
+
+    #[test]
+    fn offline_backend_follows_the_simulation() {
+        let mut audio = Audio::new(Mixer::default(), OfflineBackend::default());
+        audio
+            .mixer
+            .play(&Sound::tone(220., 0.5, 0.8), PlayOptions::default());
+        for _ in 0..60 {
+            audio.update(1. / 60.);
+        }
+        assert_eq!(audio.backend.samples.len(), SAMPLE_RATE as usize * 2);
+        let (start, end) = audio.backend.samples.split_at(SAMPLE_RATE as usize);
+        assert!(peaks(start)[0] > 0.5);
+        assert_eq!(peaks(end), [0., 0.]);
+    }
+
+    #[test]
+    fn resampling_keeps_the_duration() {
+        let sound = Sound::resampled(&vec![0.5; 22_050], 22_050);
+        assert!((sound.duration() - 1.).abs() < 1e-3);
+        assert!(sound.samples().iter().all(|s| *s == 0.5));
+    }
+}
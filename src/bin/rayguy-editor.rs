@@ -0,0 +1,202 @@
+//! Tile map editor with a live preview, see `rayguy::editor`.
+//!
+//! Usage: `rayguy-editor LEVEL [WIDTH HEIGHT]`. LEVEL is opened if it exists, otherwise a new map surrounded by
+//! walls (24 x 24 unless a size is given) is created and saved to it with `Ctrl S`.
+
+use std::{env, f64::consts::PI, num::NonZeroU32, path::Path};
+
+use rayguy::{
+    editor::{self, Editor, Tool},
+    framebuffer::Framebuffer,
+    map::Map,
+    scale::{self, ScaleMode},
+    texture::test_textures,
+};
+use winit::{
+    dpi::LogicalSize,
+    event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+const USAGE: &str = "usage: rayguy-editor LEVEL [WIDTH HEIGHT]";
+// Q and E turn the spawn by this much
+const SPAWN_TURN: f64 = PI / 12.;
+
+fn open(path: &str, size: &[String]) -> Option<Map> {
+    if Path::new(path).exists() {
+        return Map::load(path).map_err(|e| eprintln!("{path}: {e}")).ok();
+    }
+    let size: Option<Vec<usize>> = size.iter().map(|s| s.parse().ok()).collect();
+    match size.as_deref() {
+        Some([]) => Some(Editor::new_map(24, 24)),
+        Some(&[width, height]) if width >= 3 && height >= 3 => Some(Editor::new_map(width, height)),
+        _ => {
+            eprintln!("{USAGE}\nmaps are at least 3 x 3 cells");
+            None
+        }
+    }
+}
+
+fn save(editor: &mut Editor, path: &str, texture_count: usize) {
+    if let Err(e) = editor.map.save(path) {
+        eprintln!("Couldn't save {path}: {e}");
+        return;
+    }
+    editor.mark_saved();
+    println!("Saved {path}");
+    for diagnostic in editor.map.validate(texture_count) {
+        println!("Warning, {diagnostic}");
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(path) = args.first().cloned() else {
+        eprintln!("{USAGE}");
+        return;
+    };
+    let Some(map) = open(&path, &args[1..]) else {
+        return;
+    };
+
+    let textures = test_textures();
+    let mut editor = Editor::new(map, textures.len());
+    let mut framebuffer = Framebuffer::new(editor::WIDTH, editor::HEIGHT);
+    let scale_mode = ScaleMode::default();
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_inner_size(LogicalSize::new(
+            editor::WIDTH as u32 * 2,
+            editor::HEIGHT as u32 * 2,
+        ))
+        .with_title(format!("rayguy-editor - {path}"))
+        .build(&event_loop)
+        .unwrap();
+
+    let context = unsafe { softbuffer::Context::new(&window) }.unwrap();
+    let mut surface = unsafe { softbuffer::Surface::new(&context, &window) }.unwrap();
+
+    // Cursor position on the framebuffer and the held buttons, painting continues while one is held
+    let mut cursor = None;
+    let mut painting = false;
+    let mut erasing = false;
+    let mut ctrl = false;
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Wait;
+        match event {
+            Event::RedrawRequested(window_id) if window_id == window.id() => {
+                let size = window.inner_size();
+                let (Some(width), Some(height)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                else {
+                    return;
+                };
+                surface.resize(width, height).unwrap();
+                editor.draw(&mut framebuffer, &textures);
+                let mut buffer = surface.buffer_mut().unwrap();
+                scale::present(
+                    &framebuffer,
+                    scale_mode,
+                    &mut buffer,
+                    size.width as usize,
+                    size.height as usize,
+                );
+                buffer.present().unwrap();
+            }
+            Event::WindowEvent { window_id, event } if window_id == window.id() => {
+                match event {
+                    WindowEvent::CloseRequested => {
+                        if editor.modified() {
+                            eprintln!("Closed with unsaved changes");
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                    WindowEvent::ModifiersChanged(modifiers) => ctrl = modifiers.ctrl(),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        let size = window.inner_size();
+                        let source = (framebuffer.width(), framebuffer.height());
+                        cursor = scale_mode
+                            .viewport(source, (size.width as usize, size.height as usize))
+                            .to_source(source, position.x as usize, position.y as usize);
+                        editor.hover(cursor);
+                        if let (Some((x, y)), true) = (cursor, painting || erasing) {
+                            editor.drag(x, y, erasing);
+                        }
+                    }
+                    WindowEvent::CursorLeft { .. } => {
+                        cursor = None;
+                        editor.hover(None);
+                    }
+                    WindowEvent::MouseInput { state, button, .. } => {
+                        let pressed = state == ElementState::Pressed;
+                        match button {
+                            MouseButton::Left => painting = pressed,
+                            MouseButton::Right => erasing = pressed,
+                            _ => return,
+                        }
+                        if let (Some((x, y)), true) = (cursor, pressed) {
+                            editor.press(x, y, button == MouseButton::Right);
+                        }
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(key),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        let step = editor.zoom as i32;
+                        match key {
+                            VirtualKeyCode::S if ctrl => save(&mut editor, &path, textures.len()),
+                            VirtualKeyCode::W => editor.tool = Tool::Wall,
+                            VirtualKeyCode::F => editor.tool = Tool::Floor,
+                            VirtualKeyCode::C => editor.tool = Tool::Ceiling,
+                            VirtualKeyCode::D => editor.tool = Tool::Door,
+                            VirtualKeyCode::N => editor.tool = Tool::Entity,
+                            VirtualKeyCode::P => editor.tool = Tool::Spawn,
+                            VirtualKeyCode::X => editor.tool = Tool::Erase,
+                            VirtualKeyCode::K => editor.next_entity_kind(),
+                            VirtualKeyCode::Q => editor.rotate_spawn(SPAWN_TURN),
+                            VirtualKeyCode::E => editor.rotate_spawn(-SPAWN_TURN),
+                            VirtualKeyCode::Left => editor.scroll_by(step, 0),
+                            VirtualKeyCode::Right => editor.scroll_by(-step, 0),
+                            VirtualKeyCode::Up => editor.scroll_by(0, step),
+                            VirtualKeyCode::Down => editor.scroll_by(0, -step),
+                            VirtualKeyCode::PageUp => editor.zoom_in(),
+                            VirtualKeyCode::PageDown => editor.zoom_out(),
+                            _ => {
+                                let digits = [
+                                    VirtualKeyCode::Key1,
+                                    VirtualKeyCode::Key2,
+                                    VirtualKeyCode::Key3,
+                                    VirtualKeyCode::Key4,
+                                    VirtualKeyCode::Key5,
+                                    VirtualKeyCode::Key6,
+                                    VirtualKeyCode::Key7,
+                                    VirtualKeyCode::Key8,
+                                    VirtualKeyCode::Key9,
+                                ];
+                                match digits.iter().position(|digit| *digit == key) {
+                                    Some(texture) if texture < textures.len() => {
+                                        editor.texture = texture
+                                    }
+                                    _ => return,
+                                }
+                            }
+                        }
+                    }
+                    WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {}
+                    _ => return,
+                }
+                window.request_redraw();
+            }
+            _ => {}
+        }
+    });
+}
@@ -0,0 +1,54 @@
+//! Checks level files for mistakes before they are shipped, see `Map::validate`.
+//!
+//! Usage: `rayguy-validate [--textures COUNT] LEVEL...`, the texture count defaults to the built-in textures.
+//! Prints one line per problem and exits with an error if any level has problems or can't be read.
+
+use std::{env, process::ExitCode};
+
+use rayguy::{map::Map, texture::test_textures};
+
+fn main() -> ExitCode {
+    let mut texture_count = test_textures().len();
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--textures" {
+            match args.next().and_then(|count| count.parse().ok()) {
+                Some(count) => texture_count = count,
+                None => {
+                    eprintln!("--textures needs a number");
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: rayguy-validate [--textures COUNT] LEVEL...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut failed = false;
+    for path in &paths {
+        let map = match Map::load(path) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                failed = true;
+                continue;
+            }
+        };
+        let diagnostics = map.validate(texture_count);
+        for diagnostic in &diagnostics {
+            println!("{path}: {diagnostic}");
+        }
+        failed |= !diagnostics.is_empty();
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
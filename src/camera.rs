@@ -0,0 +1,104 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::math::Vec2;
+
+/// The direction and camera plane vectors the renderer works with are derived from `yaw` and `fov` whenever
+/// they are needed, so turning never changes their length or the angle between them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub pos: Vec2,
+    // Direction the camera looks at, in radians counter-clockwise from +x, kept in -PI..PI by rotate
+    pub yaw: f64,
+    // Horizontal field of view in radians
+    pub fov: f64,
+    // Vertical eye offset from the middle of the wall height, in wall heights (used for head bob)
+    pub z: f64,
+}
+
+impl Camera {
+    pub fn new(pos: Vec2, yaw: f64, fov: f64) -> Self {
+        Self {
+            pos,
+            yaw,
+            fov,
+            z: 0.,
+        }
+    }
+
+    /// Camera with the direction of `dir` and the field of view `2 * atan(|plane| / |dir|)`, `plane` is
+    /// expected to point to the right of `dir`.
+    pub fn from_vectors(pos: Vec2, dir: Vec2, plane: Vec2) -> Self {
+        Self::new(
+            pos,
+            dir.angle(),
+            2. * (plane.length() / dir.length()).atan(),
+        )
+    }
+
+    /// Unit vector the camera looks along.
+    pub fn dir(&self) -> Vec2 {
+        Vec2::from_angle(self.yaw)
+    }
+
+    /// The 2d raycaster version of camera plane, perpendicular to `dir` and pointing to the right side of the
+    /// screen. Its length is `tan(fov / 2)`.
+    pub fn plane(&self) -> Vec2 {
+        -self.dir().perpendicular() * (self.fov / 2.).tan()
+    }
+
+    /// Turns the camera `radian` to the left.
+    pub fn rotate(&mut self, radian: f64) {
+        self.yaw = wrap_angle(self.yaw + radian);
+    }
+
+    /// Linear interpolation between two camera states, used to render between two simulation ticks.
+    /// The yaw takes the short way around.
+    pub fn lerp(&self, other: &Camera, t: f64) -> Camera {
+        Camera {
+            pos: self.pos.lerp(other.pos, t),
+            yaw: wrap_angle(self.yaw + wrap_angle(other.yaw - self.yaw) * t),
+            fov: self.fov + (other.fov - self.fov) * t,
+            z: self.z + (other.z - self.z) * t,
+        }
+    }
+}
+
+// Same angle in -PI..PI
+fn wrap_angle(radian: f64) -> f64 {
+    (radian + PI).rem_euclid(TAU) - PI
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spinning_keeps_fov() {
+        let fov = 66f64.to_radians();
+        let mut camera = Camera::new(Vec2::new(0., 0.), 0., fov);
+        for i in 0..100_000 {
+            // Uneven steps in both directions, like mouse input
+            camera.rotate(if i % 3 == 0 { -0.0137 } else { 0.0291 });
+            let (dir, plane) = (camera.dir(), camera.plane());
+            assert!((dir.length() - 1.).abs() < 1e-12);
+            assert!(dir.dot(plane).abs() < 1e-12);
+            assert!((2. * (plane.length() / dir.length()).atan() - fov).abs() < 1e-12);
+            assert!((-PI..PI).contains(&camera.yaw));
+        }
+    }
+
+    #[test]
+    fn from_vectors_round_trips() {
+        let camera =
+            Camera::from_vectors(Vec2::new(1., 2.), Vec2::new(-1., 0.), Vec2::new(0., 0.66));
+        assert!((camera.dir() - Vec2::new(-1., 0.)).length() < 1e-12);
+        assert!((camera.plane() - Vec2::new(0., 0.66)).length() < 1e-12);
+    }
+
+    #[test]
+    fn lerp_takes_short_way() {
+        let a = Camera::new(Vec2::new(0., 0.), PI - 0.1, 1.);
+        let b = Camera::new(Vec2::new(0., 0.), -PI + 0.1, 1.);
+        assert!((a.lerp(&b, 0.5).yaw.abs() - PI).abs() < 1e-12);
+    }
+}
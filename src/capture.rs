@@ -0,0 +1,375 @@
+//! Writing frames to image files, for screenshots and for recording frame sequences that can be turned into
+//! videos offline (for example `ffmpeg -i frames/frame-%05d.png video.mp4`).
+//!
+//! Both formats are written without any dependencies. PNG files are not compressed, which keeps the encoder
+//! small and fast enough to record every frame.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::framebuffer::Framebuffer;
+
+/// Image file formats frames can be saved as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Ppm => "ppm",
+        }
+    }
+}
+
+/// Writes 0RGB `pixels` as a binary PPM (P6) image.
+pub fn write_ppm(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[u32],
+) -> io::Result<()> {
+    write!(writer, "P6\n{width} {height}\n255\n")?;
+    writer.write_all(&rgb_bytes(&pixels[..width * height]))
+}
+
+/// Reads a binary PPM (P6) image with 8 bits per channel, as written by [`write_ppm`].
+pub fn read_ppm(reader: &mut impl Read) -> io::Result<Framebuffer> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    // Header fields are separated by whitespace and may be interrupted by comments, a single whitespace
+    // character separates the header from the pixels
+    let mut fields = Vec::with_capacity(4);
+    let mut i = 0;
+    while fields.len() < 4 {
+        match data.get(i) {
+            None => return Err(invalid("truncated header")),
+            Some(b'#') => {
+                while data.get(i).is_some_and(|c| *c != b'\n') {
+                    i += 1;
+                }
+            }
+            Some(c) if c.is_ascii_whitespace() => i += 1,
+            Some(_) => {
+                let start = i;
+                while data.get(i).is_some_and(|c| !c.is_ascii_whitespace()) {
+                    i += 1;
+                }
+                fields.push(&data[start..i]);
+            }
+        }
+    }
+    i += 1;
+
+    if fields[0] != b"P6" {
+        return Err(invalid("not a binary PPM image"));
+    }
+    let number = |field: &[u8]| {
+        std::str::from_utf8(field)
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| invalid("invalid number in header"))
+    };
+    let (width, height, max) = (number(fields[1])?, number(fields[2])?, number(fields[3])?);
+    if max != 255 {
+        return Err(invalid("only 8 bits per channel are supported"));
+    }
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| invalid("image too large"))?;
+    let bytes = data
+        .get(i..)
+        .and_then(|pixels| pixels.get(..size))
+        .ok_or_else(|| invalid("truncated pixel data"))?;
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    for (pixel, rgb) in framebuffer
+        .pixels_mut()
+        .iter_mut()
+        .zip(bytes.chunks_exact(3))
+    {
+        *pixel = ((rgb[0] as u32) << 16) | ((rgb[1] as u32) << 8) | rgb[2] as u32;
+    }
+    Ok(framebuffer)
+}
+
+/// Writes 0RGB `pixels` as an 8-bit RGB PNG image.
+pub fn write_png(
+    writer: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[u32],
+) -> io::Result<()> {
+    writer.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, default compression, filtering and no interlacing
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    // Every row starts with its filter type, 0 is none
+    let mut scanlines = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels[..width * height].chunks_exact(width.max(1)) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(&rgb_bytes(row));
+    }
+    write_chunk(writer, b"IDAT", &zlib_stored(&scanlines))?;
+
+    write_chunk(writer, b"IEND", &[])
+}
+
+fn rgb_bytes(pixels: &[u32]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|color| [(color >> 16) as u8, (color >> 8) as u8, *color as u8])
+        .collect()
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(data)?;
+    let crc = crc32(crc32(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    writer.write_all(&crc.to_be_bytes())
+}
+
+// zlib stream made of uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = u16::MAX as usize;
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    // Deflate with a 32K window, no preset dictionary, check bits so the header is a multiple of 31
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        // An empty stream still needs one final block
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// First `{prefix}-0001.{extension}`, `{prefix}-0002.{extension}`... in `dir` that doesn't exist yet, so
+/// screenshots never overwrite each other.
+pub fn next_free_path(dir: impl AsRef<Path>, prefix: &str, format: ImageFormat) -> PathBuf {
+    (1..)
+        .map(|i| {
+            dir.as_ref()
+                .join(format!("{prefix}-{i:04}.{}", format.extension()))
+        })
+        .find(|path| !path.exists())
+        .expect("ran out of screenshot numbers")
+}
+
+/// Saves every frame it's given as a numbered image in a directory.
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    dir: PathBuf,
+    format: ImageFormat,
+    next: u32,
+}
+
+impl FrameRecorder {
+    /// Creates `dir` if needed. Existing frames in it are overwritten.
+    pub fn new(dir: impl Into<PathBuf>, format: ImageFormat) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            format,
+            next: 0,
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Number of frames recorded so far.
+    pub fn frames(&self) -> u32 {
+        self.next
+    }
+
+    /// Saves `framebuffer` as the next frame and returns where it was written.
+    pub fn record(&mut self, framebuffer: &Framebuffer) -> io::Result<PathBuf> {
+        let path = self.dir.join(format!(
+            "frame-{:05}.{}",
+            self.next,
+            self.format.extension()
+        ));
+        framebuffer.save(&path, self.format)?;
+        self.next += 1;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 5;
+    const HEIGHT: usize = 3;
+
+    fn pixels() -> Vec<u32> {
+        (0..(WIDTH * HEIGHT) as u32)
+            .map(|i| (i * 0x0a0b0c) ^ 0x123456)
+            .map(|color| color & 0xffffff)
+            .collect()
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let mut data = Vec::new();
+        write_ppm(&mut data, WIDTH, HEIGHT, &pixels()).unwrap();
+        let framebuffer = read_ppm(&mut data.as_slice()).unwrap();
+        assert_eq!((framebuffer.width(), framebuffer.height()), (WIDTH, HEIGHT));
+        assert_eq!(framebuffer.pixels(), pixels());
+
+        let with_comment = b"P6 # comment\n2 1\n255\n\x01\x02\x03\x04\x05\x06";
+        let framebuffer = read_ppm(&mut &with_comment[..]).unwrap();
+        assert_eq!(framebuffer.pixels(), [0x010203, 0x040506]);
+    }
+
+    #[test]
+    fn invalid_ppm() {
+        for data in [
+            &b""[..],
+            b"P6\n2 1\n",
+            b"P3\n2 1\n255\n\x01\x02\x03\x04\x05\x06",
+            b"P6\n2 1\n65535\n\x01\x02\x03\x04\x05\x06",
+            b"P6\n2 x\n255\n",
+            b"P6\n2 1\n255\n\x01\x02\x03",
+            b"P6\n2 1\n255",
+            // Sizes whose byte count overflows
+            b"P6\n18446744073709551615 2\n255\n",
+            b"P6\n6148914691236517206 1\n255\n",
+        ] {
+            let error = read_ppm(&mut &data[..]).unwrap_err();
+            assert_eq!(
+                error.kind(),
+                io::ErrorKind::InvalidData,
+                "{}",
+                String::from_utf8_lossy(data)
+            );
+        }
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(0xffff_ffff, b"123456789") ^ 0xffff_ffff, 0xcbf4_3926);
+        assert_eq!(crc32(0xffff_ffff, b"") ^ 0xffff_ffff, 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b"123456789"), 0x091e_01de);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn png_structure() {
+        let mut data = Vec::new();
+        write_png(&mut data, WIDTH, HEIGHT, &pixels()).unwrap();
+        assert_eq!(&data[..8], b"\x89PNG\r\n\x1a\n");
+
+        // Walk the chunks and check their lengths and checksums
+        let mut chunks = Vec::new();
+        let mut rest = &data[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, body) = (&rest[4..8], &rest[8..8 + len]);
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            assert_eq!(crc32(crc32(0xffff_ffff, kind), body) ^ 0xffff_ffff, crc);
+            chunks.push((kind.to_vec(), body.to_vec()));
+            rest = &rest[12 + len..];
+        }
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind.as_slice()).collect();
+        assert_eq!(kinds, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(WIDTH as u32).to_be_bytes());
+        header.extend_from_slice(&(HEIGHT as u32).to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        assert_eq!(chunks[0].1, header);
+
+        // One stored block holding the filtered scanlines
+        let idat = &chunks[1].1;
+        let scanlines = (WIDTH * 3 + 1) * HEIGHT;
+        assert_eq!(&idat[..2], [0x78, 0x01]);
+        assert_eq!(u16::from_be_bytes([0x78, 0x01]) % 31, 0);
+        assert_eq!(idat[2], 1);
+        assert_eq!(&idat[3..5], (scanlines as u16).to_le_bytes());
+        let stored = &idat[7..7 + scanlines];
+        for (row, expected) in stored
+            .chunks_exact(WIDTH * 3 + 1)
+            .zip(pixels().chunks(WIDTH))
+        {
+            assert_eq!(row[0], 0);
+            assert_eq!(row[1..], rgb_bytes(expected));
+        }
+        assert_eq!(idat[7 + scanlines..], adler32(stored).to_be_bytes());
+    }
+
+    #[test]
+    fn large_data_is_split_into_blocks() {
+        let data: Vec<u8> = (0..u16::MAX as usize * 2 + 10).map(|i| i as u8).collect();
+        let zlib = zlib_stored(&data);
+        let mut stored = Vec::new();
+        let mut rest = &zlib[2..zlib.len() - 4];
+        loop {
+            let len = u16::from_le_bytes([rest[1], rest[2]]) as usize;
+            assert_eq!(!u16::from_le_bytes([rest[3], rest[4]]) as usize, len);
+            stored.extend_from_slice(&rest[5..5 + len]);
+            let last = rest[0] == 1;
+            rest = &rest[5 + len..];
+            if last {
+                break;
+            }
+        }
+        assert!(rest.is_empty());
+        assert_eq!(stored, data);
+        assert_eq!(
+            zlib_stored(&[]),
+            [0x78, 0x01, 1, 0, 0, 0xff, 0xff, 0, 0, 0, 1]
+        );
+    }
+}
@@ -4,6 +4,28 @@ pub const TEST_LEVEL_HEIGHT: usize = 24;
 pub const TEXTURE_WIDTH: u32 = 64;
 pub const TEXTURE_HEIGHT: u32 = 64;
 
+// Simulation ticks per second, independent of the frame rate
+pub const TICK_RATE: u32 = 60;
+// Frame cap used when the monitor refresh rate is unknown or VSYNC is off
+pub const FRAME_CAP: u32 = 144;
+// Rays give up after this distance (in multiples of the ray direction, roughly cells) without hitting a wall
+pub const MAX_RAY_DISTANCE: f64 = 64.;
+// Horizontal field of view in degrees
+pub const FOV: f64 = 66.;
+
+// Pace frames to the monitor refresh rate instead of FRAME_CAP
+pub const VSYNC: bool = false;
+
+// Key bindings are read from here if the file exists, see `input::Bindings` for the format
+pub const BINDINGS_PATH: &str = "bindings.cfg";
+
+// Screenshots (F12) are numbered PNG files in SCREENSHOT_DIR, recordings (F11) numbered frames in RECORDING_DIR
+pub const SCREENSHOT_DIR: &str = ".";
+pub const RECORDING_DIR: &str = "frames";
+
+// Quicksaves (F5) go here and are loaded back with F9, see `save::SaveGame` for the format
+pub const SAVE_PATH: &str = "quicksave.sav";
+
 pub const SCREEN_WIDTH: u32 = SURFACE_WIDTH * 4;
 pub const SCREEN_HEIGHT: u32 = SURFACE_HEIGHT * 4;
 // pub const SCREEN_WIDTH: u32 = 640;
@@ -15,6 +37,9 @@ pub const SURFACE_HEIGHT: u32 = 144;
 // pub const SURFACE_WIDTH: u32 = 640;
 // pub const SURFACE_HEIGHT: u32 = 480;
 
+// Internal resolutions the demo cycles through with F3, it starts at SURFACE_WIDTH x SURFACE_HEIGHT
+pub const RESOLUTIONS: [(u32, u32); 4] = [(160, 144), (320, 200), (320, 240), (640, 480)];
+
 #[rustfmt::skip]
 pub const TEST_LEVEL: [[i8; TEST_LEVEL_WIDTH]; TEST_LEVEL_HEIGHT] = [
   [4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,4,7,7,7,7,7,7,7,7],
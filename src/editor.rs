@@ -0,0 +1,497 @@
+//! State and drawing of the level editor (`rayguy-editor`), kept out of the binary so it can be tested without a
+//! window.
+//!
+//! The screen is split into the map grid on the left, a preview rendered from the player spawn on the right and
+//! the texture palette at the bottom. All positions taken by [`Editor`] are pixels of its [`WIDTH`] x [`HEIGHT`]
+//! framebuffer.
+
+use std::f64::consts::PI;
+
+use crate::{
+    camera::Camera,
+    consts::{FOV, TEXTURE_HEIGHT, TEXTURE_WIDTH},
+    font::{Font, DEFAULT_FONT},
+    framebuffer::Framebuffer,
+    map::{Entity, Map},
+    math::Vec2,
+    render,
+};
+
+pub const WIDTH: usize = 640;
+pub const HEIGHT: usize = 400;
+
+// Map grid
+const GRID_WIDTH: usize = 400;
+const GRID_HEIGHT: usize = 364;
+// Preview of the level from the player spawn, in the aspect ratio of the game
+const PREVIEW_X: i32 = 408;
+const PREVIEW_Y: i32 = 8;
+const PREVIEW_WIDTH: usize = 224;
+const PREVIEW_HEIGHT: usize = 202;
+// Texture palette below the grid
+const PALETTE_Y: i32 = 368;
+const SWATCH_SIZE: usize = 28;
+const SWATCH_SPACING: usize = 32;
+
+const MIN_ZOOM: usize = 4;
+const MAX_ZOOM: usize = 48;
+
+const BACKGROUND_COLOR: u32 = 0x202020;
+const EMPTY_COLOR: u32 = 0x505050;
+const GRID_LINE_COLOR: u32 = 0x303030;
+const HOVER_COLOR: u32 = 0xffffff;
+const DOOR_COLOR: u32 = 0xffd000;
+const ENTITY_COLOR: u32 = 0xff4040;
+const SPAWN_COLOR: u32 = 0x40ff40;
+const SELECTED_COLOR: u32 = 0xffffff;
+const TEXT_COLOR: u32 = 0xffffff;
+const DIM_TEXT_COLOR: u32 = 0xa0a0a0;
+
+/// Kinds of entities the entity tool places, cycled with [`Editor::next_entity_kind`].
+pub const ENTITY_KINDS: [&str; 3] = ["enemy", "pickup", "decoration"];
+pub const ENTITY_RADIUS: f64 = 0.3;
+
+/// What clicking a cell of the grid does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Wall with the selected texture.
+    Wall,
+    /// Floor with the selected texture, texture 0 is the default checkerboard floor.
+    Floor,
+    /// Ceilings use texture 7 minus the floor texture, so this sets the floor that goes with the selected
+    /// ceiling texture.
+    Ceiling,
+    /// Closed door with the selected texture, or a new texture for an existing door.
+    Door,
+    /// Entity of the selected kind in the center of the cell.
+    Entity,
+    /// Moves the player spawn to the center of the cell.
+    Spawn,
+    /// Empties the cell and removes doors and entities in it.
+    Erase,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 7] = [
+        Tool::Wall,
+        Tool::Floor,
+        Tool::Ceiling,
+        Tool::Door,
+        Tool::Entity,
+        Tool::Spawn,
+        Tool::Erase,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Wall => "Wall",
+            Tool::Floor => "Floor",
+            Tool::Ceiling => "Ceiling",
+            Tool::Door => "Door",
+            Tool::Entity => "Entity",
+            Tool::Spawn => "Spawn",
+            Tool::Erase => "Erase",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Editor {
+    pub map: Map,
+    pub tool: Tool,
+    /// Selected texture of the palette.
+    pub texture: usize,
+    /// Index into [`ENTITY_KINDS`].
+    pub entity_kind: usize,
+    /// Size of a cell in pixels.
+    pub zoom: usize,
+    /// Position of the top left corner of the map in the grid.
+    pub scroll: (i32, i32),
+    texture_count: usize,
+    // Cell under the cursor
+    hover: Option<(i32, i32)>,
+    modified: bool,
+    preview: Framebuffer,
+    font: Font<'static>,
+}
+
+impl Editor {
+    /// Editor for `map` with a palette of `texture_count` textures.
+    pub fn new(map: Map, texture_count: usize) -> Self {
+        let zoom = (GRID_WIDTH / map.width().max(1))
+            .min(GRID_HEIGHT / map.height().max(1))
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        Self {
+            map,
+            tool: Tool::Wall,
+            texture: 0,
+            entity_kind: 0,
+            zoom,
+            scroll: (0, 0),
+            texture_count,
+            hover: None,
+            modified: false,
+            preview: Framebuffer::new(PREVIEW_WIDTH, PREVIEW_HEIGHT),
+            font: DEFAULT_FONT,
+        }
+    }
+
+    /// New `width` x `height` map surrounded by walls.
+    pub fn new_map(width: usize, height: usize) -> Map {
+        let mut map = Map::new(width, height);
+        for x in 0..width {
+            map.set(x, 0, 1);
+            map.set(x, height - 1, 1);
+        }
+        for y in 0..height {
+            map.set(0, y, 1);
+            map.set(width - 1, y, 1);
+        }
+        map.spawn.pos = Vec2::new(width as f64 / 2., height as f64 / 2.);
+        map
+    }
+
+    /// Whether the map changed since the editor was created or [`Editor::mark_saved`] was called.
+    pub fn modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.modified = false;
+    }
+
+    /// Cell of the map at pixel `(x, y)`, `None` outside of the map or the grid.
+    pub fn cell_at(&self, x: usize, y: usize) -> Option<(i32, i32)> {
+        if x >= GRID_WIDTH || y >= GRID_HEIGHT {
+            return None;
+        }
+        let cell_x = (x as i32 - self.scroll.0).div_euclid(self.zoom as i32);
+        let cell_y = (y as i32 - self.scroll.1).div_euclid(self.zoom as i32);
+        self.map.get(cell_x, cell_y).map(|_| (cell_x, cell_y))
+    }
+
+    /// Texture of the palette swatch at pixel `(x, y)`.
+    pub fn palette_at(&self, x: usize, y: usize) -> Option<usize> {
+        let y = y as i32 - PALETTE_Y;
+        if y < 0 || y as usize >= SWATCH_SIZE || x % SWATCH_SPACING < 2 {
+            return None;
+        }
+        let texture = x / SWATCH_SPACING;
+        (texture < self.texture_count).then_some(texture)
+    }
+
+    /// Moves the cursor to pixel `(x, y)`, `None` when it left the window.
+    pub fn hover(&mut self, cursor: Option<(usize, usize)>) {
+        self.hover = cursor.and_then(|(x, y)| self.cell_at(x, y));
+    }
+
+    /// Mouse button press at pixel `(x, y)`: selects a palette texture or uses the tool, or the eraser when
+    /// `erase` is set.
+    pub fn press(&mut self, x: usize, y: usize, erase: bool) {
+        if let Some(texture) = self.palette_at(x, y) {
+            self.texture = texture;
+        } else {
+            self.drag(x, y, erase);
+        }
+    }
+
+    /// Mouse movement to pixel `(x, y)` with a button held, paints every cell the cursor passes.
+    pub fn drag(&mut self, x: usize, y: usize, erase: bool) {
+        if let Some((cell_x, cell_y)) = self.cell_at(x, y) {
+            let tool = if erase { Tool::Erase } else { self.tool };
+            self.apply(tool, cell_x as usize, cell_y as usize);
+        }
+    }
+
+    /// Uses `tool` on the cell `(x, y)`.
+    pub fn apply(&mut self, tool: Tool, x: usize, y: usize) {
+        let before = self.map.clone();
+        let center = Vec2::new(x as f64 + 0.5, y as f64 + 0.5);
+        let door = self.map.door_at(x as i32, y as i32);
+        let texture = self.texture as i8;
+
+        match tool {
+            Tool::Door => match door {
+                Some(door) => self.map.set_door_cell(door, texture + 1),
+                None => {
+                    self.map.add_door(x, y, texture + 1);
+                }
+            },
+            Tool::Entity => {
+                if self.entities_in(x, y).next().is_none() {
+                    self.map.entities.push(Entity {
+                        kind: ENTITY_KINDS[self.entity_kind].to_string(),
+                        pos: center,
+                        radius: ENTITY_RADIUS,
+                    });
+                }
+            }
+            Tool::Spawn => self.map.spawn.pos = center,
+            Tool::Wall | Tool::Floor | Tool::Ceiling | Tool::Erase => {
+                let cell = match tool {
+                    Tool::Wall => texture + 1,
+                    Tool::Floor => -texture,
+                    // Texture 7 needs floor 0, the default floor has the ceiling textures 5 and 3 though
+                    Tool::Ceiling => -(7 - texture).max(0),
+                    _ => 0,
+                };
+                if let Some(door) = door {
+                    self.map.remove_door(door);
+                }
+                if tool == Tool::Erase {
+                    self.map.entities.retain(|entity| {
+                        (entity.pos.x.floor() as usize, entity.pos.y.floor() as usize) != (x, y)
+                    });
+                }
+                self.map.set(x, y, cell);
+            }
+        }
+        self.modified |= self.map != before;
+    }
+
+    fn entities_in(&self, x: usize, y: usize) -> impl Iterator<Item = &Entity> {
+        self.map.entities.iter().filter(move |entity| {
+            (entity.pos.x.floor() as usize, entity.pos.y.floor() as usize) == (x, y)
+        })
+    }
+
+    /// Turns the player spawn `radian` to the left.
+    pub fn rotate_spawn(&mut self, radian: f64) {
+        self.map.spawn.yaw = (self.map.spawn.yaw + radian + PI).rem_euclid(2. * PI) - PI;
+        self.modified = true;
+    }
+
+    pub fn next_entity_kind(&mut self) {
+        self.entity_kind = (self.entity_kind + 1) % ENTITY_KINDS.len();
+    }
+
+    pub fn scroll_by(&mut self, dx: i32, dy: i32) {
+        self.scroll.0 += dx;
+        self.scroll.1 += dy;
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom + 2).min(MAX_ZOOM);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = self.zoom.saturating_sub(2).max(MIN_ZOOM);
+    }
+
+    pub fn draw(&mut self, framebuffer: &mut Framebuffer, textures: &[Vec<u32>]) {
+        framebuffer.fill(BACKGROUND_COLOR);
+        self.draw_grid(framebuffer, textures);
+
+        let spawn = self.map.spawn;
+        let camera = Camera::new(spawn.pos, spawn.yaw, FOV.to_radians());
+        render::render(&mut self.preview, &camera, &self.map, textures, 1.);
+        framebuffer.blit(
+            PREVIEW_X,
+            PREVIEW_Y,
+            self.preview.pixels(),
+            PREVIEW_WIDTH,
+            None,
+        );
+
+        let hover = match self.hover {
+            Some((x, y)) => format!("{x}, {y}: {}", self.map.cell(x, y)),
+            None => String::new(),
+        };
+        let info = format!(
+            "{}{}\nTexture {}\nEntity {}\n{hover}",
+            self.tool.name(),
+            if self.modified { "  (modified)" } else { "" },
+            self.texture,
+            ENTITY_KINDS[self.entity_kind],
+        );
+        let info_y = PREVIEW_Y + PREVIEW_HEIGHT as i32 + 8;
+        framebuffer.text(PREVIEW_X, info_y, &info, &self.font, TEXT_COLOR);
+        framebuffer.text(
+            PREVIEW_X,
+            info_y + 5 * self.font.line_height() as i32,
+            "W F C D N P X  tools\n1-9 or click  texture\nK  entity kind\nQ E  turn spawn\nArrows  scroll\n\
+             PgUp PgDn  zoom\nRight click  erase\nCtrl S  save",
+            &self.font,
+            DIM_TEXT_COLOR,
+        );
+
+        for (index, texture) in textures.iter().enumerate().take(self.texture_count) {
+            let x = (index * SWATCH_SPACING) as i32 + 2;
+            framebuffer.blit_scaled(
+                x,
+                PALETTE_Y,
+                (SWATCH_SIZE, SWATCH_SIZE),
+                texture,
+                TEXTURE_WIDTH as usize,
+                None,
+            );
+            if index == self.texture {
+                framebuffer.rect(
+                    x - 2,
+                    PALETTE_Y - 2,
+                    SWATCH_SIZE + 4,
+                    SWATCH_SIZE + 4,
+                    SELECTED_COLOR,
+                );
+            }
+        }
+    }
+
+    fn draw_grid(&self, framebuffer: &mut Framebuffer, textures: &[Vec<u32>]) {
+        let zoom = self.zoom;
+        let texture_size = (TEXTURE_WIDTH * TEXTURE_HEIGHT) as usize;
+        let to_screen = |x: f64, y: f64| {
+            (
+                self.scroll.0 + (x * zoom as f64) as i32,
+                self.scroll.1 + (y * zoom as f64) as i32,
+            )
+        };
+
+        let mut grid = Framebuffer::new(GRID_WIDTH, GRID_HEIGHT);
+        for y in 0..self.map.height() {
+            for x in 0..self.map.width() {
+                let (sx, sy) = to_screen(x as f64, y as f64);
+                let cell = self.map.cell(x as i32, y as i32);
+                let texture = match cell {
+                    cell if cell > 0 => Some(cell as usize - 1),
+                    cell if cell < 0 => Some(cell.unsigned_abs() as usize),
+                    _ => None,
+                };
+                match texture
+                    .and_then(|t| textures.get(t))
+                    .filter(|t| t.len() == texture_size)
+                {
+                    Some(texture) => grid.blit_scaled(
+                        sx,
+                        sy,
+                        (zoom, zoom),
+                        texture,
+                        TEXTURE_WIDTH as usize,
+                        None,
+                    ),
+                    None => grid.fill_rect(sx, sy, zoom, zoom, EMPTY_COLOR),
+                }
+                // Floors are drawn darker than walls, like in the game
+                if cell < 0 {
+                    grid.darken_rect(sx, sy, zoom, zoom);
+                }
+                grid.rect(sx, sy, zoom + 1, zoom + 1, GRID_LINE_COLOR);
+            }
+        }
+
+        for door in self.map.doors() {
+            let (sx, sy) = to_screen(door.x as f64, door.y as f64);
+            grid.rect(sx + 1, sy + 1, zoom - 1, zoom - 1, DOOR_COLOR);
+            if door.open {
+                grid.line(
+                    sx + 1,
+                    sy + 1,
+                    sx + zoom as i32 - 1,
+                    sy + zoom as i32 - 1,
+                    DOOR_COLOR,
+                );
+            }
+        }
+
+        let marker = (zoom / 3).max(2);
+        for entity in &self.map.entities {
+            let (sx, sy) = to_screen(entity.pos.x, entity.pos.y);
+            let half = marker as i32 / 2;
+            grid.fill_rect(sx - half, sy - half, marker, marker, ENTITY_COLOR);
+        }
+
+        let spawn = self.map.spawn;
+        let (sx, sy) = to_screen(spawn.pos.x, spawn.pos.y);
+        let tip = spawn.pos + Vec2::from_angle(spawn.yaw) * 0.8;
+        let (tx, ty) = to_screen(tip.x, tip.y);
+        grid.line(sx, sy, tx, ty, SPAWN_COLOR);
+        grid.fill_rect(sx - 1, sy - 1, 3, 3, SPAWN_COLOR);
+
+        if let Some((x, y)) = self.hover {
+            let (sx, sy) = to_screen(x as f64, y as f64);
+            grid.rect(sx, sy, zoom + 1, zoom + 1, HOVER_COLOR);
+        }
+
+        framebuffer.blit(0, 0, grid.pixels(), GRID_WIDTH, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor() -> Editor {
+        Editor::new(Editor::new_map(8, 6), 8)
+    }
+
+    #[test]
+    fn tools_edit_the_map() {
+        let mut editor = editor();
+        editor.texture = 3;
+        editor.apply(Tool::Wall, 2, 2);
+        editor.apply(Tool::Floor, 3, 2);
+        editor.apply(Tool::Ceiling, 4, 2);
+        assert_eq!(
+            [(2, 2), (3, 2), (4, 2)].map(|(x, y)| editor.map.cell(x, y)),
+            [4, -3, -4]
+        );
+        assert!(editor.modified());
+
+        editor.apply(Tool::Door, 5, 2);
+        editor.apply(Tool::Entity, 6, 3);
+        editor.apply(Tool::Entity, 6, 3);
+        editor.apply(Tool::Spawn, 1, 4);
+        assert_eq!(editor.map.door_at(5, 2), Some(0));
+        assert_eq!(editor.map.cell(5, 2), 4);
+        assert_eq!(editor.map.entities.len(), 1);
+        assert_eq!(editor.map.spawn.pos, Vec2::new(1.5, 4.5));
+
+        editor.apply(Tool::Erase, 5, 2);
+        editor.apply(Tool::Erase, 6, 3);
+        assert_eq!(editor.map.door_at(5, 2), None);
+        assert_eq!(editor.map.cell(5, 2), 0);
+        assert!(editor.map.entities.is_empty());
+    }
+
+    #[test]
+    fn repainting_a_door_keeps_it_open() {
+        let mut editor = editor();
+        editor.apply(Tool::Door, 3, 3);
+        editor.map.set_door_open(0, true);
+        editor.texture = 5;
+        editor.apply(Tool::Door, 3, 3);
+        assert_eq!(editor.map.doors()[0].cell, 6);
+        assert_eq!(editor.map.cell(3, 3), 0);
+    }
+
+    #[test]
+    fn mouse_picks_cells_and_textures() {
+        let mut editor = editor();
+        let zoom = editor.zoom;
+        editor.scroll = (10, 20);
+        assert_eq!(editor.cell_at(10 + 2 * zoom, 20 + zoom), Some((2, 1)));
+        assert_eq!(editor.cell_at(9, 20), None);
+        assert_eq!(editor.cell_at(GRID_WIDTH, 20), None);
+
+        editor.press(SWATCH_SPACING * 2 + 5, PALETTE_Y as usize + 5, false);
+        assert_eq!(editor.texture, 2);
+        assert_eq!(
+            editor.palette_at(SWATCH_SPACING * 9 + 5, PALETTE_Y as usize),
+            None
+        );
+
+        editor.press(10 + 2 * zoom, 20 + 2 * zoom, false);
+        assert_eq!(editor.map.cell(2, 2), 3);
+        editor.drag(10 + 2 * zoom, 20 + 2 * zoom, true);
+        assert_eq!(editor.map.cell(2, 2), 0);
+    }
+
+    #[test]
+    fn saved_maps_load_the_same() {
+        let mut editor = editor();
+        editor.apply(Tool::Door, 3, 3);
+        editor.apply(Tool::Entity, 2, 2);
+        editor.rotate_spawn(1.);
+        assert_eq!(Map::parse(&editor.map.to_string()).unwrap(), editor.map);
+    }
+}
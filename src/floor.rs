@@ -0,0 +1,324 @@
+//! Floor and ceiling casting.
+//!
+//! Every row of the screen below the horizon is a straight line on the floor (and every row above it one on
+//! the ceiling), so a row can be drawn by stepping a world position by a constant amount per pixel. This is
+//! the hottest loop of the renderer, it touches every pixel of the screen. [`draw_span`] is the straightforward
+//! reference implementation, [`draw_span_fast`] produces the same output (up to rounding at texel edges) with
+//! fixed-point stepping and texture sampling whose bounds are checked once per frame by
+//! [`FloorTextures::validate`] instead of once per pixel. Both work on any kind of texel through [`span`] and
+//! [`draw_span_fast_with`], which is how the paletted renderer shares them.
+
+use crate::{
+    camera::Camera,
+    consts::{TEXTURE_HEIGHT, TEXTURE_WIDTH},
+    map::Map,
+    math::Vec2,
+    projection::Projection,
+    render::{darken, texel},
+};
+
+/// A row of floor or ceiling as seen from the camera.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FloorRow {
+    /// World position seen by the leftmost pixel.
+    pub start: Vec2,
+    /// How much the world position advances per pixel.
+    pub step: Vec2,
+    pub is_floor: bool,
+}
+
+impl FloorRow {
+    /// The row `y` of the screen described by `projection`, `None` for the row at the horizon which is infinitely
+    /// far away.
+    pub fn new(y: usize, projection: &Projection, camera: &Camera) -> Option<Self> {
+        let (pos, dir, plane) = (camera.pos, camera.dir(), camera.plane());
+
+        // Rows below the horizon show the floor and rows above it the ceiling. The distance is the perpendicular
+        // one, the same walls are projected with.
+        let (row_distance, is_floor) = projection.row_distance(y)?;
+
+        let ray_dir_leftmost = dir - plane;
+        let ray_dir_rightmost = dir + plane;
+
+        Some(Self {
+            start: pos + ray_dir_leftmost * row_distance,
+            step: (ray_dir_rightmost - ray_dir_leftmost)
+                * (row_distance / projection.width() as f64),
+            is_floor,
+        })
+    }
+}
+
+// Texture of the floor in the given cell, the ceiling uses 7 - this.
+fn floor_texture(map: &Map, cell_x: i32, cell_y: i32) -> usize {
+    match map.cell(cell_x, cell_y) {
+        cell if cell < 0 => cell.unsigned_abs() as usize,
+        _ if cell_x.wrapping_add(cell_y).rem_euclid(2) == 0 => 2,
+        _ => 4,
+    }
+}
+
+/// Texture and texel index seen by every pixel of `row`, from the leftmost one on. The texture is `None` for
+/// ceilings above floors without a matching ceiling texture. Never ends, zip it with the pixels.
+pub fn span<'a>(row: &FloorRow, map: &'a Map) -> impl Iterator<Item = (Option<usize>, usize)> + 'a {
+    let (mut floor, step, is_floor) = (row.start, row.step, row.is_floor);
+
+    std::iter::from_fn(move || {
+        let (cell_x, cell_y) = (floor.x.floor() as i32, floor.y.floor() as i32);
+
+        let texture_x = (TEXTURE_WIDTH as f64 * (floor.x - cell_x as f64)) as usize
+            & (TEXTURE_WIDTH as usize - 1);
+        let texture_y = (TEXTURE_HEIGHT as f64 * (floor.y - cell_y as f64)) as usize
+            & (TEXTURE_HEIGHT as usize - 1);
+
+        floor.x += step.x;
+        floor.y += step.y;
+
+        let floor_texture = floor_texture(map, cell_x, cell_y);
+        let texture = if is_floor {
+            Some(floor_texture)
+        } else {
+            7usize.checked_sub(floor_texture)
+        };
+        Some((texture, TEXTURE_WIDTH as usize * texture_y + texture_x))
+    })
+}
+
+/// Reference implementation, draws `row` into `pixels`. Floors without a matching ceiling texture and missing
+/// textures are drawn black.
+pub fn draw_span(row: &FloorRow, pixels: &mut [u32], map: &Map, textures: &[Vec<u32>]) {
+    for (pixel, (texture, index)) in pixels.iter_mut().zip(span(row, map)) {
+        let color = texture.map_or(0, |texture| texel(textures, texture, index));
+        *pixel = darken(color);
+    }
+}
+
+/// Textures that have been checked to cover every texture id [`draw_span_fast`] can look up on a map.
+/// Borrows the map so it can't change while the check is relied upon. `T` is the type of a texel, 0RGB colors
+/// unless the textures are paletted.
+#[derive(Debug)]
+pub struct FloorTextures<'a, T = u32> {
+    textures: &'a [Vec<T>],
+    map: &'a Map,
+}
+
+// Derives would require T: Clone
+impl<T> Clone for FloorTextures<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for FloorTextures<'_, T> {}
+
+impl<'a, T> FloorTextures<'a, T> {
+    /// `None` if some floor or ceiling texture of `map` is missing or has the wrong size, the reference
+    /// implementation has to be used then.
+    pub fn validate(textures: &'a [Vec<T>], map: &'a Map) -> Option<Self> {
+        // Ceilings use 7 - the floor texture, so 0..=7 covers both
+        let max_floor_texture = (0..map.width() as i32)
+            .flat_map(|x| (0..map.height() as i32).map(move |y| (x, y)))
+            .map(|(x, y)| floor_texture(map, x, y))
+            .max()
+            .unwrap_or(0);
+        let texture_size = (TEXTURE_WIDTH * TEXTURE_HEIGHT) as usize;
+        let valid = max_floor_texture <= 7
+            && textures.len() >= 8
+            && textures[..8].iter().all(|t| t.len() == texture_size);
+        valid.then_some(Self { textures, map })
+    }
+}
+
+// Fixed-point numbers with 32 fractional bits. That's plenty of precision for stepping across a few thousand
+// pixels and still leaves 31 bits for the cell coordinate.
+const FRACTION_BITS: u32 = 32;
+const TEXTURE_X_SHIFT: u32 = FRACTION_BITS - TEXTURE_WIDTH.trailing_zeros();
+const TEXTURE_Y_SHIFT: u32 = FRACTION_BITS - TEXTURE_HEIGHT.trailing_zeros();
+
+fn to_fixed(v: f64) -> i64 {
+    (v * (1u64 << FRACTION_BITS) as f64) as i64
+}
+
+// Cell and texel index of a fixed-point position
+#[inline(always)]
+fn fixed_texel(x: i64, y: i64) -> (i32, i32, usize) {
+    let cell_x = (x >> FRACTION_BITS) as i32;
+    let cell_y = (y >> FRACTION_BITS) as i32;
+    let texture_x = (x >> TEXTURE_X_SHIFT) as usize & (TEXTURE_WIDTH as usize - 1);
+    let texture_y = (y >> TEXTURE_Y_SHIFT) as usize & (TEXTURE_HEIGHT as usize - 1);
+    (
+        cell_x,
+        cell_y,
+        TEXTURE_WIDTH as usize * texture_y + texture_x,
+    )
+}
+
+#[inline(always)]
+fn sample<T: Copy>(
+    textures: &FloorTextures<T>,
+    is_floor: bool,
+    cell_x: i32,
+    cell_y: i32,
+    texel: usize,
+) -> T {
+    let floor_texture = floor_texture(textures.map, cell_x, cell_y);
+    let texture = if is_floor {
+        floor_texture
+    } else {
+        7 - floor_texture
+    };
+    // SAFETY: FloorTextures::validate checked that floor_texture returns at most 7 for every cell of this map
+    // (cells outside of it wrap around to one of those or aren't floors, which gives 2 or 4) and that there are
+    // at least 8 textures, so both `floor_texture` and `7 - floor_texture` are in bounds.
+    let texture = unsafe { textures.textures.get_unchecked(texture) };
+    // SAFETY: FloorTextures::validate checked that the first 8 textures have TEXTURE_WIDTH * TEXTURE_HEIGHT
+    // pixels, fixed_texel masks both texel coordinates to that size.
+    unsafe { *texture.get_unchecked(texel) }
+}
+
+/// Same output as [`draw_span`] except for pixels that sample exactly on the edge between two texels, where
+/// fixed-point and floating point stepping can round to different sides. Cameras at arbitrary positions hit
+/// that almost never, but axis aligned ones at exactly representable positions do: `benches/floor.rs` measures
+/// 1.17% of pixels differing at 160 x 144, 0.43% at 640 x 480 and 0.28% at 1920 x 1080. About 3.5x faster
+/// than [`draw_span`] in that benchmark.
+///
+/// With the `simd` feature, positions and texel indices are computed for 8 pixels at a time in fixed-size
+/// arrays, which the compiler can turn into vector instructions. Whether that helps depends on the CPU and
+/// the row width, it has measured both slower and faster than the scalar loop, so it's off by default.
+pub fn draw_span_fast(row: &FloorRow, pixels: &mut [u32], textures: &FloorTextures) {
+    draw_span_fast_with(row, pixels, textures, darken);
+}
+
+/// [`draw_span_fast`] for any kind of texel, every pixel is set to `shade` of the texel it shows.
+#[inline(always)]
+pub fn draw_span_fast_with<T: Copy, P>(
+    row: &FloorRow,
+    pixels: &mut [P],
+    textures: &FloorTextures<T>,
+    shade: impl Fn(T) -> P,
+) {
+    let (mut x, mut y) = (to_fixed(row.start.x), to_fixed(row.start.y));
+    let (step_x, step_y) = (to_fixed(row.step.x), to_fixed(row.step.y));
+
+    #[cfg(feature = "simd")]
+    let pixels = {
+        const LANES: usize = 8;
+
+        let mut chunks = pixels.chunks_exact_mut(LANES);
+        for chunk in &mut chunks {
+            let mut cells_x = [0i32; LANES];
+            let mut cells_y = [0i32; LANES];
+            let mut texels = [0usize; LANES];
+            for lane in 0..LANES {
+                (cells_x[lane], cells_y[lane], texels[lane]) = fixed_texel(
+                    x.wrapping_add(step_x.wrapping_mul(lane as i64)),
+                    y.wrapping_add(step_y.wrapping_mul(lane as i64)),
+                );
+            }
+            x = x.wrapping_add(step_x.wrapping_mul(LANES as i64));
+            y = y.wrapping_add(step_y.wrapping_mul(LANES as i64));
+
+            for lane in 0..LANES {
+                chunk[lane] = shade(sample(
+                    textures,
+                    row.is_floor,
+                    cells_x[lane],
+                    cells_y[lane],
+                    texels[lane],
+                ));
+            }
+        }
+        chunks.into_remainder()
+    };
+
+    for pixel in pixels.iter_mut() {
+        let (cell_x, cell_y, texel) = fixed_texel(x, y);
+        x = x.wrapping_add(step_x);
+        y = y.wrapping_add(step_y);
+        *pixel = shade(sample(textures, row.is_floor, cell_x, cell_y, texel));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{PI, TAU};
+
+    use super::*;
+    use crate::{consts::FOV, generate::Rng, texture::test_textures};
+
+    // Fraction of pixels draw_span_fast may draw differently from draw_span for cameras at random positions
+    const MAX_MISMATCH: f64 = 0.001;
+
+    fn mismatches(row: &FloorRow, width: usize, textures: &FloorTextures, map: &Map) -> usize {
+        let mut reference = vec![0; width];
+        let mut fast = vec![0; width];
+        draw_span(row, &mut reference, map, textures.textures);
+        draw_span_fast(row, &mut fast, textures);
+        reference.iter().zip(&fast).filter(|(a, b)| a != b).count()
+    }
+
+    // Runs whichever of the scalar and the batched path is compiled in, test with and without `--features simd`
+    #[test]
+    fn fast_path_matches_reference() {
+        let map = Map::test_level();
+        let textures = test_textures();
+        let floor_textures = FloorTextures::validate(&textures, &map).unwrap();
+        let mut rng = Rng::new(31);
+
+        let (mut pixels, mut mismatched) = (0, 0);
+        for _ in 0..2000 {
+            let camera = Camera::new(
+                Vec2::new(1. + rng.next_f64() * 22., 1. + rng.next_f64() * 22.),
+                rng.next_f64() * TAU,
+                FOV.to_radians(),
+            );
+            // Widths that aren't a multiple of the simd batch size leave a remainder
+            let (width, height) = (rng.range(1, 400), rng.range(2, 300));
+            let projection = Projection::new(width, height, 1., &camera);
+            let Some(row) = FloorRow::new(rng.range(0, height), &projection, &camera) else {
+                continue;
+            };
+            pixels += width;
+            mismatched += mismatches(&row, width, &floor_textures, &map);
+        }
+        let fraction = mismatched as f64 / pixels as f64;
+        assert!(fraction <= MAX_MISMATCH, "{fraction}");
+    }
+
+    #[test]
+    fn mismatches_are_on_texel_edges() {
+        // The benchmark pose is axis aligned with exactly representable coordinates, so many pixels sample right
+        // on the edge between two texels where the two paths may round to different sides.
+        let map = Map::test_level();
+        let textures = test_textures();
+        let floor_textures = FloorTextures::validate(&textures, &map).unwrap();
+        let camera = Camera::new(Vec2::new(22., 11.5), PI, FOV.to_radians());
+        let (width, height) = (160, 144);
+        let projection = Projection::new(width, height, 1., &camera);
+        let on_edge = |v: f64, size: u32| {
+            let texels = v * size as f64;
+            (texels - texels.round()).abs() < 1e-6
+        };
+
+        let (mut pixels, mut mismatched) = (0, 0);
+        let (mut reference, mut fast) = (vec![0; width], vec![0; width]);
+        for y in 0..height {
+            let Some(row) = FloorRow::new(y, &projection, &camera) else {
+                continue;
+            };
+            draw_span(&row, &mut reference, &map, &textures);
+            draw_span_fast(&row, &mut fast, &floor_textures);
+            for x in (0..width).filter(|&x| reference[x] != fast[x]) {
+                let pos = row.start + row.step * x as f64;
+                assert!(
+                    on_edge(pos.x, TEXTURE_WIDTH) || on_edge(pos.y, TEXTURE_HEIGHT),
+                    "({x}, {y}) samples {pos:?}"
+                );
+                mismatched += 1;
+            }
+            pixels += width;
+        }
+        // Measured 1.17%
+        assert!(mismatched as f64 / pixels as f64 <= 0.015);
+    }
+}
@@ -0,0 +1,300 @@
+/// Monospace bitmap font. Every glyph is `height` bytes, one per row from top to bottom, with the leftmost
+/// pixel in the highest bit, so glyphs can be at most 8 pixels wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Font<'a> {
+    width: usize,
+    height: usize,
+    // Character of the first glyph, the rest follow in order
+    first: char,
+    glyphs: &'a [u8],
+}
+
+/// 5x7 font covering printable ASCII, embedded so text can be drawn without any assets.
+pub const DEFAULT_FONT: Font<'static> = Font {
+    width: 5,
+    height: 7,
+    first: ' ',
+    glyphs: &DEFAULT_GLYPHS,
+};
+
+impl<'a> Font<'a> {
+    pub fn new(width: usize, height: usize, first: char, glyphs: &'a [u8]) -> Self {
+        assert!(width <= 8, "glyphs can be at most 8 pixels wide");
+        Self {
+            width,
+            height,
+            first,
+            glyphs,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Horizontal distance between the start of two characters.
+    pub fn advance(&self) -> usize {
+        self.width + 1
+    }
+
+    /// Vertical distance between two lines of text.
+    pub fn line_height(&self) -> usize {
+        self.height + 1
+    }
+
+    /// Rows of the glyph for `c`, `None` if the font doesn't have one.
+    pub fn glyph(&self, c: char) -> Option<&'a [u8]> {
+        let index = (c as u32).checked_sub(self.first as u32)? as usize;
+        self.glyphs
+            .get(index * self.height..(index + 1) * self.height)
+    }
+
+    /// Size of `text` in pixels, the longest line by the number of lines.
+    pub fn measure(&self, text: &str) -> (usize, usize) {
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let lines = text.lines().count();
+        (
+            (columns * self.advance()).saturating_sub(1),
+            (lines * self.line_height()).saturating_sub(1),
+        )
+    }
+}
+
+#[rustfmt::skip]
+const DEFAULT_GLYPHS: [u8; 95 * 7] = [
+    // ' '
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // '!'
+    0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x20,
+    // '"'
+    0x50, 0x50, 0x50, 0x00, 0x00, 0x00, 0x00,
+    // '#'
+    0x50, 0x50, 0xf8, 0x50, 0xf8, 0x50, 0x50,
+    // '$'
+    0x20, 0x78, 0xa0, 0x70, 0x28, 0xf0, 0x20,
+    // '%'
+    0xc0, 0xc8, 0x10, 0x20, 0x40, 0x98, 0x18,
+    // '&'
+    0x60, 0x90, 0xa0, 0x40, 0xa8, 0x90, 0x68,
+    // "'"
+    0x20, 0x20, 0x40, 0x00, 0x00, 0x00, 0x00,
+    // '('
+    0x10, 0x20, 0x40, 0x40, 0x40, 0x20, 0x10,
+    // ')'
+    0x40, 0x20, 0x10, 0x10, 0x10, 0x20, 0x40,
+    // '*'
+    0x00, 0x20, 0xa8, 0x70, 0xa8, 0x20, 0x00,
+    // '+'
+    0x00, 0x20, 0x20, 0xf8, 0x20, 0x20, 0x00,
+    // ','
+    0x00, 0x00, 0x00, 0x00, 0x60, 0x20, 0x40,
+    // '-'
+    0x00, 0x00, 0x00, 0xf8, 0x00, 0x00, 0x00,
+    // '.'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x60, 0x60,
+    // '/'
+    0x00, 0x08, 0x10, 0x20, 0x40, 0x80, 0x00,
+    // '0'
+    0x70, 0x88, 0x98, 0xa8, 0xc8, 0x88, 0x70,
+    // '1'
+    0x20, 0x60, 0x20, 0x20, 0x20, 0x20, 0x70,
+    // '2'
+    0x70, 0x88, 0x08, 0x10, 0x20, 0x40, 0xf8,
+    // '3'
+    0xf8, 0x10, 0x20, 0x10, 0x08, 0x88, 0x70,
+    // '4'
+    0x10, 0x30, 0x50, 0x90, 0xf8, 0x10, 0x10,
+    // '5'
+    0xf8, 0x80, 0xf0, 0x08, 0x08, 0x88, 0x70,
+    // '6'
+    0x30, 0x40, 0x80, 0xf0, 0x88, 0x88, 0x70,
+    // '7'
+    0xf8, 0x08, 0x10, 0x20, 0x40, 0x40, 0x40,
+    // '8'
+    0x70, 0x88, 0x88, 0x70, 0x88, 0x88, 0x70,
+    // '9'
+    0x70, 0x88, 0x88, 0x78, 0x08, 0x10, 0x60,
+    // ':'
+    0x00, 0x60, 0x60, 0x00, 0x60, 0x60, 0x00,
+    // ';'
+    0x00, 0x60, 0x60, 0x00, 0x60, 0x20, 0x40,
+    // '<'
+    0x10, 0x20, 0x40, 0x80, 0x40, 0x20, 0x10,
+    // '='
+    0x00, 0x00, 0xf8, 0x00, 0xf8, 0x00, 0x00,
+    // '>'
+    0x40, 0x20, 0x10, 0x08, 0x10, 0x20, 0x40,
+    // '?'
+    0x70, 0x88, 0x08, 0x10, 0x20, 0x00, 0x20,
+    // '@'
+    0x70, 0x88, 0x08, 0x68, 0xa8, 0xa8, 0x70,
+    // 'A'
+    0x70, 0x88, 0x88, 0x88, 0xf8, 0x88, 0x88,
+    // 'B'
+    0xf0, 0x88, 0x88, 0xf0, 0x88, 0x88, 0xf0,
+    // 'C'
+    0x70, 0x88, 0x80, 0x80, 0x80, 0x88, 0x70,
+    // 'D'
+    0xe0, 0x90, 0x88, 0x88, 0x88, 0x90, 0xe0,
+    // 'E'
+    0xf8, 0x80, 0x80, 0xf0, 0x80, 0x80, 0xf8,
+    // 'F'
+    0xf8, 0x80, 0x80, 0xf0, 0x80, 0x80, 0x80,
+    // 'G'
+    0x70, 0x88, 0x80, 0xb8, 0x88, 0x88, 0x78,
+    // 'H'
+    0x88, 0x88, 0x88, 0xf8, 0x88, 0x88, 0x88,
+    // 'I'
+    0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x70,
+    // 'J'
+    0x38, 0x10, 0x10, 0x10, 0x10, 0x90, 0x60,
+    // 'K'
+    0x88, 0x90, 0xa0, 0xc0, 0xa0, 0x90, 0x88,
+    // 'L'
+    0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xf8,
+    // 'M'
+    0x88, 0xd8, 0xa8, 0xa8, 0x88, 0x88, 0x88,
+    // 'N'
+    0x88, 0x88, 0xc8, 0xa8, 0x98, 0x88, 0x88,
+    // 'O'
+    0x70, 0x88, 0x88, 0x88, 0x88, 0x88, 0x70,
+    // 'P'
+    0xf0, 0x88, 0x88, 0xf0, 0x80, 0x80, 0x80,
+    // 'Q'
+    0x70, 0x88, 0x88, 0x88, 0xa8, 0x90, 0x68,
+    // 'R'
+    0xf0, 0x88, 0x88, 0xf0, 0xa0, 0x90, 0x88,
+    // 'S'
+    0x78, 0x80, 0x80, 0x70, 0x08, 0x08, 0xf0,
+    // 'T'
+    0xf8, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+    // 'U'
+    0x88, 0x88, 0x88, 0x88, 0x88, 0x88, 0x70,
+    // 'V'
+    0x88, 0x88, 0x88, 0x88, 0x88, 0x50, 0x20,
+    // 'W'
+    0x88, 0x88, 0x88, 0xa8, 0xa8, 0xa8, 0x50,
+    // 'X'
+    0x88, 0x88, 0x50, 0x20, 0x50, 0x88, 0x88,
+    // 'Y'
+    0x88, 0x88, 0x88, 0x50, 0x20, 0x20, 0x20,
+    // 'Z'
+    0xf8, 0x08, 0x10, 0x20, 0x40, 0x80, 0xf8,
+    // '['
+    0x70, 0x40, 0x40, 0x40, 0x40, 0x40, 0x70,
+    // '\\'
+    0x00, 0x80, 0x40, 0x20, 0x10, 0x08, 0x00,
+    // ']'
+    0x70, 0x10, 0x10, 0x10, 0x10, 0x10, 0x70,
+    // '^'
+    0x20, 0x50, 0x88, 0x00, 0x00, 0x00, 0x00,
+    // '_'
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8,
+    // '`'
+    0x40, 0x20, 0x10, 0x00, 0x00, 0x00, 0x00,
+    // 'a'
+    0x00, 0x00, 0x70, 0x08, 0x78, 0x88, 0x78,
+    // 'b'
+    0x80, 0x80, 0xb0, 0xc8, 0x88, 0x88, 0xf0,
+    // 'c'
+    0x00, 0x00, 0x70, 0x80, 0x80, 0x88, 0x70,
+    // 'd'
+    0x08, 0x08, 0x68, 0x98, 0x88, 0x88, 0x78,
+    // 'e'
+    0x00, 0x00, 0x70, 0x88, 0xf8, 0x80, 0x70,
+    // 'f'
+    0x30, 0x48, 0x40, 0xe0, 0x40, 0x40, 0x40,
+    // 'g'
+    0x00, 0x78, 0x88, 0x88, 0x78, 0x08, 0x70,
+    // 'h'
+    0x80, 0x80, 0xb0, 0xc8, 0x88, 0x88, 0x88,
+    // 'i'
+    0x20, 0x00, 0x60, 0x20, 0x20, 0x20, 0x70,
+    // 'j'
+    0x10, 0x00, 0x30, 0x10, 0x10, 0x90, 0x60,
+    // 'k'
+    0x80, 0x80, 0x90, 0xa0, 0xc0, 0xa0, 0x90,
+    // 'l'
+    0x60, 0x20, 0x20, 0x20, 0x20, 0x20, 0x70,
+    // 'm'
+    0x00, 0x00, 0xd0, 0xa8, 0xa8, 0x88, 0x88,
+    // 'n'
+    0x00, 0x00, 0xb0, 0xc8, 0x88, 0x88, 0x88,
+    // 'o'
+    0x00, 0x00, 0x70, 0x88, 0x88, 0x88, 0x70,
+    // 'p'
+    0x00, 0x00, 0xf0, 0x88, 0xf0, 0x80, 0x80,
+    // 'q'
+    0x00, 0x00, 0x68, 0x98, 0x78, 0x08, 0x08,
+    // 'r'
+    0x00, 0x00, 0xb0, 0xc8, 0x80, 0x80, 0x80,
+    // 's'
+    0x00, 0x00, 0x70, 0x80, 0x70, 0x08, 0xf0,
+    // 't'
+    0x40, 0x40, 0xe0, 0x40, 0x40, 0x48, 0x30,
+    // 'u'
+    0x00, 0x00, 0x88, 0x88, 0x88, 0x98, 0x68,
+    // 'v'
+    0x00, 0x00, 0x88, 0x88, 0x88, 0x50, 0x20,
+    // 'w'
+    0x00, 0x00, 0x88, 0x88, 0xa8, 0xa8, 0x50,
+    // 'x'
+    0x00, 0x00, 0x88, 0x50, 0x20, 0x50, 0x88,
+    // 'y'
+    0x00, 0x00, 0x88, 0x88, 0x78, 0x08, 0x70,
+    // 'z'
+    0x00, 0x00, 0xf8, 0x10, 0x20, 0x40, 0xf8,
+    // '{'
+    0x10, 0x20, 0x20, 0x40, 0x20, 0x20, 0x10,
+    // '|'
+    0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20,
+    // '}'
+    0x40, 0x20, 0x20, 0x10, 0x20, 0x20, 0x40,
+    // '~'
+    0x00, 0x00, 0x40, 0xa8, 0x10, 0x00, 0x00,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Framebuffer;
+
+    #[test]
+    fn measure() {
+        let font = DEFAULT_FONT;
+        assert_eq!(font.measure(""), (0, 0));
+        assert_eq!(font.measure("a"), (5, 7));
+        // The longest line counts, there is no spacing after the last character or line
+        assert_eq!(font.measure("ab\ncde\n"), (3 * 6 - 1, 2 * 8 - 1));
+        assert_eq!(font.measure("ab\n\nc"), (2 * 6 - 1, 3 * 8 - 1));
+    }
+
+    #[test]
+    fn glyphs_land_at_their_offsets() {
+        let font = DEFAULT_FONT;
+        assert_eq!(
+            font.glyph('1'),
+            Some(&[0x20, 0x60, 0x20, 0x20, 0x20, 0x20, 0x70][..])
+        );
+        assert_eq!(font.glyph('\t'), None);
+        assert_eq!(font.glyph('\u{7f}'), None);
+
+        let mut framebuffer = Framebuffer::new(20, 20);
+        framebuffer.text(3, 2, "1-\n.", &font, 1);
+        let drawn: Vec<_> = (0..20)
+            .flat_map(|y| (0..20).map(move |x| (x, y)))
+            .filter(|&(x, y)| framebuffer.get(x, y) == 1)
+            .collect();
+        let mut expected = vec![(5, 2), (4, 3), (5, 3), (5, 4), (5, 5), (5, 6), (5, 7)];
+        expected.extend([(4, 8), (5, 8), (6, 8)]);
+        // '-' is one advance to the right, '.' one line height down
+        expected.extend((9..14).map(|x| (x, 5)));
+        expected.extend([(4, 15), (5, 15), (4, 16), (5, 16)]);
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(drawn, expected);
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+};
+
+use crate::{
+    capture::{self, ImageFormat},
+    font::Font,
+    render::darken,
+};
+
+/// 0RGB pixel buffer at the internal render resolution, row major.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> u32 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: u32) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn fill(&mut self, color: u32) {
+        self.pixels.fill(color);
+    }
+
+    /// Writes the frame as an uncompressed PNG image.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        capture::write_png(&mut writer, self.width(), self.height(), self.pixels())?;
+        writer.flush()
+    }
+
+    /// Writes the frame as a binary PPM image.
+    pub fn save_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(fs::File::create(path)?);
+        capture::write_ppm(&mut writer, self.width(), self.height(), self.pixels())?;
+        writer.flush()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Png => self.save_png(path),
+            ImageFormat::Ppm => self.save_ppm(path),
+        }
+    }
+
+    /// Fills a rectangle, the parts outside of the framebuffer are clipped.
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: usize, height: usize, color: u32) {
+        let Some((columns, rows)) = self.clip(x, y, width, height) else {
+            return;
+        };
+        for row in rows {
+            self.pixels[row * self.width + columns.start..row * self.width + columns.end]
+                .fill(color);
+        }
+    }
+
+    /// Halves the brightness of a rectangle, used as a translucent background for text.
+    pub fn darken_rect(&mut self, x: i32, y: i32, width: usize, height: usize) {
+        let Some((columns, rows)) = self.clip(x, y, width, height) else {
+            return;
+        };
+        for row in rows {
+            for pixel in
+                &mut self.pixels[row * self.width + columns.start..row * self.width + columns.end]
+            {
+                *pixel = darken(*pixel);
+            }
+        }
+    }
+
+    /// One pixel wide outline of a rectangle.
+    pub fn rect(&mut self, x: i32, y: i32, width: usize, height: usize, color: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        // Far edges past i32::MAX are off-screen anyway
+        let far = |start: i32, size: usize| {
+            (start as i64 + size.min(i32::MAX as usize) as i64 - 1).min(i32::MAX as i64) as i32
+        };
+        self.fill_rect(x, y, width, 1, color);
+        self.fill_rect(x, far(y, height), width, 1, color);
+        self.fill_rect(x, y, 1, height, color);
+        self.fill_rect(far(x, width), y, 1, height, color);
+    }
+
+    /// Line from `(x0, y0)` to `(x1, y1)`, both ends included. Lines that leave the framebuffer are clipped to
+    /// it first, so they can be off by a pixel compared to drawing all of it.
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: u32) {
+        let Some((from, to)) = self.clip_line((x0 as i64, y0 as i64), (x1 as i64, y1 as i64))
+        else {
+            return;
+        };
+        for (x, y) in line_points(from, to) {
+            if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+                self.set(x as usize, y as usize, color);
+            }
+        }
+    }
+
+    /// Copies an `image_width` pixels wide image to `(x, y)`. Pixels of the color `key` are skipped, which is
+    /// how sprites get transparent parts.
+    pub fn blit(&mut self, x: i32, y: i32, image: &[u32], image_width: usize, key: Option<u32>) {
+        if image_width == 0 {
+            return;
+        }
+        let image_height = image.len() / image_width;
+        let Some((columns, rows)) = self.clip(x, y, image_width, image_height) else {
+            return;
+        };
+        for row in rows {
+            let image_y = (row as i64 - y as i64) as usize;
+            for column in columns.clone() {
+                let image_x = (column as i64 - x as i64) as usize;
+                let color = image[image_y * image_width + image_x];
+                if Some(color) != key {
+                    self.pixels[row * self.width + column] = color;
+                }
+            }
+        }
+    }
+
+    /// Same as [`Framebuffer::blit`], but the image is stretched to `size` with nearest neighbour scaling.
+    /// Images without a single full row draw nothing.
+    pub fn blit_scaled(
+        &mut self,
+        x: i32,
+        y: i32,
+        size: (usize, usize),
+        image: &[u32],
+        image_width: usize,
+        key: Option<u32>,
+    ) {
+        let (width, height) = size;
+        if image_width == 0 || width == 0 || height == 0 {
+            return;
+        }
+        let image_height = image.len() / image_width;
+        if image_height == 0 {
+            return;
+        }
+        let Some((columns, rows)) = self.clip(x, y, width, height) else {
+            return;
+        };
+        for row in rows {
+            let image_y =
+                ((row as i64 - y as i64) as usize * image_height / height).min(image_height - 1);
+            for column in columns.clone() {
+                let image_x = ((column as i64 - x as i64) as usize * image_width / width)
+                    .min(image_width - 1);
+                let color = image[image_y * image_width + image_x];
+                if Some(color) != key {
+                    self.pixels[row * self.width + column] = color;
+                }
+            }
+        }
+    }
+
+    /// Draws `text` with its top left corner at `(x, y)`. Every `\n` starts a new line, characters the font
+    /// doesn't have are left blank.
+    pub fn text(&mut self, x: i32, y: i32, text: &str, font: &Font, color: u32) {
+        for (line_index, line) in text.lines().enumerate() {
+            let line_y = y as i64 + (line_index * font.line_height()) as i64;
+            for (char_index, c) in line.chars().enumerate() {
+                let Some(glyph) = font.glyph(c) else {
+                    continue;
+                };
+                let char_x = x as i64 + (char_index * font.advance()) as i64;
+                for (glyph_y, bits) in glyph.iter().enumerate() {
+                    for glyph_x in 0..font.width() {
+                        if bits & (0x80 >> glyph_x) == 0 {
+                            continue;
+                        }
+                        let (px, py) = (char_x + glyph_x as i64, line_y + glyph_y as i64);
+                        if px >= 0
+                            && py >= 0
+                            && (px as usize) < self.width
+                            && (py as usize) < self.height
+                        {
+                            self.set(px as usize, py as usize, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Part of a line inside of the framebuffer (Liang-Barsky), None if there is none. Bresenham walks every point
+    // of a line, this keeps lines that reach far outside from taking forever.
+    fn clip_line(&self, from: (i64, i64), to: (i64, i64)) -> Option<((i64, i64), (i64, i64))> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let (max_x, max_y) = (self.width as i64 - 1, self.height as i64 - 1);
+        let inside = |(x, y): (i64, i64)| (0..=max_x).contains(&x) && (0..=max_y).contains(&y);
+        if inside(from) && inside(to) {
+            return Some((from, to));
+        }
+
+        let (x0, y0) = (from.0 as f64, from.1 as f64);
+        let (dx, dy) = ((to.0 - from.0) as f64, (to.1 - from.1) as f64);
+        let (mut t0, mut t1) = (0f64, 1f64);
+        for (p, q) in [
+            (-dx, x0),
+            (dx, max_x as f64 - x0),
+            (-dy, y0),
+            (dy, max_y as f64 - y0),
+        ] {
+            if p == 0. {
+                if q < 0. {
+                    return None;
+                }
+                continue;
+            }
+            let t = q / p;
+            if p < 0. {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        let point = |t: f64| ((x0 + dx * t).round() as i64, (y0 + dy * t).round() as i64);
+        Some((point(t0), point(t1)))
+    }
+
+    // Columns and rows of a rectangle that are inside of the framebuffer, None if it's completely outside
+    fn clip(
+        &self,
+        x: i32,
+        y: i32,
+        width: usize,
+        height: usize,
+    ) -> Option<(Range<usize>, Range<usize>)> {
+        let clamp = |v: i64, max: usize| v.clamp(0, max as i64) as usize;
+        let end = |start: i32, size: usize| {
+            (start as i64).saturating_add(i64::try_from(size).unwrap_or(i64::MAX))
+        };
+        let columns = clamp(x as i64, self.width)..clamp(end(x, width), self.width);
+        let rows = clamp(y as i64, self.height)..clamp(end(y, height), self.height);
+        (!columns.is_empty() && !rows.is_empty()).then_some((columns, rows))
+    }
+}
+
+/// Points of a line with Bresenham's line algorithm, both ends included.
+pub(crate) fn line_points(
+    (mut x0, mut y0): (i64, i64),
+    (x1, y1): (i64, i64),
+) -> impl Iterator<Item = (i64, i64)> {
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let mut error = dx + dy;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let point = (x0, y0);
+        if x0 == x1 && y0 == y1 {
+            done = true;
+            return Some(point);
+        }
+        let error2 = 2 * error;
+        if error2 >= dy {
+            error += dy;
+            x0 += step_x;
+        }
+        if error2 <= dx {
+            error += dx;
+            y0 += step_y;
+        }
+        Some(point)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::font::DEFAULT_FONT;
+
+    fn coordinate() -> impl Strategy<Value = i32> {
+        prop_oneof![-20i32..30, Just(i32::MIN), Just(i32::MAX)]
+    }
+
+    fn size() -> impl Strategy<Value = usize> {
+        prop_oneof![0usize..30, Just(usize::MAX)]
+    }
+
+    proptest! {
+        // Shapes partly or completely off-screen, with negative and extreme coordinates
+        #[test]
+        fn clips_everything(
+            (width, height) in (0usize..12, 0usize..10),
+            (x0, y0, x1, y1) in (coordinate(), coordinate(), -20i32..30, -20i32..30),
+            (w, h) in (size(), size()),
+            text in "[ -~\n]{0,12}",
+            image_width in 1usize..5,
+            image_len in 0usize..16,
+        ) {
+            let mut framebuffer = Framebuffer::new(width, height);
+            framebuffer.darken_rect(x0, y0, w, h);
+            framebuffer.rect(x0, y0, w, h, 1);
+            framebuffer.line(x0, y0, x1, y1, 2);
+            framebuffer.text(x0, y0, &text, &DEFAULT_FONT, 3);
+            // Also images shorter than a row and ones with a partial last row
+            let image: Vec<u32> = (0..image_len as u32).collect();
+            framebuffer.blit(x0, y0, &image, image_width, Some(0));
+            framebuffer.blit_scaled(x0, y0, (w.min(64), h.min(64)), &image, image_width, None);
+
+            // Exactly the visible part of a filled rectangle changes
+            let mut framebuffer = Framebuffer::new(width, height);
+            framebuffer.fill_rect(x0, y0, w, h, 4);
+            for py in 0..height {
+                for px in 0..width {
+                    let (px, py, x0, y0) = (px as i128, py as i128, x0 as i128, y0 as i128);
+                    let inside =
+                        px >= x0 && px < x0 + w as i128 && py >= y0 && py < y0 + h as i128;
+                    prop_assert_eq!(framebuffer.get(px as usize, py as usize) == 4, inside);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::{Duration, Instant};
+
+/// How often frames are presented, independent of the simulation tick rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePacing {
+    /// Render as often as the event loop wakes up.
+    Unlimited,
+    /// Render at most this many frames per second.
+    Capped(u32),
+}
+
+impl FramePacing {
+    /// Vsync-like pacing: cap at the refresh rate reported by the monitor (in millihertz, as winit reports it).
+    /// Falls back to `fallback` when the refresh rate is unknown.
+    pub fn from_refresh_rate(millihertz: Option<u32>, fallback: FramePacing) -> Self {
+        match millihertz {
+            Some(mhz) if mhz >= 1000 => Self::Capped((mhz + 500) / 1000),
+            _ => fallback,
+        }
+    }
+
+    fn frame_duration(&self) -> Option<Duration> {
+        match *self {
+            Self::Unlimited => None,
+            Self::Capped(0) => None,
+            Self::Capped(fps) => Some(Duration::from_secs(1) / fps),
+        }
+    }
+}
+
+/// What the caller should do for the current frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// Number of fixed simulation ticks to run before rendering.
+    pub ticks: u32,
+    /// How far (0..1) the clock is between the last simulated tick and the next one.
+    /// Render `previous.lerp(&current, alpha)` to hide the tick granularity.
+    pub alpha: f64,
+}
+
+/// Fixed-timestep game loop clock.
+///
+/// The simulation always advances in steps of exactly [`GameLoop::dt`] seconds, no matter how fast or slow
+/// frames are presented. Leftover time is carried over to the next frame and exposed as the interpolation
+/// factor [`Frame::alpha`].
+#[derive(Debug)]
+pub struct GameLoop {
+    tick: Duration,
+    pacing: FramePacing,
+    // Frames longer than this are clamped so that a stall (window drag, breakpoint...) doesn't make the
+    // simulation run hundreds of ticks to catch up.
+    max_frame_time: Duration,
+    last: Instant,
+    accumulator: Duration,
+    next_frame: Instant,
+}
+
+impl GameLoop {
+    pub fn new(tick_rate: u32, pacing: FramePacing) -> Self {
+        assert!(tick_rate > 0, "tick rate must be positive");
+        let now = Instant::now();
+        Self {
+            tick: Duration::from_secs(1) / tick_rate,
+            pacing,
+            max_frame_time: Duration::from_millis(250),
+            last: now,
+            accumulator: Duration::ZERO,
+            next_frame: now,
+        }
+    }
+
+    /// Length of one simulation tick in seconds.
+    pub fn dt(&self) -> f64 {
+        self.tick.as_secs_f64()
+    }
+
+    pub fn pacing(&self) -> FramePacing {
+        self.pacing
+    }
+
+    pub fn set_pacing(&mut self, pacing: FramePacing) {
+        self.pacing = pacing;
+        self.next_frame = Instant::now();
+    }
+
+    /// Whether enough time has passed since the last frame to present a new one.
+    pub fn frame_due(&self) -> bool {
+        Instant::now() >= self.next_frame
+    }
+
+    /// When the next frame is due, `None` if frames aren't capped.
+    pub fn next_frame_at(&self) -> Option<Instant> {
+        self.pacing.frame_duration().map(|_| self.next_frame)
+    }
+
+    /// Advances the clock to now and returns how many ticks to simulate and the interpolation factor.
+    pub fn begin_frame(&mut self) -> Frame {
+        self.begin_frame_at(Instant::now())
+    }
+
+    /// Same as [`GameLoop::begin_frame`] but with an explicit timestamp.
+    pub fn begin_frame_at(&mut self, now: Instant) -> Frame {
+        let elapsed = now
+            .saturating_duration_since(self.last)
+            .min(self.max_frame_time);
+        self.last = now;
+        self.accumulator += elapsed;
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick {
+            self.accumulator -= self.tick;
+            ticks += 1;
+        }
+
+        if let Some(frame_duration) = self.pacing.frame_duration() {
+            // Schedule from the previous deadline to keep a steady cadence, but don't try to make up
+            // for frames we already missed.
+            self.next_frame += frame_duration;
+            if self.next_frame < now {
+                self.next_frame = now + frame_duration;
+            }
+        }
+
+        Frame {
+            ticks,
+            alpha: self.accumulator.as_secs_f64() / self.tick.as_secs_f64(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn ticks_and_alpha() {
+        // 10 ms ticks
+        let mut game_loop = GameLoop::new(100, FramePacing::Unlimited);
+        let start = game_loop.last;
+
+        let frame = game_loop.begin_frame_at(start + 25 * MS);
+        assert_eq!(frame.ticks, 2);
+        assert!((frame.alpha - 0.5).abs() < 1e-9);
+        // The leftover 5 ms carry over
+        let frame = game_loop.begin_frame_at(start + 31 * MS);
+        assert_eq!(frame.ticks, 1);
+        assert!((frame.alpha - 0.1).abs() < 1e-9);
+        // Time going backwards doesn't simulate anything
+        let frame = game_loop.begin_frame_at(start);
+        assert_eq!(frame.ticks, 0);
+        assert!((frame.alpha - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut game_loop = GameLoop::new(100, FramePacing::Unlimited);
+        let start = game_loop.last;
+        let frame = game_loop.begin_frame_at(start + Duration::from_secs(5));
+        assert_eq!(frame.ticks, 25);
+        assert_eq!(frame.alpha, 0.);
+        assert_eq!(game_loop.next_frame_at(), None);
+    }
+
+    #[test]
+    fn capped_pacing() {
+        let mut game_loop = GameLoop::new(100, FramePacing::Capped(50));
+        let start = game_loop.last;
+        assert_eq!(game_loop.next_frame_at(), Some(start));
+
+        // Deadlines follow each other even when a frame starts a little late
+        game_loop.begin_frame_at(start + MS);
+        assert_eq!(game_loop.next_frame_at(), Some(start + 20 * MS));
+        game_loop.begin_frame_at(start + 22 * MS);
+        assert_eq!(game_loop.next_frame_at(), Some(start + 40 * MS));
+        // Missed frames aren't made up for
+        game_loop.begin_frame_at(start + 100 * MS);
+        assert_eq!(game_loop.next_frame_at(), Some(start + 120 * MS));
+
+        assert_eq!(
+            FramePacing::from_refresh_rate(Some(59_940), FramePacing::Unlimited),
+            FramePacing::Capped(60)
+        );
+        assert_eq!(
+            FramePacing::from_refresh_rate(None, FramePacing::Capped(30)),
+            FramePacing::Capped(30)
+        );
+    }
+}
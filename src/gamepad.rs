@@ -0,0 +1,168 @@
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+
+use crate::input::{Action, Input};
+
+/// How stick positions are turned into action values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadSettings {
+    /// Stick deflection (0..1) below which the stick counts as centered. Worn sticks rarely rest at exactly 0.
+    pub dead_zone: f64,
+    /// Exponent of the response curve applied after the dead zone. `1.` is linear, higher values give finer
+    /// control near the center.
+    pub curve: f64,
+    /// Multiplier for the turning stick, 1 means full deflection turns as fast as the turn keys.
+    pub turn_sensitivity: f64,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            curve: 2.,
+            turn_sensitivity: 1.,
+        }
+    }
+}
+
+impl GamepadSettings {
+    /// Maps a raw stick magnitude (0..1) through the dead zone and response curve. The result still covers
+    /// 0..1, so there is no jump at the edge of the dead zone.
+    pub fn response(&self, magnitude: f64) -> f64 {
+        if magnitude <= self.dead_zone {
+            return 0.;
+        }
+        let scaled = ((magnitude - self.dead_zone) / (1. - self.dead_zone)).min(1.);
+        scaled.powf(self.curve)
+    }
+
+    // Radial dead zone for a whole stick, so diagonals aren't cut off like with a per-axis one
+    fn stick(&self, x: f64, y: f64) -> (f64, f64) {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude == 0. {
+            return (0., 0.);
+        }
+        let scale = self.response(magnitude.min(1.)) / magnitude;
+        (x * scale, y * scale)
+    }
+}
+
+/// Reads the first connected gamepad and feeds it into [`Input`] as analog action values:
+/// left stick moves and strafes, right stick turns, south button uses, left stick click or left trigger runs.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+    pub settings: GamepadSettings,
+}
+
+impl Gamepads {
+    /// The error is boxed, gilrs' is large because it can carry a whole `Gilrs` along.
+    pub fn new(settings: GamepadSettings) -> Result<Self, Box<gilrs::Error>> {
+        let gilrs = Gilrs::new()?;
+        let active = gilrs.gamepads().next().map(|(id, _)| id);
+        Ok(Self {
+            gilrs,
+            active,
+            settings,
+        })
+    }
+
+    /// Drains pending gamepad events and updates the analog values of `input`. Call once per frame.
+    pub fn update(&mut self, input: &mut Input) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected if self.active.is_none() => self.active = Some(event.id),
+                EventType::Disconnected if self.active == Some(event.id) => {
+                    self.active = self.gilrs.gamepads().next().map(|(id, _)| id);
+                }
+                // Whichever gamepad was touched last is the one that's used
+                EventType::ButtonPressed(..) => self.active = Some(event.id),
+                _ => (),
+            }
+        }
+
+        let gamepad = match self.active {
+            Some(id) if self.gilrs.gamepad(id).is_connected() => self.gilrs.gamepad(id),
+            _ => {
+                for action in Action::ALL {
+                    input.set_analog(action, 0.);
+                }
+                return;
+            }
+        };
+
+        let (strafe, forward) = self.settings.stick(
+            gamepad.value(Axis::LeftStickX) as f64,
+            gamepad.value(Axis::LeftStickY) as f64,
+        );
+        set_axis(input, Action::MoveBackward, Action::MoveForward, forward);
+        set_axis(input, Action::StrafeLeft, Action::StrafeRight, strafe);
+
+        let turn = gamepad.value(Axis::RightStickX) as f64;
+        let turn =
+            turn.signum() * self.settings.response(turn.abs()) * self.settings.turn_sensitivity;
+        set_axis(input, Action::TurnLeft, Action::TurnRight, turn);
+
+        let run = gamepad.is_pressed(Button::LeftThumb) || gamepad.is_pressed(Button::LeftTrigger2);
+        input.set_analog(Action::Run, run as i32 as f64);
+        input.set_analog(Action::Use, gamepad.is_pressed(Button::South) as i32 as f64);
+        input.set_analog(
+            Action::Fire,
+            gamepad.is_pressed(Button::RightTrigger2) as i32 as f64,
+        );
+    }
+}
+
+fn set_axis(input: &mut Input, negative: Action, positive: Action, value: f64) {
+    input.set_analog(positive, value.max(0.));
+    input.set_analog(negative, (-value).max(0.));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn response_curve() {
+        let settings = GamepadSettings::default();
+        assert_eq!(settings.response(0.), 0.);
+        assert_eq!(settings.response(settings.dead_zone), 0.);
+        // No jump at the edge of the dead zone and full deflection still reaches 1
+        assert!(settings.response(settings.dead_zone + 1e-6) < 1e-6);
+        assert!(close(settings.response(1.), 1.));
+        assert!(close(settings.response(1.5), 1.));
+        // Halfway past the dead zone is a quarter with the default square curve
+        let halfway = settings.dead_zone + (1. - settings.dead_zone) / 2.;
+        assert!(close(settings.response(halfway), 0.25));
+
+        let linear = GamepadSettings {
+            curve: 1.,
+            ..settings
+        };
+        assert!(close(linear.response(halfway), 0.5));
+    }
+
+    #[test]
+    fn radial_stick() {
+        let settings = GamepadSettings::default();
+        assert_eq!(settings.stick(0., 0.), (0., 0.));
+        // Inside the dead zone on both axes, even where a per-axis dead zone would let it through
+        assert_eq!(settings.stick(0.1, 0.1), (0., 0.));
+
+        // Direction is kept, the length follows the response curve
+        let (x, y) = settings.stick(0.6, 0.8);
+        assert!(close(y / x, 0.8 / 0.6));
+        assert!(close((x * x + y * y).sqrt(), 1.));
+        let (x, y) = settings.stick(0.3, 0.4);
+        assert!(close((x * x + y * y).sqrt(), settings.response(0.5)));
+
+        // Corners of a square stick gate are clamped to 1
+        let (x, y) = settings.stick(1., 1.);
+        assert!(close(x, y) && close((x * x + y * y).sqrt(), 1.));
+        let (x, y) = settings.stick(-1., 0.);
+        assert!(close(x, -1.) && y == 0.);
+    }
+}
@@ -0,0 +1,706 @@
+//! Procedural level generation.
+//!
+//! Every generator carves open cells out of solid rock and labels them with regions, rooms for
+//! [`Generator::Rooms`], areas around random points for [`Generator::Caves`] and stretches of the path for
+//! [`Generator::Maze`]. The same steps finish every map: whatever isn't reachable from the spawn is filled in,
+//! the border is closed and every region gets textures picked from [`TextureRules`]. The same seed always gives
+//! the same map.
+
+use std::collections::VecDeque;
+
+use crate::{
+    map::{Map, Spawn},
+    math::Vec2,
+};
+
+/// Small, fast and deterministic random number generator (SplitMix64). Not suitable for anything that needs
+/// to be unpredictable.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..1`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `low..high`, `high` has to be larger than `low`.
+    pub fn range(&mut self, low: usize, high: usize) -> usize {
+        assert!(low < high, "empty range {low}..{high}");
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len())]
+    }
+}
+
+/// Which textures regions get. Every region picks one wall and one floor texture, walls take the textures of
+/// the region next to them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureRules {
+    /// Wall texture of the outer border.
+    pub border: usize,
+    /// Wall textures regions pick from.
+    pub walls: Vec<usize>,
+    /// Floor textures regions pick from, 0 is the default floor. Ceilings use 7 minus these, so they have to
+    /// be 7 or less.
+    pub floors: Vec<usize>,
+    /// Wall and floor textures of corridors between rooms.
+    pub corridor_wall: usize,
+    pub corridor_floor: usize,
+}
+
+impl Default for TextureRules {
+    fn default() -> Self {
+        Self {
+            border: 0,
+            walls: vec![1, 3, 4, 5, 6],
+            floors: vec![0, 1, 3, 5],
+            corridor_wall: 7,
+            corridor_floor: 0,
+        }
+    }
+}
+
+impl TextureRules {
+    /// Checks that there's something to pick from and every texture fits in a map cell. Walls are stored as
+    /// texture + 1 in an `i8`, so they have to be below 127, floors can't be above 7.
+    pub fn check(&self) -> Result<(), String> {
+        if self.walls.is_empty() || self.floors.is_empty() {
+            return Err("walls and floors need at least one texture each".into());
+        }
+        let walls = self.walls.iter().chain([&self.border, &self.corridor_wall]);
+        if let Some(wall) = walls.copied().find(|wall| *wall >= i8::MAX as usize) {
+            return Err(format!("wall texture {wall} is above {}", i8::MAX - 1));
+        }
+        let floors = self.floors.iter().chain([&self.corridor_floor]);
+        if let Some(floor) = floors.copied().find(|floor| *floor > 7) {
+            return Err(format!("floor texture {floor} is above 7"));
+        }
+        Ok(())
+    }
+}
+
+/// Rooms connected by corridors, placed by splitting the map in two over and over (binary space
+/// partitioning).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoomSettings {
+    /// Areas are only split while both halves would be at least this long on the split axis.
+    pub min_area: usize,
+    /// Smallest room side, rooms fill their area up to a wall around them.
+    pub min_room: usize,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            min_area: 8,
+            min_room: 3,
+        }
+    }
+}
+
+/// Caves grown with a cellular automaton from random noise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaveSettings {
+    /// Fraction of cells that start as rock.
+    pub fill: f64,
+    /// Smoothing steps, more make rounder caves.
+    pub steps: usize,
+    /// Average number of cells per region.
+    pub region_size: usize,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            fill: 0.45,
+            steps: 5,
+            region_size: 150,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Generator {
+    Rooms(RoomSettings),
+    Caves(CaveSettings),
+    /// A perfect maze, there's exactly one path between any two cells. Corridors are one cell wide and lie on
+    /// odd coordinates.
+    Maze {
+        /// Length of the path after which the next region starts.
+        region_length: usize,
+    },
+}
+
+impl Generator {
+    /// Name used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Rooms(_) => "rooms",
+            Generator::Caves(_) => "caves",
+            Generator::Maze { .. } => "maze",
+        }
+    }
+
+    /// The generator called `name` with default settings.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rooms" => Some(Generator::Rooms(RoomSettings::default())),
+            "caves" => Some(Generator::Caves(CaveSettings::default())),
+            "maze" => Some(Generator::Maze { region_length: 30 }),
+            _ => None,
+        }
+    }
+
+    /// A `width` x `height` map with a closed border and a spawn everything open can be reached from. Maps have
+    /// to be at least 5 x 5 and `rules` have to pass [`TextureRules::check`].
+    pub fn generate(&self, width: usize, height: usize, seed: u64, rules: &TextureRules) -> Map {
+        assert!(
+            width >= 5 && height >= 5,
+            "maps have to be at least 5 x 5, got {width} x {height}"
+        );
+        if let Err(message) = rules.check() {
+            panic!("invalid texture rules, {message}");
+        }
+        let mut rng = Rng::new(seed);
+        let mut grid = Grid::new(width, height);
+        let spawn = match self {
+            Generator::Rooms(settings) => rooms(&mut grid, &mut rng, settings),
+            Generator::Caves(settings) => caves(&mut grid, &mut rng, settings),
+            Generator::Maze { region_length } => maze(&mut grid, &mut rng, *region_length),
+        };
+        grid.finish(spawn, &mut rng, rules)
+    }
+}
+
+// Region of cells that are rock
+const ROCK: usize = usize::MAX;
+// Region of corridors between rooms
+const CORRIDOR: usize = usize::MAX - 1;
+
+// Working state of a generator, the region of every cell
+struct Grid {
+    width: usize,
+    height: usize,
+    regions: Vec<usize>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            regions: vec![ROCK; width * height],
+        }
+    }
+
+    fn region(&self, x: usize, y: usize) -> usize {
+        self.regions[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, region: usize) {
+        self.regions[y * self.width + x] = region;
+    }
+
+    fn is_open(&self, x: usize, y: usize) -> bool {
+        self.region(x, y) != ROCK
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (self.width, self.height);
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .map(move |(dx, dy)| (x as i32 + dx, y as i32 + dy))
+            .filter(move |(x, y)| {
+                *x >= 0 && *y >= 0 && (*x as usize) < width && (*y as usize) < height
+            })
+            .map(|(x, y)| (x as usize, y as usize))
+    }
+
+    // Distance in steps from `start` to every open cell, None for the ones that can't be reached
+    fn distances(&self, start: (usize, usize)) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.regions.len()];
+        distances[start.1 * self.width + start.0] = Some(0);
+        let mut queue = VecDeque::from([(start, 0)]);
+        while let Some(((x, y), distance)) = queue.pop_front() {
+            for (nx, ny) in self.neighbours(x, y) {
+                let index = ny * self.width + nx;
+                if self.is_open(nx, ny) && distances[index].is_none() {
+                    distances[index] = Some(distance + 1);
+                    queue.push_back(((nx, ny), distance + 1));
+                }
+            }
+        }
+        distances
+    }
+
+    fn finish(mut self, spawn: (usize, usize), rng: &mut Rng, rules: &TextureRules) -> Map {
+        let (width, height) = (self.width, self.height);
+        for x in 0..width {
+            self.set(x, 0, ROCK);
+            self.set(x, height - 1, ROCK);
+        }
+        for y in 0..height {
+            self.set(0, y, ROCK);
+            self.set(width - 1, y, ROCK);
+        }
+        // Generators put the spawn inside, but it may have been on the border that was just closed
+        let spawn = (spawn.0.clamp(1, width - 2), spawn.1.clamp(1, height - 2));
+        if !self.is_open(spawn.0, spawn.1) {
+            self.set(spawn.0, spawn.1, 0);
+        }
+        let distances = self.distances(spawn);
+        for (region, distance) in self.regions.iter_mut().zip(&distances) {
+            if distance.is_none() {
+                *region = ROCK;
+            }
+        }
+
+        // Textures of every region, in the order they first appear so they only depend on the seed
+        let mut textures: Vec<(usize, usize, usize)> = Vec::new();
+        let mut textures_of = |region: usize, rng: &mut Rng| {
+            if region == CORRIDOR {
+                return (rules.corridor_wall, rules.corridor_floor);
+            }
+            match textures.iter().find(|(r, _, _)| *r == region) {
+                Some(&(_, wall, floor)) => (wall, floor),
+                None => {
+                    let (wall, floor) = (*rng.pick(&rules.walls), *rng.pick(&rules.floors));
+                    textures.push((region, wall, floor));
+                    (wall, floor)
+                }
+            }
+        };
+
+        let mut map = Map::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let region = self.region(x, y);
+                let cell = if region != ROCK {
+                    -(textures_of(region, rng).1 as i8)
+                } else if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    rules.border as i8 + 1
+                } else {
+                    // Rock takes the wall texture of the first open neighbour, rock surrounded by rock is
+                    // never seen
+                    let neighbour = self
+                        .neighbours(x, y)
+                        .map(|(nx, ny)| self.region(nx, ny))
+                        .find(|region| *region != ROCK);
+                    let wall = neighbour.map_or(rules.border, |region| textures_of(region, rng).0);
+                    wall as i8 + 1
+                };
+                map.set(x, y, cell);
+            }
+        }
+
+        // Look along the longest open line from the spawn
+        let yaw = [0., 90., 180., 270.]
+            .into_iter()
+            .map(f64::to_radians)
+            .max_by_key(|yaw| {
+                let dir = Vec2::from_angle(*yaw);
+                (1..)
+                    .take_while(|step| {
+                        let cell = Vec2::new(spawn.0 as f64, spawn.1 as f64) + dir * *step as f64;
+                        self.is_open(cell.x.round() as usize, cell.y.round() as usize)
+                    })
+                    .count()
+            })
+            .unwrap();
+        map.spawn = Spawn {
+            pos: Vec2::new(spawn.0 as f64 + 0.5, spawn.1 as f64 + 0.5),
+            yaw,
+        };
+        map
+    }
+}
+
+// An axis aligned rectangle of cells
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    fn center(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+}
+
+fn rooms(grid: &mut Grid, rng: &mut Rng, settings: &RoomSettings) -> (usize, usize) {
+    let min_area = settings.min_area.max(settings.min_room + 2);
+    let area = Rect {
+        x: 1,
+        y: 1,
+        width: grid.width - 2,
+        height: grid.height - 2,
+    };
+    let mut rooms = Vec::new();
+    split(grid, rng, area, min_area, settings.min_room, &mut rooms);
+    rooms[0].center()
+}
+
+// Splits `area` until it's too small, puts a room into every part and connects the rooms of both halves.
+// Returns the rooms in `area`.
+fn split(
+    grid: &mut Grid,
+    rng: &mut Rng,
+    area: Rect,
+    min_area: usize,
+    min_room: usize,
+    rooms: &mut Vec<Rect>,
+) {
+    let split_x =
+        area.width >= 2 * min_area && (area.width >= area.height || area.height < 2 * min_area);
+    let split_y = !split_x && area.height >= 2 * min_area;
+
+    if !split_x && !split_y {
+        // Leave a wall between rooms of neighbouring areas
+        let max_width = area.width.saturating_sub(1).max(1);
+        let max_height = area.height.saturating_sub(1).max(1);
+        let width = rng.range(min_room.min(max_width), max_width + 1);
+        let height = rng.range(min_room.min(max_height), max_height + 1);
+        let room = Rect {
+            x: area.x + rng.range(0, max_width - width + 1),
+            y: area.y + rng.range(0, max_height - height + 1),
+            width,
+            height,
+        };
+        let region = rooms.len();
+        for y in room.y..room.y + room.height {
+            for x in room.x..room.x + room.width {
+                grid.set(x, y, region);
+            }
+        }
+        rooms.push(room);
+        return;
+    }
+
+    let (first, second) = if split_x {
+        let at = rng.range(min_area, area.width - min_area + 1);
+        (
+            Rect { width: at, ..area },
+            Rect {
+                x: area.x + at,
+                width: area.width - at,
+                ..area
+            },
+        )
+    } else {
+        let at = rng.range(min_area, area.height - min_area + 1);
+        (
+            Rect { height: at, ..area },
+            Rect {
+                y: area.y + at,
+                height: area.height - at,
+                ..area
+            },
+        )
+    };
+
+    let start = rooms.len();
+    split(grid, rng, first, min_area, min_room, rooms);
+    let middle = rooms.len();
+    split(grid, rng, second, min_area, min_room, rooms);
+    let from = rooms[rng.range(start, middle)].center();
+    let to = rooms[rng.range(middle, rooms.len())].center();
+    corridor(grid, rng, from, to);
+}
+
+// L-shaped corridor between two cells, bending either way
+fn corridor(grid: &mut Grid, rng: &mut Rng, from: (usize, usize), to: (usize, usize)) {
+    let corner = if rng.chance(0.5) {
+        (to.0, from.1)
+    } else {
+        (from.0, to.1)
+    };
+    for (a, b) in [(from, corner), (corner, to)] {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                // Corridors pass through rooms without changing them
+                if !grid.is_open(x, y) {
+                    grid.set(x, y, CORRIDOR);
+                }
+            }
+        }
+    }
+}
+
+fn caves(grid: &mut Grid, rng: &mut Rng, settings: &CaveSettings) -> (usize, usize) {
+    let (width, height) = (grid.width, grid.height);
+    let mut rock: Vec<bool> = (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            x == 0 || y == 0 || x == width - 1 || y == height - 1 || rng.chance(settings.fill)
+        })
+        .collect();
+
+    for _ in 0..settings.steps {
+        let previous = rock.clone();
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                let neighbours = (y - 1..=y + 1)
+                    .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                    .filter(|&(nx, ny)| (nx, ny) != (x, y) && previous[ny * width + nx])
+                    .count();
+                if neighbours > 4 {
+                    rock[y * width + x] = true;
+                } else if neighbours < 4 {
+                    rock[y * width + x] = false;
+                }
+            }
+        }
+    }
+    for (i, rock) in rock.iter().enumerate() {
+        if !rock {
+            grid.regions[i] = 0;
+        }
+    }
+
+    // Spawn in the largest cave, everything else gets filled in
+    let mut best: Option<((usize, usize), usize)> = None;
+    let mut seen = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            if !grid.is_open(x, y) || seen[y * width + x] {
+                continue;
+            }
+            let distances = grid.distances((x, y));
+            let size = distances.iter().flatten().count();
+            for (seen, distance) in seen.iter_mut().zip(&distances) {
+                *seen |= distance.is_some();
+            }
+            if best.is_none_or(|(_, best_size)| size > best_size) {
+                best = Some(((x, y), size));
+            }
+        }
+    }
+    let Some((spawn, size)) = best else {
+        // Solid rock, finish opens up the spawn
+        return (width / 2, height / 2);
+    };
+
+    // Regions are the areas closest to random points in the cave
+    let distances = grid.distances(spawn);
+    let cave: Vec<_> = (0..width * height)
+        .filter(|i| distances[*i].is_some())
+        .map(|i| (i % width, i / width))
+        .collect();
+    let centers: Vec<_> = (0..(size / settings.region_size.max(1)).max(1))
+        .map(|_| *rng.pick(&cave))
+        .collect();
+    for &(x, y) in &cave {
+        let region = (0..centers.len())
+            .min_by_key(|&i| {
+                let (cx, cy) = centers[i];
+                x.abs_diff(cx).pow(2) + y.abs_diff(cy).pow(2)
+            })
+            .unwrap();
+        grid.set(x, y, region);
+    }
+    spawn
+}
+
+fn maze(grid: &mut Grid, rng: &mut Rng, region_length: usize) -> (usize, usize) {
+    // Junctions are at odd coordinates, the cells between two of them are opened when the path goes through
+    let (columns, rows) = ((grid.width - 1) / 2, (grid.height - 1) / 2);
+    let mut visited = vec![false; columns * rows];
+    // Recursive backtracking, with the path length to color regions
+    let mut stack = vec![((0, 0), 0)];
+    visited[0] = true;
+    grid.set(1, 1, 0);
+    while let Some(&((column, row), length)) = stack.last() {
+        let unvisited: Vec<(usize, usize)> = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .map(|(dx, dy)| (column as i32 + dx, row as i32 + dy))
+            .filter(|(c, r)| *c >= 0 && *r >= 0 && (*c as usize) < columns && (*r as usize) < rows)
+            .map(|(c, r)| (c as usize, r as usize))
+            .filter(|(c, r)| !visited[r * columns + c])
+            .collect();
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+        let (next_column, next_row) = *rng.pick(&unvisited);
+        visited[next_row * columns + next_column] = true;
+        let region = (length + 2) / region_length.max(1);
+        grid.set(column + next_column + 1, row + next_row + 1, region);
+        grid.set(next_column * 2 + 1, next_row * 2 + 1, region);
+        stack.push(((next_column, next_row), length + 2));
+    }
+    (1, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::test_textures;
+
+    const SIZES: [(usize, usize); 5] = [(5, 5), (24, 24), (40, 17), (9, 64), (6, 8)];
+
+    fn generators() -> [Generator; 3] {
+        ["rooms", "caves", "maze"].map(|name| Generator::from_name(name).unwrap())
+    }
+
+    // Open cells, and whether all of them can be reached from the spawn
+    fn open_cells(map: &Map) -> (usize, bool) {
+        let mut grid = Grid::new(map.width(), map.height());
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                if !map.is_solid(x as i32, y as i32) {
+                    grid.set(x, y, 0);
+                }
+            }
+        }
+        let spawn = map.spawn.pos;
+        let distances = grid.distances((spawn.x as usize, spawn.y as usize));
+        let open = grid.regions.iter().filter(|r| **r != ROCK).count();
+        (open, distances.iter().flatten().count() == open)
+    }
+
+    #[test]
+    fn maps_are_valid() {
+        for generator in generators() {
+            for (width, height) in SIZES {
+                for seed in 0..20 {
+                    let map = generator.generate(width, height, seed, &TextureRules::default());
+                    let name = generator.name();
+                    assert_eq!(
+                        map.validate(test_textures().len()),
+                        [],
+                        "{name} {width}x{height} seed {seed}\n{map}"
+                    );
+                    let (open, connected) = open_cells(&map);
+                    assert!(
+                        open > 0 && connected,
+                        "{name} {width}x{height} seed {seed}\n{map}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_are_reproducible() {
+        for generator in generators() {
+            let rules = TextureRules::default();
+            let map = generator.generate(32, 24, 7, &rules);
+            assert_eq!(map, generator.generate(32, 24, 7, &rules));
+            assert_ne!(map, generator.generate(32, 24, 8, &rules));
+        }
+    }
+
+    #[test]
+    fn mazes_are_perfect() {
+        for seed in 0..10 {
+            let map = Generator::from_name("maze").unwrap().generate(
+                21,
+                15,
+                seed,
+                &TextureRules::default(),
+            );
+            // A tree of 10 x 7 junctions has one passage less than junctions
+            let (open, connected) = open_cells(&map);
+            assert!(connected);
+            assert_eq!(open, 10 * 7 + (10 * 7 - 1));
+        }
+    }
+
+    #[test]
+    fn regions_follow_the_rules() {
+        let rules = TextureRules {
+            border: 2,
+            walls: vec![4],
+            floors: vec![3],
+            corridor_wall: 4,
+            corridor_floor: 3,
+        };
+        for generator in generators() {
+            let map = generator.generate(30, 30, 1, &rules);
+            for y in 0..30 {
+                for x in 0..30 {
+                    let on_border = x == 0 || y == 0 || x == 29 || y == 29;
+                    // Rock that no region touches keeps the border texture
+                    let expected: &[i8] = if on_border { &[3] } else { &[3, 5, -3] };
+                    assert!(expected.contains(&map.cell(x, y)), "{map}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rules_are_checked() {
+        assert_eq!(TextureRules::default().check(), Ok(()));
+        let broken = [
+            TextureRules {
+                walls: vec![],
+                ..TextureRules::default()
+            },
+            TextureRules {
+                floors: vec![],
+                ..TextureRules::default()
+            },
+            TextureRules {
+                border: 127,
+                ..TextureRules::default()
+            },
+            TextureRules {
+                walls: vec![1, 200],
+                ..TextureRules::default()
+            },
+            TextureRules {
+                floors: vec![8],
+                ..TextureRules::default()
+            },
+            TextureRules {
+                corridor_floor: 8,
+                ..TextureRules::default()
+            },
+        ];
+        for rules in broken {
+            assert!(rules.check().is_err(), "{rules:?}");
+        }
+        let edge = TextureRules {
+            walls: vec![126],
+            floors: vec![7],
+            ..TextureRules::default()
+        };
+        assert_eq!(edge.check(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid texture rules")]
+    fn generate_rejects_broken_rules() {
+        let rules = TextureRules {
+            walls: vec![],
+            ..TextureRules::default()
+        };
+        Generator::from_name("rooms")
+            .unwrap()
+            .generate(10, 10, 0, &rules);
+    }
+}
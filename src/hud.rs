@@ -0,0 +1,191 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    font::{Font, DEFAULT_FONT},
+    framebuffer::Framebuffer,
+};
+
+const TEXT_COLOR: u32 = 0xffffff;
+const STATUS_BAR_LINE_COLOR: u32 = 0x808080;
+const CROSSHAIR_COLOR: u32 = 0xe0e0e0;
+// How often the FPS counter updates, more often makes it unreadable
+const FPS_INTERVAL: Duration = Duration::from_millis(500);
+// How long messages stay on screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+// Older messages are dropped when there are more
+const MAX_MESSAGES: usize = 4;
+
+/// Frames per second, averaged over a short interval.
+#[derive(Debug, Clone)]
+pub struct FpsCounter {
+    frames: u32,
+    since: Instant,
+    fps: f64,
+}
+
+impl Default for FpsCounter {
+    fn default() -> Self {
+        Self {
+            frames: 0,
+            since: Instant::now(),
+            fps: 0.,
+        }
+    }
+}
+
+impl FpsCounter {
+    /// Counts a frame that was presented at `now`.
+    pub fn frame(&mut self, now: Instant) {
+        self.frames += 1;
+        let elapsed = now.saturating_duration_since(self.since);
+        if elapsed >= FPS_INTERVAL {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.since = now;
+        }
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+}
+
+/// Status bar, crosshair, FPS counter and messages drawn on top of the 3D view.
+#[derive(Debug, Clone)]
+pub struct Hud {
+    pub visible: bool,
+    pub crosshair: bool,
+    pub show_fps: bool,
+    pub font: Font<'static>,
+    fps: FpsCounter,
+    // Text and when it was shown, oldest first
+    messages: Vec<(String, Instant)>,
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            crosshair: true,
+            show_fps: true,
+            font: DEFAULT_FONT,
+            fps: FpsCounter::default(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+impl Hud {
+    pub fn fps(&self) -> &FpsCounter {
+        &self.fps
+    }
+
+    /// Call once for every presented frame.
+    pub fn frame(&mut self, now: Instant) {
+        self.fps.frame(now);
+        self.messages
+            .retain(|(_, since)| now.saturating_duration_since(*since) < MESSAGE_DURATION);
+    }
+
+    /// Shows `text` in the middle of the top of the screen for a few seconds.
+    pub fn message(&mut self, text: impl Into<String>, now: Instant) {
+        self.messages.push((text.into(), now));
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+    }
+
+    /// Draws the HUD with `status` as the text of the status bar, an empty status hides the bar.
+    pub fn draw(&self, framebuffer: &mut Framebuffer, status: &str) {
+        if !self.visible {
+            return;
+        }
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+
+        if !status.is_empty() {
+            let (_, text_height) = self.font.measure(status);
+            let bar_height = text_height + 4;
+            let top = height as i32 - bar_height as i32;
+            framebuffer.darken_rect(0, top, width, bar_height);
+            framebuffer.fill_rect(0, top, width, 1, STATUS_BAR_LINE_COLOR);
+            framebuffer.text(2, top + 2, status, &self.font, TEXT_COLOR);
+        }
+
+        if self.crosshair {
+            // A plus with a gap in the middle, so it doesn't hide what it's pointing at
+            let (x, y) = (width as i32 / 2, height as i32 / 2);
+            framebuffer.line(x - 4, y, x - 2, y, CROSSHAIR_COLOR);
+            framebuffer.line(x + 2, y, x + 4, y, CROSSHAIR_COLOR);
+            framebuffer.line(x, y - 4, x, y - 2, CROSSHAIR_COLOR);
+            framebuffer.line(x, y + 2, x, y + 4, CROSSHAIR_COLOR);
+        }
+
+        let mut top = 2;
+        for (text, _) in &self.messages {
+            let (text_width, text_height) = self.font.measure(text);
+            let left = (width as i32 - text_width as i32) / 2;
+            framebuffer.darken_rect(left - 2, top - 2, text_width + 4, text_height + 4);
+            framebuffer.text(left, top, text, &self.font, TEXT_COLOR);
+            top += text_height as i32 + 4;
+        }
+
+        if self.show_fps {
+            let text = format!("{:.0} FPS", self.fps.fps());
+            let (text_width, text_height) = self.font.measure(&text);
+            framebuffer.darken_rect(0, 0, text_width + 4, text_height + 4);
+            framebuffer.text(2, 2, &text, &self.font, TEXT_COLOR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(hud: &Hud) -> Vec<&str> {
+        hud.messages.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    #[test]
+    fn messages_expire() {
+        let start = Instant::now();
+        let mut hud = Hud::default();
+        hud.message("first", start);
+        hud.message("second", start + Duration::from_secs(1));
+
+        hud.frame(start + MESSAGE_DURATION - Duration::from_millis(1));
+        assert_eq!(texts(&hud), ["first", "second"]);
+        hud.frame(start + MESSAGE_DURATION);
+        assert_eq!(texts(&hud), ["second"]);
+        hud.frame(start + Duration::from_secs(1) + MESSAGE_DURATION);
+        assert!(texts(&hud).is_empty());
+
+        for i in 0..MAX_MESSAGES + 2 {
+            hud.message(i.to_string(), start);
+        }
+        assert_eq!(texts(&hud), ["2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn draws_messages_until_they_expire() {
+        let start = Instant::now();
+        let mut hud = Hud {
+            crosshair: false,
+            show_fps: false,
+            ..Hud::default()
+        };
+        let mut framebuffer = Framebuffer::new(40, 20);
+        hud.message("-", start);
+        hud.draw(&mut framebuffer, "");
+        // Centered at the top, '-' is the middle row of the glyph
+        let left = (40 - 5) / 2;
+        for x in left..left + 5 {
+            assert_eq!(framebuffer.get(x, 2 + 3), TEXT_COLOR);
+        }
+
+        hud.frame(start + MESSAGE_DURATION);
+        let mut framebuffer = Framebuffer::new(40, 20);
+        hud.draw(&mut framebuffer, "");
+        assert!(framebuffer.pixels().iter().all(|&pixel| pixel == 0));
+    }
+}
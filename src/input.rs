@@ -0,0 +1,427 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs, io,
+    path::Path,
+};
+
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+};
+
+/// Something the player can do, independent of the key or button that triggers it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Run,
+    Use,
+    Fire,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Run,
+        Action::Use,
+        Action::Fire,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "MoveForward",
+            Action::MoveBackward => "MoveBackward",
+            Action::StrafeLeft => "StrafeLeft",
+            Action::StrafeRight => "StrafeRight",
+            Action::TurnLeft => "TurnLeft",
+            Action::TurnRight => "TurnRight",
+            Action::Run => "Run",
+            Action::Use => "Use",
+            Action::Fire => "Fire",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A physical input that can be bound to an [`Action`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "MouseLeft" => Some(Binding::Mouse(MouseButton::Left)),
+            "MouseRight" => Some(Binding::Mouse(MouseButton::Right)),
+            "MouseMiddle" => Some(Binding::Mouse(MouseButton::Middle)),
+            _ if name.starts_with("Mouse") && name.len() > 5 => name[5..]
+                .parse()
+                .ok()
+                .map(|n| Binding::Mouse(MouseButton::Other(n))),
+            _ => key_from_name(name).map(Binding::Key),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{key:?}"),
+            Binding::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Binding::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Binding::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Binding::Mouse(MouseButton::Other(n)) => write!(f, "Mouse{n}"),
+        }
+    }
+}
+
+// winit doesn't provide a way to parse key names, so list every key under the name `Debug` gives it. Bindings
+// are displayed with those names, anything missing here couldn't be loaded back.
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+key_names!(
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L,
+    M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11,
+    F12, F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause,
+    Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right, Down, Back, Return, Space,
+    Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7,
+    Numpad8, Numpad9, NumpadAdd, NumpadDivide, NumpadDecimal, NumpadComma, NumpadEnter,
+    NumpadEquals, NumpadMultiply, NumpadSubtract, AbntC1, AbntC2, Apostrophe, Apps, Asterisk, At,
+    Ax, Backslash, Calculator, Capital, Colon, Comma, Convert, Equals, Grave, Kana, Kanji, LAlt,
+    LBracket, LControl, LShift, LWin, Mail, MediaSelect, MediaStop, Minus, Mute, MyComputer,
+    NavigateForward, NavigateBackward, NextTrack, NoConvert, OEM102, Period, PlayPause, Plus, Power,
+    PrevTrack, RAlt, RBracket, RControl, RShift, RWin, Semicolon, Slash, Sleep, Stop, Sysrq, Tab,
+    Underline, Unlabeled, VolumeDown, VolumeUp, Wake, WebBack, WebFavorites, WebForward, WebHome,
+    WebRefresh, WebSearch, WebStop, Yen, Copy, Paste, Cut,
+);
+
+/// Keys the demo handles itself before [`Input`] sees them, [`Bindings::parse`] rejects them.
+pub const RESERVED_KEYS: [VirtualKeyCode; 12] = [
+    VirtualKeyCode::F1,
+    VirtualKeyCode::F2,
+    VirtualKeyCode::F3,
+    VirtualKeyCode::F4,
+    VirtualKeyCode::F5,
+    VirtualKeyCode::F9,
+    VirtualKeyCode::F11,
+    VirtualKeyCode::F12,
+    VirtualKeyCode::Tab,
+    VirtualKeyCode::PageUp,
+    VirtualKeyCode::PageDown,
+    // Releases the cursor while it's grabbed
+    VirtualKeyCode::Escape,
+];
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Io(e) => write!(f, "couldn't read bindings: {e}"),
+            BindingsError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for BindingsError {}
+
+impl From<io::Error> for BindingsError {
+    fn from(e: io::Error) -> Self {
+        BindingsError::Io(e)
+    }
+}
+
+/// Maps keys and mouse buttons to actions. An action can have any number of bindings.
+///
+/// The text format is one action per line, `Action = Binding, Binding...`, with `#` starting a comment:
+/// ```text
+/// MoveForward = W, Up
+/// Run = LShift, Space
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    map: HashMap<Binding, Action>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+        let mut bindings = Self::empty();
+        for (key, action) in [
+            (W, Action::MoveForward),
+            (Up, Action::MoveForward),
+            (S, Action::MoveBackward),
+            (Down, Action::MoveBackward),
+            (A, Action::StrafeLeft),
+            (D, Action::StrafeRight),
+            (Left, Action::TurnLeft),
+            (Right, Action::TurnRight),
+            (LShift, Action::Run),
+            (Space, Action::Run),
+            (E, Action::Use),
+            (LControl, Action::Fire),
+        ] {
+            bindings.bind(Binding::Key(key), action);
+        }
+        bindings.bind(Binding::Mouse(MouseButton::Left), Action::Fire);
+        bindings
+    }
+}
+
+impl Bindings {
+    pub fn empty() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Binds `binding` to `action`, replacing whatever it was bound to before.
+    pub fn bind(&mut self, binding: Binding, action: Action) {
+        self.map.insert(binding, action);
+    }
+
+    pub fn unbind(&mut self, binding: Binding) {
+        self.map.remove(&binding);
+    }
+
+    pub fn action(&self, binding: Binding) -> Option<Action> {
+        self.map.get(&binding).copied()
+    }
+
+    /// All bindings of `action`, sorted by name so the output is stable.
+    pub fn bindings_of(&self, action: Action) -> Vec<Binding> {
+        let mut bindings: Vec<_> = self
+            .map
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(b, _)| *b)
+            .collect();
+        bindings.sort_by_key(|b| b.to_string());
+        bindings
+    }
+
+    pub fn parse(src: &str) -> Result<Self, BindingsError> {
+        let mut bindings = Self::empty();
+        for (i, line) in src.lines().enumerate() {
+            let error = |message: String| BindingsError::Parse {
+                line: i + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (action, keys) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `Action = Key, ...`, got `{line}`")))?;
+            let action = Action::from_name(action.trim())
+                .ok_or_else(|| error(format!("unknown action `{}`", action.trim())))?;
+            for key in keys.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+                let binding =
+                    Binding::from_name(key).ok_or_else(|| error(format!("unknown key `{key}`")))?;
+                if matches!(binding, Binding::Key(key) if RESERVED_KEYS.contains(&key)) {
+                    return Err(error(format!("`{key}` is reserved by the game")));
+                }
+                bindings.bind(binding, action);
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BindingsError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Bindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for action in Action::ALL {
+            let bindings = self.bindings_of(action);
+            if bindings.is_empty() {
+                continue;
+            }
+            let bindings: Vec<_> = bindings.iter().map(|b| b.to_string()).collect();
+            writeln!(f, "{} = {}", action.name(), bindings.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Tracks which actions are held and how far the mouse moved since it was last read.
+///
+/// Besides keys and buttons, actions can be driven by analog sources (e.g. a gamepad stick) through
+/// [`Input::set_analog`], everything reading actions sees both.
+#[derive(Debug)]
+pub struct Input {
+    pub bindings: Bindings,
+    /// Radians of rotation per pixel of mouse movement.
+    pub mouse_sensitivity: f64,
+    held: HashSet<Binding>,
+    analog: HashMap<Action, f64>,
+    mouse_delta: (f64, f64),
+    mouse_look: bool,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            mouse_sensitivity: 0.003,
+            held: HashSet::new(),
+            analog: HashMap::new(),
+            mouse_delta: (0., 0.),
+            mouse_look: false,
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => self.set_held(Binding::Key(key), state),
+            WindowEvent::MouseInput { button, state, .. } => {
+                self.set_held(Binding::Mouse(button), state)
+            }
+            // Don't keep moving after alt-tabbing away while a key was held
+            WindowEvent::Focused(false) => self.held.clear(),
+            _ => (),
+        }
+    }
+
+    /// Raw mouse motion, only recorded while mouse look is enabled.
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = *event {
+            if self.mouse_look {
+                self.mouse_delta.0 += dx;
+                self.mouse_delta.1 += dy;
+            }
+        }
+    }
+
+    fn set_held(&mut self, binding: Binding, state: ElementState) {
+        match state {
+            ElementState::Pressed => self.held.insert(binding),
+            ElementState::Released => self.held.remove(&binding),
+        };
+    }
+
+    /// Sets how strongly an analog source is pushing `action`, `1.` being the same as a held key and
+    /// `0.` releasing it.
+    pub fn set_analog(&mut self, action: Action, value: f64) {
+        let value = value.max(0.);
+        if value > 0. {
+            self.analog.insert(action, value);
+        } else {
+            self.analog.remove(&action);
+        }
+    }
+
+    /// Whether a key or button bound to `action` is held, or an analog source pushes it more than halfway.
+    pub fn is_down(&self, action: Action) -> bool {
+        self.value(action) > 0.5
+    }
+
+    /// How strongly `action` is requested, `1.` for held keys.
+    pub fn value(&self, action: Action) -> f64 {
+        let digital = self
+            .held
+            .iter()
+            .any(|binding| self.bindings.action(*binding) == Some(action));
+        if digital {
+            1.
+        } else {
+            self.analog.get(&action).copied().unwrap_or(0.)
+        }
+    }
+
+    /// `value(positive) - value(negative)`, so `1.` if only `positive` is fully held and `-1.` if only
+    /// `negative` is.
+    pub fn axis(&self, negative: Action, positive: Action) -> f64 {
+        self.value(positive) - self.value(negative)
+    }
+
+    pub fn mouse_look(&self) -> bool {
+        self.mouse_look
+    }
+
+    pub fn set_mouse_look(&mut self, enabled: bool) {
+        self.mouse_look = enabled;
+        self.mouse_delta = (0., 0.);
+    }
+
+    /// Rotation in radians requested by the mouse since the last call, positive is to the left
+    /// (same as `Vec2::rotate`).
+    pub fn take_mouse_turn(&mut self) -> f64 {
+        let (dx, _) = std::mem::take(&mut self.mouse_delta);
+        -dx * self.mouse_sensitivity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let mut bindings = Bindings::default();
+        bindings.bind(Binding::Mouse(MouseButton::Other(4)), Action::Use);
+        bindings.bind(Binding::Key(VirtualKeyCode::NumpadEnter), Action::Use);
+        bindings.bind(Binding::Key(VirtualKeyCode::WebBack), Action::Run);
+        assert_eq!(Bindings::parse(&bindings.to_string()).unwrap(), bindings);
+    }
+
+    #[test]
+    fn errors() {
+        for (src, line) in [
+            ("Run = LShift\nFly = Space", 2),
+            ("Run = Shift", 1),
+            ("Run LShift", 1),
+            ("Run = Mouse", 1),
+            ("# comment\nUse = F5", 2),
+            ("Use = E, PageUp", 1),
+        ] {
+            assert!(
+                matches!(Bindings::parse(src), Err(BindingsError::Parse { line: l, .. }) if l == line),
+                "{src}"
+            );
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Text format of maps.
+//!
+//! One statement per line, `#` starts a comment. `size` has to come before `cells`, which is followed by one
+//! line per row from y = 0 down, each listing the cells of x = 0.. separated by whitespace. Angles are in radians.
+//! ```text
+//! size 4 3
+//! out_of_bounds wall
+//! spawn 1.5 1.5 0
+//! cells
+//! 1  1 1 1
+//! 1 -3 0 2
+//! 1  1 1 1
+//! door 3 1 2 closed
+//! entity barrel 2.5 1.5 0.3
+//! trigger exit 2 1 1 1 once door_open:0 end_level
+//! script scripts/intro.rhai
+//! ```
+//! The `cell` of a door is its wall when closed, whatever `cells` has in its place is ignored.
+//!
+//! Triggers are `trigger NAME X Y WIDTH HEIGHT once|repeat CONDITION ACTION...` where the condition is `always`,
+//! `door_open:DOOR`, `door_closed:DOOR` or `fired:NAME` and the action is the rest of the line. Script paths are
+//! relative to the level file and can't contain spaces.
+
+use std::{fmt, fs, io, path::Path};
+
+use crate::{
+    map::{Entity, Map, OutOfBounds, Spawn},
+    math::Vec2,
+    trigger::{Area, Condition, Mode, Trigger},
+};
+
+/// Largest width and height a level can have. Cells are addressed with `i32`, this keeps every cell and the
+/// cell count well inside of it.
+pub const MAX_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(e) => write!(f, "couldn't read level: {e}"),
+            LevelError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<io::Error> for LevelError {
+    fn from(e: io::Error) -> Self {
+        LevelError::Io(e)
+    }
+}
+
+impl OutOfBounds {
+    pub fn name(self) -> &'static str {
+        match self {
+            OutOfBounds::Wall => "wall",
+            OutOfBounds::Void => "void",
+            OutOfBounds::Wrap => "wrap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [OutOfBounds::Wall, OutOfBounds::Void, OutOfBounds::Wrap]
+            .into_iter()
+            .find(|o| o.name() == name)
+    }
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Once => "once",
+            Mode::Repeat => "repeat",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Mode::Once, Mode::Repeat]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::Always => write!(f, "always"),
+            Condition::DoorOpen(door) => write!(f, "door_open:{door}"),
+            Condition::DoorClosed(door) => write!(f, "door_closed:{door}"),
+            Condition::Fired(name) => write!(f, "fired:{name}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "always" => Ok(Condition::Always),
+            Some(("door_open", door)) => Ok(Condition::DoorOpen(number(door)?)),
+            Some(("door_closed", door)) => Ok(Condition::DoorClosed(number(door)?)),
+            Some(("fired", name)) if !name.is_empty() => Ok(Condition::Fired(name.to_string())),
+            _ => Err(format!("unknown trigger condition `{s}`")),
+        }
+    }
+}
+
+pub(crate) fn number<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("`{word}` isn't a valid number"))
+}
+
+// Whether `s` survives being written as one value of a statement: not empty, no spaces and no `#`
+pub(crate) fn is_word(s: &str) -> bool {
+    !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || c == '#')
+}
+
+// Splits a statement into exactly N arguments after its keyword
+pub(crate) fn arguments<'a, const N: usize>(
+    keyword: &str,
+    words: &[&'a str],
+) -> Result<[&'a str; N], String> {
+    words
+        .try_into()
+        .map_err(|_| format!("`{keyword}` takes {N} values, got {}", words.len()))
+}
+
+impl Map {
+    pub fn parse(src: &str) -> Result<Self, LevelError> {
+        let mut map: Option<Map> = None;
+        // Row of the cells section that's read next, None outside of it
+        let mut row = None;
+        let mut spawn = None;
+        let mut out_of_bounds = OutOfBounds::default();
+        let mut doors = Vec::new();
+        let mut entities = Vec::new();
+        let mut triggers = Vec::new();
+        let mut scripts = Vec::new();
+
+        for (i, line) in src.lines().enumerate() {
+            let error = |message: String| LevelError::Parse {
+                line: i + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+
+            if let (Some(y), Some(map)) = (row, map.as_mut()) {
+                if words.len() != map.width() {
+                    return Err(error(format!(
+                        "row {y} has {} cells instead of {}",
+                        words.len(),
+                        map.width()
+                    )));
+                }
+                for (x, word) in words.iter().enumerate() {
+                    map.set(x, y, number(word).map_err(error)?);
+                }
+                row = (y + 1 < map.height()).then_some(y + 1);
+                continue;
+            }
+
+            let (keyword, words) = (words[0], &words[1..]);
+            match keyword {
+                "size" => {
+                    if map.is_some() {
+                        return Err(error("the size is already set".to_string()));
+                    }
+                    let [width, height] = arguments(keyword, words).map_err(error)?;
+                    let (width, height): (usize, usize) = (
+                        number(width).map_err(error)?,
+                        number(height).map_err(error)?,
+                    );
+                    if width.max(height) > MAX_SIZE
+                        || width
+                            .checked_mul(height)
+                            .is_none_or(|cells| cells > i32::MAX as usize)
+                    {
+                        return Err(error(format!(
+                            "{width}x{height} is larger than the maximum of {MAX_SIZE}x{MAX_SIZE}"
+                        )));
+                    }
+                    map = Some(Map::new(width, height));
+                }
+                "out_of_bounds" => {
+                    let [name] = arguments(keyword, words).map_err(error)?;
+                    out_of_bounds = OutOfBounds::from_name(name)
+                        .ok_or_else(|| error(format!("unknown out of bounds policy `{name}`")))?;
+                }
+                "spawn" => {
+                    let [x, y, yaw] = arguments(keyword, words).map_err(error)?;
+                    spawn = Some(Spawn {
+                        pos: Vec2::new(number(x).map_err(error)?, number(y).map_err(error)?),
+                        yaw: number(yaw).map_err(error)?,
+                    });
+                }
+                "cells" => {
+                    let map = map
+                        .as_ref()
+                        .ok_or_else(|| error("`cells` before `size`".to_string()))?;
+                    row = (map.height() > 0).then_some(0);
+                }
+                "door" => {
+                    let [x, y, cell, state] = arguments(keyword, words).map_err(error)?;
+                    let (x, y): (usize, usize) =
+                        (number(x).map_err(error)?, number(y).map_err(error)?);
+                    let cell: i8 = number(cell).map_err(error)?;
+                    let open = match state {
+                        "open" => true,
+                        "closed" => false,
+                        _ => {
+                            return Err(error(format!(
+                                "expected `open` or `closed`, got `{state}`"
+                            )))
+                        }
+                    };
+                    if cell <= 0 {
+                        return Err(error(format!("doors need a wall cell, got {cell}")));
+                    }
+                    doors.push((i + 1, x, y, cell, open));
+                }
+                "entity" => {
+                    let [kind, x, y, radius] = arguments(keyword, words).map_err(error)?;
+                    entities.push(Entity {
+                        kind: kind.to_string(),
+                        pos: Vec2::new(number(x).map_err(error)?, number(y).map_err(error)?),
+                        radius: number(radius).map_err(error)?,
+                    });
+                }
+                "trigger" if words.len() >= 7 => {
+                    let [name, x, y, width, height, mode, condition] =
+                        arguments(keyword, &words[..7]).map_err(error)?;
+                    triggers.push((
+                        i + 1,
+                        Trigger {
+                            name: name.to_string(),
+                            area: Area {
+                                x: number(x).map_err(error)?,
+                                y: number(y).map_err(error)?,
+                                width: number(width).map_err(error)?,
+                                height: number(height).map_err(error)?,
+                            },
+                            mode: Mode::from_name(mode).ok_or_else(|| {
+                                error(format!("expected `once` or `repeat`, got `{mode}`"))
+                            })?,
+                            condition: condition.parse().map_err(error)?,
+                            action: words[7..].join(" "),
+                        },
+                    ));
+                }
+                "trigger" => {
+                    return Err(error(format!(
+                        "`trigger` takes at least 7 values, got {}",
+                        words.len()
+                    )))
+                }
+                "script" => {
+                    let [path] = arguments(keyword, words).map_err(error)?;
+                    scripts.push(path.to_string());
+                }
+                _ => return Err(error(format!("unknown statement `{keyword}`"))),
+            }
+        }
+
+        let error = |message: &str| LevelError::Parse {
+            line: src.lines().count(),
+            message: message.to_string(),
+        };
+        let mut map = map.ok_or_else(|| error("missing `size`"))?;
+        if row.is_some() {
+            return Err(error("the file ended in the middle of `cells`"));
+        }
+        for (line, x, y, cell, open) in doors {
+            if x >= map.width() || y >= map.height() {
+                return Err(LevelError::Parse {
+                    line,
+                    message: format!("door ({x}, {y}) is outside of the map"),
+                });
+            }
+            let index = map.add_door(x, y, cell);
+            map.set_door_open(index, open);
+        }
+        map.set_out_of_bounds(out_of_bounds);
+        if let Some(spawn) = spawn {
+            map.spawn = spawn;
+        }
+        map.entities = entities;
+        for (line, trigger) in triggers {
+            let Area {
+                x,
+                y,
+                width,
+                height,
+            } = trigger.area;
+            let fits = |start: usize, length: usize, size: usize| {
+                start.checked_add(length).is_some_and(|end| end <= size)
+            };
+            if !fits(x, width, map.width()) || !fits(y, height, map.height()) {
+                return Err(LevelError::Parse {
+                    line,
+                    message: format!(
+                        "trigger {} ({x}, {y}) {width}x{height} is outside of the map",
+                        trigger.name
+                    ),
+                });
+            }
+            map.triggers.push(trigger);
+        }
+        map.scripts = scripts;
+        Ok(map)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "size {} {}", self.width(), self.height())?;
+        writeln!(f, "out_of_bounds {}", self.out_of_bounds().name())?;
+        let Spawn { pos, yaw } = self.spawn;
+        writeln!(f, "spawn {} {} {yaw}", pos.x, pos.y)?;
+        writeln!(f, "cells")?;
+        for y in 0..self.height() as i32 {
+            let row: Vec<_> = (0..self.width() as i32)
+                .map(|x| format!("{:>3}", self.cell(x, y)))
+                .collect();
+            writeln!(f, "{}", row.join(" "))?;
+        }
+        for door in self.doors() {
+            let state = if door.open { "open" } else { "closed" };
+            writeln!(f, "door {} {} {} {state}", door.x, door.y, door.cell)?;
+        }
+        for Entity { kind, pos, radius } in &self.entities {
+            writeln!(f, "entity {kind} {} {} {radius}", pos.x, pos.y)?;
+        }
+        for trigger in &self.triggers {
+            let Area {
+                x,
+                y,
+                width,
+                height,
+            } = trigger.area;
+            writeln!(
+                f,
+                "trigger {} {x} {y} {width} {height} {} {} {}",
+                trigger.name,
+                trigger.mode.name(),
+                trigger.condition,
+                trigger.action
+            )?;
+        }
+        for script in &self.scripts {
+            writeln!(f, "script {script}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut map = Map::test_level();
+        map.set_out_of_bounds(OutOfBounds::Void);
+        let door = map.add_door(11, 11, 5);
+        map.set_door_open(door, true);
+        map.add_door(2, 2, 3);
+        map.entities.push(Entity {
+            kind: "barrel".to_string(),
+            pos: Vec2::new(3.25, 4.5),
+            radius: 0.3,
+        });
+        map.triggers.push(Trigger {
+            name: "vault".to_string(),
+            area: Area {
+                x: 3,
+                y: 4,
+                width: 2,
+                height: 5,
+            },
+            mode: Mode::Repeat,
+            condition: Condition::Fired("key".to_string()),
+            action: "open_door 0".to_string(),
+        });
+        map.scripts.push("scripts/vault.rhai".to_string());
+        assert_eq!(Map::parse(&map.to_string()).unwrap(), map);
+    }
+
+    #[test]
+    fn parses_the_documented_example() {
+        let map = Map::parse(
+            "size 4 3
+            out_of_bounds wall
+            spawn 1.5 1.5 0 # facing +x
+            cells
+            1  1 1 1
+            1 -3 0 2
+            1  1 1 1
+            door 3 1 2 closed
+            entity barrel 2.5 1.5 0.3
+            trigger exit 2 1 1 1 once door_open:0 end_level
+            script scripts/intro.rhai",
+        )
+        .unwrap();
+        assert_eq!((map.width(), map.height()), (4, 3));
+        assert_eq!(map.get(1, 1), Some(-3));
+        assert_eq!(map.door_at(3, 1), Some(0));
+        assert_eq!(map.entities[0].kind, "barrel");
+        assert_eq!(map.triggers[0].area, Area::cell(2, 1));
+        assert_eq!(map.triggers[0].condition, Condition::DoorOpen(0));
+        assert_eq!(map.triggers[0].action, "end_level");
+        assert_eq!(map.scripts, ["scripts/intro.rhai"]);
+    }
+
+    #[test]
+    fn reports_the_line() {
+        for (src, line) in [
+            ("cells", 1),
+            ("size 2 1\ncells\n0 0\nspawn 1 1", 4),
+            ("size 2 2\ncells\n0 0 0", 3),
+            ("size 2 1\n\ncells\n0 x", 4),
+            ("size 2 2\ncells\n0 0", 3),
+            ("size 2 1\ndoor 5 0 1 open\ncells\n0 0", 2),
+            ("size 1 1\nteleporter 0 0", 2),
+            ("size 1 1\ntrigger a 0 0 1 1 once sometimes x", 2),
+            ("size 1 1\n\ntrigger a 0 0 1 1 once", 3),
+            (
+                "size 2 2\ntrigger t 1 1 2147483647 1 once always end_level",
+                2,
+            ),
+            (
+                "trigger t 0 0 1 1 once always x\nsize 1 1\ntrigger t 1 0 1 1 once always x",
+                3,
+            ),
+            (
+                "size 2 2\ntrigger t 1 18446744073709551615 1 1 once always x",
+                2,
+            ),
+            ("\nsize 4097 1", 2),
+            ("size 1 4097", 1),
+            ("size 4294967296 4294967296", 1),
+            ("size 18446744073709551615 18446744073709551615", 1),
+            ("size -1 2", 1),
+        ] {
+            match Map::parse(src) {
+                Err(LevelError::Parse { line: l, .. }) => assert_eq!(l, line, "{src}"),
+                other => panic!("{src}: {other:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+pub mod audio;
+pub mod camera;
+pub mod capture;
+pub mod consts;
+pub mod editor;
+pub mod floor;
+pub mod font;
+pub mod framebuffer;
+pub mod game_loop;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod generate;
+pub mod hud;
+pub mod input;
+pub mod level;
+pub mod map;
+pub mod math;
+pub mod minimap;
+#[cfg(feature = "palette")]
+pub mod palette;
+pub mod path;
+pub mod player;
+pub mod projection;
+pub mod ray;
+pub mod render;
+pub mod save;
+pub mod scale;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod texture;
+pub mod trigger;
+pub mod validate;
+pub mod view_model;
@@ -1,31 +1,109 @@
-use std::{
-    num::NonZeroU32,
-    time::{Duration, Instant},
+use std::{collections::BTreeMap, env, num::NonZeroU32, time::Instant};
+
+use rayguy::{
+    audio::{Audio, Mixer, NullBackend, PlayOptions, Sound, Source},
+    camera::Camera,
+    capture::{self, FrameRecorder, ImageFormat},
+    consts::*,
+    framebuffer::Framebuffer,
+    game_loop::{FramePacing, GameLoop},
+    generate::{Generator, TextureRules},
+    hud::Hud,
+    input::{Action, Bindings, Input},
+    map::Map,
+    minimap::{self, Explored, Minimap},
+    player::{PlayerController, PlayerEvent},
+    save::SaveGame,
+    scale::{self, ScaleMode},
+    texture::test_textures,
+    trigger::Triggers,
+    view_model::ViewModel,
 };
-
-mod consts;
-mod math;
-use consts::*;
-
-use ezbuffer::WrapBuffer;
-// use ezbuffer::{Color, BLUE, GREEN, RED, WHITE, YELLOW};
-use math::Vec2;
 use winit::{
     dpi::LogicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{CursorGrabMode, Window, WindowBuilder},
 };
 
-fn get_time() -> u128 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let stop = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-    stop.as_millis()
+fn set_mouse_look(window: &Window, input: &mut Input, enabled: bool) {
+    let grab = if enabled {
+        // Not every platform supports both modes
+        window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+    } else {
+        window.set_cursor_grab(CursorGrabMode::None)
+    };
+    let enabled = enabled && grab.is_ok();
+    window.set_cursor_visible(!enabled);
+    input.set_mouse_look(enabled);
+}
+
+/// Runs the action of a trigger that fired, returns false when it ends the level.
+fn run_trigger(map: &mut Map, action: &str) -> bool {
+    let words: Vec<_> = action.split_whitespace().collect();
+    match words.as_slice() {
+        [verb @ ("open_door" | "close_door"), door] => match door.parse() {
+            Ok(door) if door < map.doors().len() => map.set_door_open(door, *verb == "open_door"),
+            _ => eprintln!("Trigger action `{action}` refers to a missing door"),
+        },
+        ["end_level"] => {
+            println!("Level complete");
+            return false;
+        }
+        _ if map.scripts.is_empty() => eprintln!("Unknown trigger action `{action}`"),
+        // Left to the level's scripts
+        _ => {}
+    }
+    true
 }
 
 fn main() {
+    // A level file can be passed as the first argument, or `--generate rooms|caves|maze [SEED]` for a random one
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut map = match args.first() {
+        Some(flag) if flag == "--generate" => {
+            let generator = args.get(1).and_then(|name| Generator::from_name(name));
+            let seed = args.get(2).map_or(Some(0), |seed| seed.parse().ok());
+            let (Some(generator), Some(seed)) = (generator, seed) else {
+                eprintln!("usage: rayguy --generate rooms|caves|maze [SEED]");
+                return;
+            };
+            generator.generate(32, 32, seed, &TextureRules::default())
+        }
+        Some(path) => match Map::load(path) {
+            Ok(map) => map,
+            Err(e) => {
+                eprintln!("{path}: {e}");
+                return;
+            }
+        },
+        None => Map::test_level(),
+    };
+    for diagnostic in map.validate(test_textures().len()) {
+        eprintln!("Warning, {diagnostic}");
+    }
+    #[cfg(feature = "scripting")]
+    let mut scripts = {
+        // Script paths are relative to the level file
+        let dir = args
+            .first()
+            .and_then(|path| std::path::Path::new(path).parent())
+            .unwrap_or(std::path::Path::new(""));
+        match rayguy::script::Scripts::for_map(&map, dir) {
+            Ok(scripts) => scripts,
+            Err(e) => {
+                eprintln!("{e}");
+                return;
+            }
+        }
+    };
+    #[cfg(not(feature = "scripting"))]
+    if !map.scripts.is_empty() {
+        eprintln!("Warning, the level's scripts only run with the `scripting` feature");
+    }
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_inner_size(LogicalSize::new(SCREEN_WIDTH, SCREEN_HEIGHT))
@@ -36,334 +114,329 @@ fn main() {
     let context = unsafe { softbuffer::Context::new(&window) }.unwrap();
     let mut surface = unsafe { softbuffer::Surface::new(&context, &window) }.unwrap();
 
-    // FOV = 2 * atan(0.66/1.0)=66°
-    let mut pos = Vec2::new(22., 12.); // x and y start position
-    let mut dir = Vec2::new(-1., 0.); // initial direction vector
-    let mut plane = Vec2::new(0., 0.66); // the 2d raycaster version of camera plane
-
-    let mut time = 0u64;
-    let mut frame_time = 0u64;
-
-    let mut pressed_keys = [false; 256];
-
-    // TEMPORARY CODE TO GENERATE TEXTURES TODO remove later
-    let mut texture = vec![vec![0u32; (TEXTURE_WIDTH * TEXTURE_HEIGHT) as usize]; 8];
-    for x in 0..TEXTURE_WIDTH {
-        for y in 0..TEXTURE_HEIGHT {
-            let xor_color = (x * 256 / TEXTURE_WIDTH) ^ (y * 256 / TEXTURE_HEIGHT);
-            //int xcolor = x * 256 / texWidth;
-            let y_color = y * 256 / TEXTURE_HEIGHT;
-            let xy_color = y * 128 / TEXTURE_HEIGHT + x * 128 / TEXTURE_WIDTH;
-            texture[0][(TEXTURE_WIDTH * y + x) as usize] =
-                65536 * 254 * ((x != y) && x != (TEXTURE_WIDTH - y)) as u32; //flat red texture with black cross
-            texture[1][(TEXTURE_WIDTH * y + x) as usize] =
-                xy_color + 256 * xy_color + 65536 * xy_color; //sloped greyscale
-            texture[2][(TEXTURE_WIDTH * y + x) as usize] = 256 * xy_color + 65536 * xy_color; //sloped yellow gradient
-            texture[3][(TEXTURE_WIDTH * y + x) as usize] =
-                xor_color + 256 * xor_color + 65536 * xor_color; //xor greyscale
-            texture[4][(TEXTURE_WIDTH * y + x) as usize] = 256 * xor_color; //xor green
-            texture[5][(TEXTURE_WIDTH * y + x) as usize] =
-                65536 * 192 * ((x % 16 != 0) && (y % 16 != 0)) as u32; //red bricks
-            texture[6][(TEXTURE_WIDTH * y + x) as usize] = 65536 * y_color; //red gradient
-            texture[7][(TEXTURE_WIDTH * y + x) as usize] = 128 + 256 * 128 + 65536 * 128;
-            //flat grey texture
+    let mut camera = Camera::new(map.spawn.pos, map.spawn.yaw, FOV.to_radians());
+    // Camera state at the previous tick, rendering interpolates between the two
+    let mut previous_camera = camera;
+    let mut alpha = 0.;
+
+    let pacing = if VSYNC {
+        FramePacing::from_refresh_rate(
+            window
+                .current_monitor()
+                .and_then(|monitor| monitor.refresh_rate_millihertz()),
+            FramePacing::Capped(FRAME_CAP),
+        )
+    } else {
+        FramePacing::Capped(FRAME_CAP)
+    };
+    let mut game_loop = GameLoop::new(TICK_RATE, pacing);
+
+    let mut player = PlayerController::default();
+
+    let bindings = match Bindings::load(BINDINGS_PATH) {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            eprintln!("Using default key bindings, {BINDINGS_PATH}: {e}");
+            Bindings::default()
         }
+    };
+    let mut input = Input::new(bindings);
+
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = rayguy::gamepad::Gamepads::new(Default::default())
+        .map_err(|e| eprintln!("Gamepad support disabled: {e}"))
+        .ok();
+
+    let texture = test_textures();
+    let mut framebuffer = Framebuffer::new(SURFACE_WIDTH as usize, SURFACE_HEIGHT as usize);
+    let mut scale_mode = ScaleMode::default();
+    let mut minimap = Minimap::new(&texture);
+    #[cfg(feature = "palette")]
+    let mut paletted = rayguy::palette::Paletted::new(&texture, 0);
+    let mut explored = Explored::new(&map);
+    // The level as loaded, saves only hold what changed since
+    let level = map.clone();
+    let mut play_time = 0.;
+    let mut triggers = Triggers::new();
+    // Triggers at the spawn fire right away
+    triggers.enter(
+        &map,
+        camera.pos.x.floor() as i32,
+        camera.pos.y.floor() as i32,
+    );
+    let mut hud = Hud::default();
+    // There's no output device yet, one plugs in by replacing NullBackend
+    let mut audio = Audio::new(Mixer::default(), NullBackend);
+    let footstep = Sound::tone(90., 0.08, 0.3);
+    #[cfg(feature = "scripting")]
+    if let Err(e) = scripts.start(&mut map, camera.pos) {
+        eprintln!("{e}");
     }
-    event_loop.run(move |event, _, control_flow| {
-        // Hard cap at 144 FPS
-        *control_flow = ControlFlow::WaitUntil(
-            Instant::now()
-                .checked_add(Duration::from_micros(1_000_000 / 144))
-                .unwrap(),
-        );
+    let mut view_model = ViewModel::test_weapon();
+    let mut recorder: Option<FrameRecorder> = None;
 
+    event_loop.run(move |event, _, control_flow| {
         match event {
             Event::MainEventsCleared => {
-                let old_time = time;
-                time = get_time() as u64;
-                frame_time = time - old_time;
-                window.request_redraw();
+                if game_loop.frame_due() {
+                    #[cfg(feature = "gamepad")]
+                    if let Some(gamepads) = &mut gamepads {
+                        gamepads.update(&mut input);
+                    }
+
+                    let frame = game_loop.begin_frame();
+                    if frame.ticks > 0 {
+                        // Spread the mouse movement of this frame over all of its ticks
+                        let mouse_turn = input.take_mouse_turn() / frame.ticks as f64;
+                        for _ in 0..frame.ticks {
+                            previous_camera = camera;
+                            player.update(&mut camera, &input, mouse_turn, &map, game_loop.dt());
+                            // Move both states when the position wraps around so the interpolation doesn't sweep
+                            // across the whole map
+                            let wrapped = map.wrap(camera.pos);
+                            previous_camera.pos += wrapped - camera.pos;
+                            camera.pos = wrapped;
+                            triggers.handle(&map, player.events());
+                            for event in triggers.drain() {
+                                let action = map.triggers[event.trigger].action.clone();
+                                if !run_trigger(&mut map, &action) {
+                                    *control_flow = ControlFlow::Exit;
+                                    return;
+                                }
+                                #[cfg(feature = "scripting")]
+                                if let Err(e) = scripts.trigger(&mut map, camera.pos, event.trigger)
+                                {
+                                    eprintln!("{e}");
+                                }
+                            }
+                            #[cfg(feature = "scripting")]
+                            {
+                                if let Err(e) = scripts.tick(&mut map, camera.pos, game_loop.dt()) {
+                                    eprintln!("{e}");
+                                }
+                                for message in scripts.take_messages() {
+                                    hud.message(message, Instant::now());
+                                }
+                            }
+                            for event in player.events() {
+                                if let PlayerEvent::Footstep(pos) = *event {
+                                    let source = Source::At(pos);
+                                    audio.mixer.play(
+                                        &footstep,
+                                        PlayOptions {
+                                            source,
+                                            ..Default::default()
+                                        },
+                                    );
+                                }
+                            }
+                            audio.mixer.update(&map, camera.pos, camera.dir());
+                            audio.update(game_loop.dt());
+                            if input.is_down(Action::Fire) {
+                                view_model.fire();
+                            }
+                            view_model.update(game_loop.dt(), &player);
+                            play_time += game_loop.dt();
+                        }
+                    }
+                    alpha = frame.alpha;
+                    window.request_redraw();
+                }
+
+                *control_flow = match game_loop.next_frame_at() {
+                    Some(deadline) => ControlFlow::WaitUntil(deadline),
+                    None => ControlFlow::Poll,
+                };
             }
 
             Event::RedrawRequested(window_id) if window_id == window.id() => {
+                // Physical pixels, so scaling stays pixel perfect on HiDPI displays
                 let (width, height) = {
                     let size = window.inner_size();
                     (size.width, size.height)
                 };
+                let (Some(nonzero_width), Some(nonzero_height)) =
+                    (NonZeroU32::new(width), NonZeroU32::new(height))
+                else {
+                    // Minimized
+                    return;
+                };
 
-                surface
-                    .resize(
-                        NonZeroU32::new(width).unwrap(),
-                        NonZeroU32::new(height).unwrap(),
-                    )
-                    .unwrap();
-
-                let mut buffer = surface.buffer_mut().unwrap();
-                buffer.fill(0);
-
-                // let mut buf = WrapBuffer::new(
-                //     buffer,
-                //     (width as usize, height as usize),
-                //     (width as usize, height as usize),
-                // );
-
-                let mut buf = WrapBuffer::new(
-                    buffer,
-                    (SURFACE_WIDTH as usize, SURFACE_HEIGHT as usize),
-                    (width as usize, height as usize),
+                surface.resize(nonzero_width, nonzero_height).unwrap();
+
+                let view = previous_camera.lerp(&camera, alpha);
+                // Stretching changes the shape of the pixels, the projection compensates for it
+                let source = (framebuffer.width(), framebuffer.height());
+                let pixel_aspect = scale_mode
+                    .viewport(source, (width as usize, height as usize))
+                    .pixel_aspect(source);
+                #[cfg(all(feature = "palette", feature = "rayon"))]
+                paletted.render_parallel(&mut framebuffer, &view, &map, pixel_aspect);
+                #[cfg(all(feature = "palette", not(feature = "rayon")))]
+                paletted.render(&mut framebuffer, &view, &map, pixel_aspect);
+                #[cfg(all(feature = "rayon", not(feature = "palette")))]
+                rayguy::render::render_parallel(
+                    &mut framebuffer,
+                    &view,
+                    &map,
+                    &texture,
+                    pixel_aspect,
                 );
-                let (width, height) = (SURFACE_WIDTH, SURFACE_HEIGHT);
-
-                // Floor
-                for y in 0..height {
-                    let ray_dir_leftmost = &dir - &plane;
-                    let ray_dir_rightmost = &dir + &plane;
-
-                    let horizon_distance = y - height / 2;
-                    let horizon_distance = horizon_distance as f64;
-
-                    // Camera vertical position
-                    let pos_z = height as f64 * 0.5;
-
-                    // If you were to put a point in front of the camera that is with a
-                    // horizontal distance of 1 and a vertical distance of horizon_distance and extend it
-                    // to hit the floor (make the vertical distance equal to pos_z) you would multiply the vector by
-                    // pos_z / p which would make the horizontal distance 1 * pos_z / p = pos_z / p
-                    let row_distance = pos_z / horizon_distance;
-
-                    let floor_step =
-                        (&ray_dir_rightmost - &ray_dir_leftmost) * (row_distance / width as f64);
-
-                    let mut floor = &pos + &(ray_dir_leftmost * row_distance);
-
-                    for x in 0..width {
-                        let (cell_x, cell_y) = (floor.x as usize, floor.y as usize);
-
-                        let texture_x = (TEXTURE_WIDTH as f64 * (floor.x - cell_x as f64)) as usize
-                            & (TEST_LEVEL_WIDTH - 1);
-                        let texture_y = (TEXTURE_HEIGHT as f64 * (floor.y - cell_y as f64))
-                            as usize
-                            & (TEST_LEVEL_HEIGHT - 1);
-
-                        floor.x += floor_step.x;
-                        floor.y += floor_step.y;
-
-                        let mut floor_texture = if (cell_x + cell_y) % 2 == 0 { 2 } else { 4 };
-                        if cell_x < 24 && cell_y < 24 {
-                            if TEST_LEVEL[cell_x][cell_y] < 0 {
-                                floor_texture = -TEST_LEVEL[cell_x][cell_y] as usize;
-                            }
-                        }
-                        // let ceiling_texture = 4;
-                        let ceiling_texture = 7 - floor_texture;
-
-                        // Floor
-                        let color =
-                            texture[floor_texture][TEXTURE_WIDTH as usize * texture_y + texture_x];
-                        let color = (color >> 1) & 8355711;
-                        buf.set_raw(x as usize, y as usize, color);
-
-                        // Ceiling (symmetrical)
+                #[cfg(not(any(feature = "rayon", feature = "palette")))]
+                rayguy::render::render(&mut framebuffer, &view, &map, &texture, pixel_aspect);
+                view_model.draw(&mut framebuffer);
+                explored.reveal_view(&view, framebuffer.width(), &map);
+                minimap.draw(
+                    &mut framebuffer,
+                    &view,
+                    &map,
+                    &explored,
+                    &minimap::markers(&map),
+                );
+                hud.frame(Instant::now());
+                let status = format!(
+                    "X {:.1} Y {:.1}  SPEED {:.1}",
+                    view.pos.x,
+                    view.pos.y,
+                    player.speed()
+                );
+                hud.draw(&mut framebuffer, &status);
 
-                        let color = texture[ceiling_texture]
-                            [TEXTURE_WIDTH as usize * texture_y + texture_x];
-                        let color = (color >> 1) & 8355711;
-                        buf.set_raw(x as usize, (height - y - 1) as usize, color);
+                if let Some(active) = &mut recorder {
+                    if let Err(e) = active.record(&framebuffer) {
+                        eprintln!("Stopped recording, {}: {e}", active.dir().display());
+                        recorder = None;
                     }
                 }
 
-                // Walls
-                for x in 0..width {
-                    // https://lodev.org/cgtutor/raycasting.html:
-                    // " cameraX is the x-coordinate on the camera plane that the current x-coordinate of the screen represents,
-                    // done this way so that the right side of the screen will get coordinate 1, the center of the screen gets
-                    // coordinate 0, and the left side of the screen gets coordinate -1. "
-                    // So if x is at the left side of the screen camera_x becomes -1 and causes makes ray_dir point to the left.
-                    // If x is -0.5, ray_dir will point to the left (left of the direction vector) but only
-                    // as much as half of the plane vector.
-                    let camera_x: f64 = 2. * x as f64 / width as f64 - 1.;
-                    let ray_dir = &dir + &(&plane * camera_x);
-                    let mut map_pos = Vec2::new(pos.x as i32 as f64, pos.y as i32 as f64);
-
-                    let mut side_dist = Vec2::new(0., 0.);
-
-                    // When you try to derive this formula, you will get |ray_dir| / ray_dir.x and |ray_dir| / ray_dir.y
-                    // (Where |ray_dir| is the length of the ray_dir vector) When you simplify the entire equation
-                    // (including some calculations after this one), you will see that |ray_dir| can be discarded
-                    // (because we only really need the ratio between ray_dir.x and .y).
-                    let delta_dist = Vec2::new((1. / ray_dir.x).abs(), (1. / ray_dir.y).abs());
-
-                    // let perp_wall_dist: f64; // will be used to calculate |ray_dir|
-
-                    let mut step = Vec2::new(0., 0.); // should be either +1 or -1. //TODO make this less ambigious
-
-                    let mut hit = 0;
-                    let mut side = 0;
-
-                    if ray_dir.x < 0. {
-                        step.x = -1.;
-                        side_dist.x = (pos.x - map_pos.x) * delta_dist.x;
-                    } else {
-                        step.x = 1.;
-                        // map_pos.x < pos.x < map_pos.x + 1 and the expression below needs to be positive
-                        // and also map_pos.x + 1 - pos.x is the actaul geometrically correct length to use here
-                        // since map_pos is the left-bottom corner of a square.
+                let mut buffer = surface.buffer_mut().unwrap();
+                scale::present(
+                    &framebuffer,
+                    scale_mode,
+                    &mut buffer,
+                    width as usize,
+                    height as usize,
+                );
+                buffer.present().unwrap();
+            }
 
-                        // delta_dist.x is the amount of distance the ray needs to go to reach the closest vertical line
-                        // that is 1 unit away from the starting position on the x axis.
-                        side_dist.x = (map_pos.x + 1. - pos.x) * delta_dist.x;
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. },
+            } if window_id == window.id() => {
+                window.request_redraw();
+            }
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode:
+                                    Some(
+                                        key @ (VirtualKeyCode::F1
+                                        | VirtualKeyCode::F2
+                                        | VirtualKeyCode::F3
+                                        | VirtualKeyCode::F4
+                                        | VirtualKeyCode::F5
+                                        | VirtualKeyCode::F9
+                                        | VirtualKeyCode::F11
+                                        | VirtualKeyCode::F12
+                                        | VirtualKeyCode::Tab
+                                        | VirtualKeyCode::PageUp
+                                        | VirtualKeyCode::PageDown),
+                                    ),
+                                state: ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    },
+            } if window_id == window.id() => {
+                match key {
+                    VirtualKeyCode::F1 => hud.visible = !hud.visible,
+                    VirtualKeyCode::F2 => {
+                        scale_mode = scale_mode.next();
+                        println!("Scale mode: {scale_mode:?}");
                     }
-
-                    if ray_dir.y < 0. {
-                        step.y = -1.;
-                        side_dist.y = (pos.y - map_pos.y) * delta_dist.y;
-                    } else {
-                        step.y = 1.;
-                        // delta_dist.y is the amount of distance the ray needs to go to reach the closest horizontal line
-                        // that is 1 unit away from the starting position on the y axis.
-                        side_dist.y = (map_pos.y + 1. - pos.y) * delta_dist.y;
+                    VirtualKeyCode::F3 => {
+                        // Cycle through the internal resolutions
+                        let current = (framebuffer.width() as u32, framebuffer.height() as u32);
+                        let next = RESOLUTIONS
+                            .iter()
+                            .position(|r| *r == current)
+                            .map_or(0, |i| (i + 1) % RESOLUTIONS.len());
+                        let (width, height) = RESOLUTIONS[next];
+                        framebuffer = Framebuffer::new(width as usize, height as usize);
+                        println!("Internal resolution: {width}x{height}");
                     }
-
-                    // Perform DDA (Digital Differential Analysis)
-                    while hit == 0 {
-                        // Step towards the closest side
-                        if side_dist.x < side_dist.y {
-                            side_dist.x += delta_dist.x;
-                            map_pos.x += step.x;
-                            side = 0;
-                        } else {
-                            side_dist.y += delta_dist.y;
-                            map_pos.y += step.y;
-                            side = 1;
-                        }
-
-                        // Check if it was a hit
-                        if TEST_LEVEL[map_pos.x as usize][map_pos.y as usize] > 0 {
-                            hit = 1;
+                    VirtualKeyCode::F4 => minimap.follow_rotation = !minimap.follow_rotation,
+                    VirtualKeyCode::F5 => {
+                        let timers = BTreeMap::from([("play_time".to_string(), play_time)]);
+                        #[cfg(feature = "scripting")]
+                        let vars = scripts.vars();
+                        #[cfg(not(feature = "scripting"))]
+                        let vars = Ok::<_, String>(BTreeMap::new());
+                        let save = vars.and_then(|vars| {
+                            SaveGame::capture(&level, &map, &camera, &triggers, timers, vars)
+                        });
+                        match save {
+                            Ok(save) => match save.save(SAVE_PATH) {
+                                Ok(()) => hud.message("Game saved", Instant::now()),
+                                Err(e) => eprintln!("Can't save {SAVE_PATH}: {e}"),
+                            },
+                            Err(e) => eprintln!("Can't save, {e}"),
                         }
                     }
-
-                    // To avoid the fisheye effect, we calculate the distance between the point and the camera _plane_.
-                    // (Hence the name, perp(enducilar)_wall_distance)
-                    // You can calculate the actual euclidean distance between the camera and the hit point but that would be more
-                    // work and would result in the aforementioned fisheye effect.
-
-                    // The equation below can be derived from a bunch of similar triangles and ratios between the hit point and the
-                    // camera plane. If you do not want to sit down and derive the same equation, you can imagine the
-                    // -delta_dist part as just going a step back to get out of the wall after the last DDA step.
-                    let perp_wall_dist: f64 = if side == 0 {
-                        side_dist.x - delta_dist.x
-                    } else {
-                        side_dist.y - delta_dist.y
-                    };
-
-                    let line_height = (width as f64 / perp_wall_dist) as i32;
-                    let draw_start = {
-                        let mut y = -line_height / 2 + height as i32 / 2;
-                        if y < 0 {
-                            y = 0;
-                        }
-                        y as usize
-                    };
-                    let draw_end = {
-                        let mut y = line_height / 2 + height as i32 / 2;
-                        if y >= height as i32 {
-                            y = height as i32 - 1;
-                        }
-                        y as usize
-                    };
-
-                    // - 1 so that texture 0 can be used
-                    let tex_num = TEST_LEVEL[map_pos.x as usize][map_pos.y as usize] - 1;
-
-                    let wall_x = {
-                        // in my version x and y were flipped, which is probably one of the reasons why it didnt work
-                        if side == 0 {
-                            pos.y + perp_wall_dist * ray_dir.y
-                        } else {
-                            pos.x + perp_wall_dist * ray_dir.x
-                        }
-                    };
-                    let wall_x = wall_x - wall_x.floor(); // basically what i did previously
-
-                    let texture_x = (wall_x * (TEXTURE_WIDTH as f64)) as u32;
-                    let texture_x = {
-                        if side == 0 && ray_dir.x > 0. || side == 1 && ray_dir.y < 0. {
-                            TEXTURE_WIDTH - texture_x - 1
-                        } else {
-                            texture_x
+                    VirtualKeyCode::F9 => {
+                        match SaveGame::load(SAVE_PATH).and_then(|save| {
+                            let (map, triggers) = save.restore(&level)?;
+                            Ok((save, map, triggers))
+                        }) {
+                            Ok((save, restored, restored_triggers)) => {
+                                map = restored;
+                                triggers = restored_triggers;
+                                camera = save.camera;
+                                previous_camera = camera;
+                                play_time = save.timers.get("play_time").copied().unwrap_or(0.);
+                                // Nothing from before the load carries over
+                                player = PlayerController::new(player.settings);
+                                explored = Explored::new(&map);
+                                audio.mixer.stop_all();
+                                #[cfg(feature = "scripting")]
+                                scripts.set_vars(&save.vars);
+                                hud.message("Game loaded", Instant::now());
+                            }
+                            Err(e) => eprintln!("Can't load {SAVE_PATH}: {e}"),
                         }
-                    };
-
-                    let step = 1. * TEXTURE_HEIGHT as f64 / line_height as f64;
-                    let mut tex_pos =
-                        (draw_start as f64 - height as f64 / 2. + line_height as f64 / 2.) * step;
-
-                    for y in draw_start..draw_end+1 {
-                        let texture_y = tex_pos as usize & (TEXTURE_HEIGHT as usize - 1);
-                        tex_pos += step;
-                        let mut color = texture[tex_num as usize]
-                            [TEXTURE_HEIGHT as usize * texture_y + texture_x as usize];
-                        if side == 1 {
-                            // 8355711 is the decimal value of 0b00000000011111110111111101111111 which is the mask we use the divide all 3 values by 2
-                            color = (color >> 1) & 8355711;
-                        };
-                        buf.set_raw(x as usize, y, color);
                     }
-
-                    // let mut color = match TEST_LEVEL[map_pos.x as usize][map_pos.y as usize] {
-                    //     1 => RED,
-                    //     2 => GREEN,
-                    //     3 => BLUE,
-                    //     4 => WHITE,
-                    //     _ => YELLOW,
-                    // };
-
-                    // if side == 1 {
-                    //     match color {
-                    //         Color::Rgb(r, g, b) => color = Color::Rgb(r / 2, g / 2, b / 2),
-                    //     }
-                    // }
-
-                    // buf.vert_line(x as usize, draw_start as usize, draw_end as usize, color);
-                }
-
-                buf.present().unwrap();
-
-                let move_speed = frame_time as f64 * 2. / 1000.;
-                let move_speed = {
-                    if pressed_keys[VirtualKeyCode::Space as usize] {
-                        move_speed * 2.5
-                    } else {
-                        move_speed
-                    }
-                };
-                let rot_speed = frame_time as f64 * 0.85 / 1000. * std::f64::consts::PI;
-
-                if pressed_keys[VirtualKeyCode::Up as usize] {
-                    if TEST_LEVEL[(pos.x + dir.x * move_speed) as usize][pos.y as usize] <= 0 {
-                        pos.x += dir.x * move_speed;
-                    }
-                    if TEST_LEVEL[pos.x as usize][(pos.y + dir.y * move_speed) as usize] <= 0 {
-                        pos.y += dir.y * move_speed;
-                    }
-                }
-                if pressed_keys[VirtualKeyCode::Down as usize] {
-                    if TEST_LEVEL[(pos.x - dir.x * move_speed) as usize][pos.y as usize] <= 0 {
-                        pos.x -= dir.x * move_speed;
-                    }
-                    if TEST_LEVEL[pos.x as usize][(pos.y - dir.y * move_speed) as usize] <= 0 {
-                        pos.y -= dir.y * move_speed;
+                    VirtualKeyCode::F11 => match recorder.take() {
+                        Some(stopped) => println!(
+                            "Recorded {} frames to {}",
+                            stopped.frames(),
+                            stopped.dir().display()
+                        ),
+                        None => match FrameRecorder::new(RECORDING_DIR, ImageFormat::Png) {
+                            Ok(started) => {
+                                println!("Recording to {RECORDING_DIR}");
+                                recorder = Some(started);
+                            }
+                            Err(e) => eprintln!("Can't record to {RECORDING_DIR}: {e}"),
+                        },
+                    },
+                    VirtualKeyCode::F12 => {
+                        // The last presented frame, at the internal resolution
+                        let path =
+                            capture::next_free_path(SCREENSHOT_DIR, "screenshot", ImageFormat::Png);
+                        match framebuffer.save_png(&path) {
+                            Ok(()) => println!("Saved {}", path.display()),
+                            Err(e) => eprintln!("Can't save {}: {e}", path.display()),
+                        }
                     }
+                    VirtualKeyCode::Tab => minimap.view = minimap.view.next(),
+                    VirtualKeyCode::PageUp => minimap.zoom_in(),
+                    _ => minimap.zoom_out(),
                 }
-                if pressed_keys[VirtualKeyCode::Right as usize] {
-                    dir.rotate(-rot_speed);
-                    plane.rotate(-rot_speed);
-                }
-                if pressed_keys[VirtualKeyCode::Left as usize] {
-                    dir.rotate(rot_speed);
-                    plane.rotate(rot_speed);
-                }
+                window.request_redraw();
             }
 
             Event::WindowEvent {
@@ -378,16 +451,38 @@ fn main() {
                     WindowEvent::KeyboardInput {
                         input:
                             KeyboardInput {
-                                virtual_keycode: Some(keycode),
-                                state,
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                state: ElementState::Pressed,
                                 ..
                             },
                         ..
                     },
-            } if window_id == window.id() => match state {
-                ElementState::Pressed => pressed_keys[keycode as usize] = true,
-                ElementState::Released => pressed_keys[keycode as usize] = false,
-            },
+            } if window_id == window.id() && input.mouse_look() => {
+                set_mouse_look(&window, &mut input, false);
+            }
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::MouseInput {
+                        state: ElementState::Pressed,
+                        button: MouseButton::Left,
+                        ..
+                    },
+            } if window_id == window.id() && !input.mouse_look() => {
+                // Clicking into the window grabs the cursor, escape releases it
+                set_mouse_look(&window, &mut input, true);
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Focused(false),
+            } if window_id == window.id() => {
+                set_mouse_look(&window, &mut input, false);
+                input.handle_window_event(&WindowEvent::Focused(false));
+            }
+            Event::WindowEvent { window_id, event } if window_id == window.id() => {
+                input.handle_window_event(&event);
+            }
+            Event::DeviceEvent { event, .. } => input.handle_device_event(&event),
 
             _ => (),
         }
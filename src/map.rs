@@ -0,0 +1,287 @@
+use std::f64::consts::PI;
+
+use crate::{
+    consts::{TEST_LEVEL, TEST_LEVEL_HEIGHT, TEST_LEVEL_WIDTH},
+    math::Vec2,
+    trigger::Trigger,
+};
+
+/// What rays and movement find outside of a map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutOfBounds {
+    /// Walls with texture 0, as if the map had a closed border.
+    #[default]
+    Wall,
+    /// Nothing, rays pass through until they reach their maximum distance and only the floor and ceiling are
+    /// drawn there. Movement still stops at the edge of the map.
+    Void,
+    /// The map repeats in every direction.
+    Wrap,
+}
+
+/// Where and in which direction the player starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spawn {
+    pub pos: Vec2,
+    /// Radians counter-clockwise from +x, like [`Camera::yaw`](crate::camera::Camera::yaw).
+    pub yaw: f64,
+}
+
+/// A wall that can be opened. While it's closed its cell holds `cell` like any other wall, open it's empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Door {
+    pub x: usize,
+    pub y: usize,
+    /// Wall cell value of the closed door (texture id + 1).
+    pub cell: i8,
+    pub open: bool,
+}
+
+/// Something placed on the map, what `kind` means is up to the game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub kind: String,
+    pub pos: Vec2,
+    pub radius: f64,
+}
+
+/// Tile map, stored the same way as `TEST_LEVEL`:
+/// positive values are walls (texture id + 1), negative values are floors with a custom texture
+/// (-texture id) and 0 is an empty cell with the default floor.
+///
+/// Doors are kept in sync with their cells by [`Map::add_door`] and [`Map::set_door_open`], spawn, entities,
+/// triggers and scripts are only data for the game to use.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    width: usize,
+    height: usize,
+    cells: Vec<i8>,
+    out_of_bounds: OutOfBounds,
+    doors: Vec<Door>,
+    pub spawn: Spawn,
+    pub entities: Vec<Entity>,
+    pub triggers: Vec<Trigger>,
+    /// Paths of level scripts relative to the level file, see `script` (needs the `scripting` feature).
+    pub scripts: Vec<String>,
+}
+
+impl Map {
+    /// Empty map without any walls, the player spawns in its center looking towards +x.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; width * height],
+            out_of_bounds: OutOfBounds::default(),
+            doors: Vec::new(),
+            spawn: Spawn {
+                pos: Vec2::new(width as f64 / 2., height as f64 / 2.),
+                yaw: 0.,
+            },
+            entities: Vec::new(),
+            triggers: Vec::new(),
+            scripts: Vec::new(),
+        }
+    }
+
+    /// Builds a map from a grid indexed as `grid[x][y]`, like `TEST_LEVEL`.
+    pub fn from_grid<const W: usize, const H: usize>(grid: &[[i8; H]; W]) -> Self {
+        let mut map = Self::new(W, H);
+        for (x, column) in grid.iter().enumerate() {
+            for (y, cell) in column.iter().enumerate() {
+                map.set(x, y, *cell);
+            }
+        }
+        map
+    }
+
+    pub fn test_level() -> Self {
+        let mut map = Self::from_grid::<TEST_LEVEL_WIDTH, TEST_LEVEL_HEIGHT>(&TEST_LEVEL);
+        map.spawn = Spawn {
+            pos: Vec2::new(22., 11.5),
+            yaw: PI,
+        };
+        map
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn out_of_bounds(&self) -> OutOfBounds {
+        self.out_of_bounds
+    }
+
+    pub fn set_out_of_bounds(&mut self, out_of_bounds: OutOfBounds) {
+        self.out_of_bounds = out_of_bounds;
+    }
+
+    /// Cell at `(x, y)`, `None` if it's outside of the map.
+    /// See [`Map::cell`] for what the renderer sees there.
+    pub fn get(&self, x: i32, y: i32) -> Option<i8> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(self.cells[y as usize * self.width + x as usize])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: i8) {
+        assert!(
+            x < self.width && y < self.height,
+            "({x}, {y}) is outside of the map"
+        );
+        self.cells[y * self.width + x] = cell;
+    }
+
+    pub fn doors(&self) -> &[Door] {
+        &self.doors
+    }
+
+    /// Index of the door at `(x, y)`.
+    pub fn door_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.doors
+            .iter()
+            .position(|door| (door.x as i32, door.y as i32) == (x, y))
+    }
+
+    /// Turns the cell at `(x, y)` into a closed door showing the wall `cell`, returns its index.
+    pub fn add_door(&mut self, x: usize, y: usize, cell: i8) -> usize {
+        assert!(cell > 0, "doors need a wall cell, got {cell}");
+        self.set(x, y, cell);
+        self.doors.push(Door {
+            x,
+            y,
+            cell,
+            open: false,
+        });
+        self.doors.len() - 1
+    }
+
+    /// Removes the door at `index` and leaves its cell in its current state.
+    pub fn remove_door(&mut self, index: usize) -> Door {
+        self.doors.remove(index)
+    }
+
+    pub fn set_door_open(&mut self, index: usize, open: bool) {
+        let door = &mut self.doors[index];
+        door.open = open;
+        let (x, y, cell) = (door.x, door.y, if open { 0 } else { door.cell });
+        self.set(x, y, cell);
+    }
+
+    /// Changes the wall a door shows while it's closed.
+    pub fn set_door_cell(&mut self, index: usize, cell: i8) {
+        assert!(cell > 0, "doors need a wall cell, got {cell}");
+        self.doors[index].cell = cell;
+        let door = self.doors[index];
+        self.set_door_open(index, door.open);
+    }
+
+    /// Cell at `(x, y)` with the out of bounds policy applied: a wall with texture 0 ([`OutOfBounds::Wall`]),
+    /// an empty cell ([`OutOfBounds::Void`]) or the cell it wraps around to ([`OutOfBounds::Wrap`]).
+    pub fn cell(&self, x: i32, y: i32) -> i8 {
+        if let Some(cell) = self.get(x, y) {
+            return cell;
+        }
+        match self.out_of_bounds {
+            OutOfBounds::Wall => 1,
+            OutOfBounds::Void => 0,
+            // A map without cells has nothing to wrap around to
+            OutOfBounds::Wrap if self.cells.is_empty() => 0,
+            OutOfBounds::Wrap => {
+                self.cells[y.rem_euclid(self.height as i32) as usize * self.width
+                    + x.rem_euclid(self.width as i32) as usize]
+            }
+        }
+    }
+
+    /// Whether the cell blocks movement. Everything outside of the map is solid unless it wraps around.
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        match self.out_of_bounds {
+            OutOfBounds::Wrap => self.cell(x, y) > 0,
+            OutOfBounds::Wall | OutOfBounds::Void => self.get(x, y).is_none_or(|cell| cell > 0),
+        }
+    }
+
+    /// Whether a ray stops at the cell, which is the case for walls but not for [`OutOfBounds::Void`].
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.cell(x, y) > 0
+    }
+
+    /// The same place inside the map for a position that left it through a wrapping edge, `pos` itself
+    /// otherwise.
+    pub fn wrap(&self, pos: Vec2) -> Vec2 {
+        if self.out_of_bounds != OutOfBounds::Wrap || self.cells.is_empty() {
+            return pos;
+        }
+        Vec2::new(
+            pos.x.rem_euclid(self.width as f64),
+            pos.y.rem_euclid(self.height as f64),
+        )
+    }
+
+    /// Whether the cell containing the world position `(x, y)` is solid.
+    pub fn is_solid_at(&self, x: f64, y: f64) -> bool {
+        self.is_solid(x.floor() as i32, y.floor() as i32)
+    }
+
+    /// Whether a square of half extent `radius` centered on `(x, y)` overlaps a solid cell.
+    pub fn collides(&self, x: f64, y: f64, radius: f64) -> bool {
+        self.overlaps(x, y, radius, |x, y| self.is_solid(x, y))
+    }
+
+    /// [`Map::collides`] with a different idea of which cells are solid.
+    pub(crate) fn overlaps(
+        &self,
+        x: f64,
+        y: f64,
+        radius: f64,
+        solid: impl Fn(i32, i32) -> bool,
+    ) -> bool {
+        [
+            (-radius, -radius),
+            (radius, -radius),
+            (-radius, radius),
+            (radius, radius),
+        ]
+        .iter()
+        .any(|(dx, dy)| solid((x + dx).floor() as i32, (y + dy).floor() as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_bounds_policies() {
+        let mut map = Map::new(3, 2);
+        map.set(0, 0, 5);
+        map.set(2, 1, -3);
+
+        assert_eq!((map.cell(-1, 0), map.cell(0, 7)), (1, 1));
+        assert!(map.is_solid(3, 0) && map.is_wall(3, 0));
+
+        map.set_out_of_bounds(OutOfBounds::Void);
+        assert_eq!((map.cell(-1, 0), map.cell(0, 7)), (0, 0));
+        assert!(map.is_solid(3, 0) && !map.is_wall(3, 0));
+
+        map.set_out_of_bounds(OutOfBounds::Wrap);
+        assert_eq!(
+            (map.cell(3, 2), map.cell(-1, -1), map.cell(-4, 3)),
+            (5, -3, -3)
+        );
+        assert!(map.is_solid(-3, 4) && !map.is_solid(3, 1));
+        assert_eq!(map.wrap(Vec2::new(-0.5, 4.25)), Vec2::new(2.5, 0.25));
+
+        // Nothing to wrap around to
+        let mut empty = Map::new(0, 0);
+        empty.set_out_of_bounds(OutOfBounds::Wrap);
+        assert_eq!(empty.cell(1, 1), 0);
+        assert_eq!(empty.wrap(Vec2::new(1., 1.)), Vec2::new(1., 1.));
+    }
+}
@@ -1,19 +1,79 @@
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Vec2 {
     pub x: f64,
     pub y: f64,
 }
 
 impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0., y: 0. };
+
     pub fn new(x: f64, y: f64) -> Self {
         Self { x, y }
     }
+
+    /// Unit vector pointing in the direction `radian`, measured counter-clockwise from +x.
+    pub fn from_angle(radian: f64) -> Self {
+        let (sin, cos) = radian.sin_cos();
+        Self::new(cos, sin)
+    }
+
+    /// Rotates in place. Rotating many times in small steps accumulates rounding errors, prefer deriving
+    /// vectors from an angle with [`Vec2::from_angle`] when that's possible.
     pub fn rotate(&mut self, radian: f64) {
-        let old_x = self.x;
-        self.x = self.x * radian.cos() - self.y * radian.sin();
-        self.y = old_x * radian.sin() + self.y * radian.cos();
+        *self = self.rotated(radian);
+    }
+
+    pub fn rotated(self, radian: f64) -> Self {
+        let (sin, cos) = radian.sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+
+    pub fn dot(self, rhs: Self) -> f64 {
+        self.x * rhs.x + self.y * rhs.y
+    }
+
+    /// z component of the 3D cross product, positive if `rhs` is counter-clockwise from `self`.
+    pub fn cross(self, rhs: Self) -> f64 {
+        self.x * rhs.y - self.y * rhs.x
+    }
+
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+
+    pub fn length_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    pub fn distance(self, rhs: Self) -> f64 {
+        (rhs - self).length()
+    }
+
+    /// Same direction with a length of 1, the zero vector stays zero.
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        if length == 0. {
+            self
+        } else {
+            self / length
+        }
+    }
+
+    /// Linear interpolation, `self` at `t = 0` and `rhs` at `t = 1`.
+    pub fn lerp(self, rhs: Self, t: f64) -> Self {
+        self + (rhs - self) * t
+    }
+
+    /// Direction in radians counter-clockwise from +x, in `-PI..=PI`.
+    pub fn angle(self) -> f64 {
+        self.y.atan2(self.x)
+    }
+
+    /// Rotated by 90 degrees counter-clockwise.
+    pub fn perpendicular(self) -> Self {
+        Self::new(-self.y, self.x)
     }
 }
 
@@ -33,6 +93,14 @@ impl Add for &Vec2 {
     }
 }
 
+impl Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
 impl Sub for &Vec2 {
     type Output = Vec2;
 
@@ -56,3 +124,200 @@ impl Mul<f64> for &Vec2 {
         Self::Output::new(self.x * rhs, self.y * rhs)
     }
 }
+
+impl Div<f64> for Vec2 {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        Self::new(self.x / rhs, self.y / rhs)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign<f64> for Vec2 {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign<f64> for Vec2 {
+    fn div_assign(&mut self, rhs: f64) {
+        *self = *self / rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn vec2() -> impl Strategy<Value = Vec2> {
+        (-1e3..1e3, -1e3..1e3).prop_map(|(x, y)| Vec2::new(x, y))
+    }
+
+    fn angle() -> impl Strategy<Value = f64> {
+        -10. * PI..10. * PI
+    }
+
+    // Relative to the size of the values involved, absolute errors grow with them
+    fn close(a: f64, b: f64, scale: f64) -> bool {
+        (a - b).abs() <= EPSILON * scale.max(1.)
+    }
+
+    fn close_vec(a: Vec2, b: Vec2, scale: f64) -> bool {
+        close(a.x, b.x, scale) && close(a.y, b.y, scale)
+    }
+
+    #[test]
+    fn basics() {
+        let v = Vec2::new(3., 4.);
+        assert_eq!(v.length(), 5.);
+        assert_eq!(v.length_squared(), 25.);
+        assert_eq!(v.dot(Vec2::new(1., 2.)), 11.);
+        assert_eq!(v.cross(Vec2::new(1., 2.)), 2.);
+        assert_eq!(v.perpendicular(), Vec2::new(-4., 3.));
+        assert_eq!(-v, Vec2::new(-3., -4.));
+        assert_eq!(v / 2., Vec2::new(1.5, 2.));
+        assert_eq!(v.normalize(), Vec2::new(0.6, 0.8));
+        assert_eq!(Vec2::ZERO.normalize(), Vec2::ZERO);
+        assert_eq!(v.distance(Vec2::ZERO), 5.);
+        assert_eq!(Vec2::new(0., 2.).angle(), FRAC_PI_2);
+        assert!(close_vec(
+            Vec2::from_angle(FRAC_PI_2),
+            Vec2::new(0., 1.),
+            1.
+        ));
+        assert!(close_vec(
+            Vec2::new(1., 0.).rotated(FRAC_PI_2),
+            Vec2::new(0., 1.),
+            1.
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn rotation_preserves_length(v in vec2(), a in angle()) {
+            let length = v.length();
+            prop_assert!(close(v.rotated(a).length(), length, length));
+        }
+
+        #[test]
+        fn rotation_preserves_dot_and_cross(v in vec2(), w in vec2(), a in angle()) {
+            let scale = v.length() * w.length();
+            prop_assert!(close(v.rotated(a).dot(w.rotated(a)), v.dot(w), scale));
+            prop_assert!(close(v.rotated(a).cross(w.rotated(a)), v.cross(w), scale));
+        }
+
+        #[test]
+        fn rotation_is_reversible(v in vec2(), a in angle()) {
+            prop_assert!(close_vec(v.rotated(a).rotated(-a), v, v.length()));
+        }
+
+        #[test]
+        fn rotations_compose(v in vec2(), a in angle(), b in angle()) {
+            prop_assert!(close_vec(v.rotated(a).rotated(b), v.rotated(a + b), v.length()));
+        }
+
+        #[test]
+        fn rotate_matches_rotated(v in vec2(), a in angle()) {
+            let mut rotated = v;
+            rotated.rotate(a);
+            prop_assert_eq!(rotated, v.rotated(a));
+        }
+
+        #[test]
+        fn from_angle_matches_angle(a in -PI..PI) {
+            let v = Vec2::from_angle(a);
+            prop_assert!(close(v.length(), 1., 1.));
+            prop_assert!(close(v.angle(), a, 1.));
+            prop_assert!(close_vec(Vec2::new(1., 0.).rotated(a), v, 1.));
+        }
+
+        #[test]
+        fn perpendicular_is_quarter_turn(v in vec2()) {
+            let p = v.perpendicular();
+            prop_assert_eq!(v.dot(p), 0.);
+            prop_assert!(close(p.length(), v.length(), v.length()));
+            prop_assert!(close(v.cross(p), v.length_squared(), v.length_squared()));
+            prop_assert!(close_vec(p, v.rotated(FRAC_PI_2), v.length()));
+        }
+
+        #[test]
+        fn products_are_symmetric(v in vec2(), w in vec2()) {
+            prop_assert_eq!(v.dot(w), w.dot(v));
+            prop_assert_eq!(v.cross(w), -w.cross(v));
+            prop_assert_eq!(v.cross(v), 0.);
+        }
+
+        #[test]
+        fn normalize_has_unit_length(v in vec2()) {
+            prop_assume!(v.length() > 1e-6);
+            let n = v.normalize();
+            prop_assert!(close(n.length(), 1., 1.));
+            prop_assert!(close(n.cross(v), 0., v.length()));
+            prop_assert!(n.dot(v) > 0.);
+        }
+
+        #[test]
+        fn lerp_hits_both_ends(v in vec2(), w in vec2(), t in 0f64..1.) {
+            let scale = v.length() + w.length();
+            prop_assert_eq!(v.lerp(w, 0.), v);
+            prop_assert!(close_vec(v.lerp(w, 1.), w, scale));
+            let between = v.lerp(w, t);
+            prop_assert!(close(v.distance(between) + between.distance(w), v.distance(w), scale));
+        }
+
+        // The operators on references are what's being compared here
+        #[allow(clippy::op_ref)]
+        #[test]
+        fn operators_are_consistent(v in vec2(), w in vec2(), s in 0.01f64..100.) {
+            prop_assert_eq!(v - w, v + -w);
+            prop_assert_eq!(&v + &w, v + w);
+            prop_assert_eq!(&v - &w, v - w);
+            prop_assert_eq!(&v * s, v * s);
+            prop_assert!(close_vec((v + w) - w, v, v.length() + w.length()));
+            prop_assert!(close_vec((v * s) / s, v, v.length()));
+            prop_assert_eq!(-(-v), v);
+        }
+
+        #[test]
+        fn compound_assignment_matches_operators(v in vec2(), w in vec2(), s in 0.01f64..100.) {
+            let mut u = v;
+            u += w;
+            prop_assert_eq!(u, v + w);
+            let mut u = v;
+            u -= w;
+            prop_assert_eq!(u, v - w);
+            let mut u = v;
+            u *= s;
+            prop_assert_eq!(u, v * s);
+            let mut u = v;
+            u /= s;
+            prop_assert_eq!(u, v / s);
+        }
+    }
+}
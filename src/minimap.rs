@@ -0,0 +1,470 @@
+//! Top-down view of the map, drawn over the rendered frame.
+//!
+//! Which cells are shown is decided by [`Explored`], which remembers every cell the rays of the renderer
+//! passed through. Unexplored cells stay hidden until the player has actually seen them.
+
+use crate::{
+    camera::Camera,
+    consts::MAX_RAY_DISTANCE,
+    framebuffer::{line_points, Framebuffer},
+    map::Map,
+    math::Vec2,
+    ray::Ray,
+};
+
+const BACKGROUND_COLOR: u32 = 0x101010;
+const FLOOR_COLOR: u32 = 0x404040;
+const BORDER_COLOR: u32 = 0x808080;
+const PLAYER_COLOR: u32 = 0xffffff;
+const FRUSTUM_COLOR: u32 = 0xffff00;
+const ENTITY_COLOR: u32 = 0xff4040;
+const DOOR_COLOR: u32 = 0x40a0ff;
+
+const MIN_ZOOM: f64 = 1.;
+const MAX_ZOOM: f64 = 32.;
+// How far the frustum lines reach, in cells
+const FRUSTUM_LENGTH: f64 = 3.;
+
+/// Cells of a map the player has seen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explored {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+impl Explored {
+    /// Nothing of `map` explored yet.
+    pub fn new(map: &Map) -> Self {
+        Self {
+            width: map.width(),
+            height: map.height(),
+            cells: vec![false; map.width() * map.height()],
+        }
+    }
+
+    pub fn is_explored(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.cells[i])
+    }
+
+    /// Marks a cell as explored, cells outside of the map are ignored.
+    pub fn reveal(&mut self, x: i32, y: i32) {
+        if let Some(i) = self.index(x, y) {
+            self.cells[i] = true;
+        }
+    }
+
+    pub fn reveal_all(&mut self) {
+        self.cells.fill(true);
+    }
+
+    /// Marks every cell the rays of a `columns` wide view from `camera` pass through, up to and including the
+    /// walls they hit. These are the same rays the renderer casts.
+    pub fn reveal_view(&mut self, camera: &Camera, columns: usize, map: &Map) {
+        self.reveal(camera.pos.x.floor() as i32, camera.pos.y.floor() as i32);
+        for x in 0..columns {
+            let camera_x = 2. * x as f64 / columns as f64 - 1.;
+            let ray_dir = camera.dir() + camera.plane() * camera_x;
+            for step in
+                Ray::new(camera.pos, ray_dir).take_while(|step| step.distance <= MAX_RAY_DISTANCE)
+            {
+                self.reveal(step.x, step.y);
+                if map.is_wall(step.x, step.y) {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+        Some(y as usize * self.width + x as usize)
+    }
+}
+
+/// Where the minimap is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MinimapView {
+    Hidden,
+    /// Small overlay in the top right corner of the frame.
+    #[default]
+    Corner,
+    /// Covers the whole frame.
+    Automap,
+}
+
+impl MinimapView {
+    pub fn next(self) -> Self {
+        match self {
+            MinimapView::Hidden => MinimapView::Corner,
+            MinimapView::Corner => MinimapView::Automap,
+            MinimapView::Automap => MinimapView::Hidden,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Entity,
+    Door,
+}
+
+/// Something on the map that isn't a wall, shown once the cell it's in has been explored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    pub pos: Vec2,
+    pub kind: MarkerKind,
+}
+
+/// Markers for the doors and entities of `map`.
+pub fn markers(map: &Map) -> Vec<Marker> {
+    let doors = map.doors().iter().map(|door| Marker {
+        pos: Vec2::new(door.x as f64 + 0.5, door.y as f64 + 0.5),
+        kind: MarkerKind::Door,
+    });
+    let entities = map.entities.iter().map(|entity| Marker {
+        pos: entity.pos,
+        kind: MarkerKind::Entity,
+    });
+    doors.chain(entities).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct Minimap {
+    pub view: MinimapView,
+    /// Size of a cell in pixels.
+    pub zoom: f64,
+    /// Rotate the map with the camera so that it always looks up, otherwise north is up.
+    pub follow_rotation: bool,
+    /// Only show explored cells.
+    pub fog_of_war: bool,
+    // Average color of every texture, walls are drawn with the color of their texture
+    wall_colors: Vec<u32>,
+}
+
+impl Minimap {
+    pub fn new(textures: &[Vec<u32>]) -> Self {
+        Self {
+            view: MinimapView::default(),
+            zoom: 4.,
+            follow_rotation: false,
+            fog_of_war: true,
+            wall_colors: textures.iter().map(|t| average_color(t)).collect(),
+        }
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = (self.zoom * 1.25).min(MAX_ZOOM);
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = (self.zoom / 1.25).max(MIN_ZOOM);
+    }
+
+    /// Draws the map around `camera` over `framebuffer`, along with the camera frustum and `markers`.
+    pub fn draw(
+        &self,
+        framebuffer: &mut Framebuffer,
+        camera: &Camera,
+        map: &Map,
+        explored: &Explored,
+        markers: &[Marker],
+    ) {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let rect = match self.view {
+            MinimapView::Hidden => return,
+            MinimapView::Corner => {
+                let size = width.min(height) * 2 / 5;
+                Rect {
+                    x: width.saturating_sub(size + 2),
+                    y: 2,
+                    width: size,
+                    height: size,
+                }
+            }
+            MinimapView::Automap => Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        };
+        if rect.width == 0 || rect.height == 0 || rect.y + rect.height > height {
+            return;
+        }
+
+        let transform = Transform::new(self, camera, &rect);
+        let visible = |x: i32, y: i32| !self.fog_of_war || explored.is_explored(x, y);
+
+        for sy in rect.y..rect.y + rect.height {
+            for sx in rect.x..rect.x + rect.width {
+                // Maps that wrap around repeat on every side
+                let world = map.wrap(transform.to_world(sx as f64 + 0.5, sy as f64 + 0.5));
+                let (x, y) = (world.x.floor() as i32, world.y.floor() as i32);
+                let color = match map.get(x, y) {
+                    Some(_) if !visible(x, y) => BACKGROUND_COLOR,
+                    Some(cell) if cell > 0 => self
+                        .wall_colors
+                        .get(cell as usize - 1)
+                        .copied()
+                        .unwrap_or(BORDER_COLOR),
+                    Some(_) => FLOOR_COLOR,
+                    None => BACKGROUND_COLOR,
+                };
+                framebuffer.set(sx, sy, color);
+            }
+        }
+
+        if self.view == MinimapView::Corner {
+            framebuffer.rect(
+                rect.x as i32 - 1,
+                rect.y as i32 - 1,
+                rect.width + 2,
+                rect.height + 2,
+                BORDER_COLOR,
+            );
+        }
+
+        for marker in markers {
+            if !visible(marker.pos.x.floor() as i32, marker.pos.y.floor() as i32) {
+                continue;
+            }
+            let (x, y) = transform.to_screen(marker.pos);
+            match marker.kind {
+                MarkerKind::Entity => draw_dot(framebuffer, &rect, x, y, 1, ENTITY_COLOR),
+                MarkerKind::Door => {
+                    let radius = (self.zoom / 4.).max(1.) as i64;
+                    draw_dot(framebuffer, &rect, x, y, radius, DOOR_COLOR)
+                }
+            }
+        }
+
+        // Frustum, the edges of the view are the rays through the sides of the screen
+        let (x, y) = transform.to_screen(camera.pos);
+        let (dir, plane) = (camera.dir(), camera.plane());
+        for edge in [dir - plane, dir + plane] {
+            let (edge_x, edge_y) = transform.to_screen(camera.pos + edge * FRUSTUM_LENGTH);
+            draw_line(framebuffer, &rect, (x, y), (edge_x, edge_y), FRUSTUM_COLOR);
+        }
+        draw_dot(framebuffer, &rect, x, y, 1, PLAYER_COLOR);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Rect {
+    fn contains(&self, x: i64, y: i64) -> bool {
+        x >= self.x as i64
+            && y >= self.y as i64
+            && x < (self.x + self.width) as i64
+            && y < (self.y + self.height) as i64
+    }
+}
+
+// Maps between world positions and screen positions. The camera is in the center of the minimap.
+struct Transform {
+    center_x: f64,
+    center_y: f64,
+    pos: Vec2,
+    // World directions pointing to the right of and up on the screen, both of length 1
+    right: Vec2,
+    up: Vec2,
+    zoom: f64,
+}
+
+impl Transform {
+    fn new(minimap: &Minimap, camera: &Camera, rect: &Rect) -> Self {
+        let (right, up) = if minimap.follow_rotation {
+            // The right side of the screen is where the camera plane points to
+            (camera.plane().normalize(), camera.dir())
+        } else {
+            // Not mirrored compared to the 3D view: looking down -x, +y is to the right of the camera
+            (Vec2::new(1., 0.), Vec2::new(0., 1.))
+        };
+        Self {
+            center_x: rect.x as f64 + rect.width as f64 / 2.,
+            center_y: rect.y as f64 + rect.height as f64 / 2.,
+            pos: camera.pos,
+            right,
+            up,
+            zoom: minimap.zoom,
+        }
+    }
+
+    fn to_world(&self, x: f64, y: f64) -> Vec2 {
+        let offset_x = (x - self.center_x) / self.zoom;
+        let offset_y = (y - self.center_y) / self.zoom;
+        self.pos + self.right * offset_x + self.up * -offset_y
+    }
+
+    fn to_screen(&self, world: Vec2) -> (i64, i64) {
+        let offset = world - self.pos;
+        let offset_x = offset.dot(self.right);
+        let offset_y = -offset.dot(self.up);
+        (
+            (self.center_x + offset_x * self.zoom).floor() as i64,
+            (self.center_y + offset_y * self.zoom).floor() as i64,
+        )
+    }
+}
+
+fn average_color(texture: &[u32]) -> u32 {
+    if texture.is_empty() {
+        return BORDER_COLOR;
+    }
+    let (mut r, mut g, mut b) = (0u64, 0u64, 0u64);
+    for color in texture {
+        r += ((color >> 16) & 0xff) as u64;
+        g += ((color >> 8) & 0xff) as u64;
+        b += (color & 0xff) as u64;
+    }
+    let n = texture.len() as u64;
+    (((r / n) << 16) | ((g / n) << 8) | (b / n)) as u32
+}
+
+fn plot(framebuffer: &mut Framebuffer, rect: &Rect, x: i64, y: i64, color: u32) {
+    if rect.contains(x, y) {
+        framebuffer.set(x as usize, y as usize, color);
+    }
+}
+
+fn draw_dot(framebuffer: &mut Framebuffer, rect: &Rect, x: i64, y: i64, radius: i64, color: u32) {
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            plot(framebuffer, rect, x + dx, y + dy, color);
+        }
+    }
+}
+
+fn draw_line(
+    framebuffer: &mut Framebuffer,
+    rect: &Rect,
+    from: (i64, i64),
+    to: (i64, i64),
+    color: u32,
+) {
+    for (x, y) in line_points(from, to) {
+        plot(framebuffer, rect, x, y, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::{Entity, OutOfBounds},
+        texture::test_textures,
+    };
+
+    // Room with solid walls around it and a wall column at x = 5
+    fn two_rooms() -> Map {
+        let mut map = Map::new(10, 5);
+        for x in 0..10 {
+            for y in 0..5 {
+                if x == 0 || y == 0 || x == 9 || y == 4 || x == 5 {
+                    map.set(x, y, 1);
+                }
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn walls_hide_what_is_behind_them() {
+        let map = two_rooms();
+        let mut explored = Explored::new(&map);
+        let camera = Camera::new(Vec2::new(2.5, 2.5), 0., 1.2);
+        explored.reveal_view(&camera, 64, &map);
+
+        assert!(explored.is_explored(2, 2) && explored.is_explored(4, 2));
+        for y in 1..4 {
+            assert!(explored.is_explored(5, y), "wall (5, {y})");
+            for x in 6..10 {
+                assert!(!explored.is_explored(x, y), "({x}, {y})");
+            }
+        }
+        // Behind the camera
+        assert!(!explored.is_explored(0, 2));
+    }
+
+    #[test]
+    fn draws_rotated_wrapping_maps() {
+        let mut map = two_rooms();
+        map.add_door(5, 2, 2);
+        map.entities.push(Entity {
+            kind: "barrel".to_string(),
+            pos: Vec2::new(7.5, 2.5),
+            radius: 0.3,
+        });
+        map.set_out_of_bounds(OutOfBounds::Wrap);
+        let mut explored = Explored::new(&map);
+        explored.reveal_all();
+        let markers = markers(&map);
+
+        let mut minimap = Minimap::new(&test_textures());
+        minimap.follow_rotation = true;
+        for view in [MinimapView::Corner, MinimapView::Automap] {
+            minimap.view = view;
+            for zoom in [MIN_ZOOM, 4., MAX_ZOOM] {
+                minimap.zoom = zoom;
+                for (width, height) in [(0, 0), (1, 1), (3, 40), (64, 48)] {
+                    let mut framebuffer = Framebuffer::new(width, height);
+                    for (x, y, yaw) in [
+                        (2.5, 2.5, 0.3),
+                        (-3.2, 7.9, 2.),
+                        (12.5, -0.5, -1.7),
+                        (1e6, -1e6, 4.),
+                    ] {
+                        let camera = Camera::new(Vec2::new(x, y), yaw, 1.2);
+                        minimap.draw(&mut framebuffer, &camera, &map, &explored, &markers);
+                    }
+                }
+            }
+        }
+
+        // The player is in the center of the corner view
+        minimap.view = MinimapView::Corner;
+        minimap.zoom = 4.;
+        let mut framebuffer = Framebuffer::new(64, 48);
+        let camera = Camera::new(Vec2::new(2.5, 2.5), 0.3, 1.2);
+        minimap.draw(&mut framebuffer, &camera, &map, &explored, &markers);
+        let size = 48 * 2 / 5;
+        assert_eq!(
+            framebuffer.get(64 - size - 2 + size / 2, 2 + size / 2),
+            PLAYER_COLOR
+        );
+
+        // A cell on the left edge shows up again past the right edge, next to the right edge's own cell
+        map.set(0, 2, 3);
+        minimap.view = MinimapView::Automap;
+        minimap.zoom = 3.;
+        let mut framebuffer = Framebuffer::new(64, 48);
+        let camera = Camera::new(Vec2::new(5.5, 2.5), 0.3, 1.2);
+        minimap.draw(&mut framebuffer, &camera, &map, &explored, &markers);
+        let rect = Rect {
+            x: 0,
+            y: 0,
+            width: 64,
+            height: 48,
+        };
+        let transform = Transform::new(&minimap, &camera, &rect);
+        let color_at = |x: f64, y: f64| {
+            let (sx, sy) = transform.to_screen(Vec2::new(x, y));
+            assert!(rect.contains(sx, sy), "({x}, {y})");
+            framebuffer.get(sx as usize, sy as usize)
+        };
+        assert_ne!(minimap.wall_colors[2], minimap.wall_colors[0]);
+        assert_eq!(color_at(0.5, 2.5), minimap.wall_colors[2]);
+        assert_eq!(color_at(10.5, 2.5), minimap.wall_colors[2]);
+        assert_eq!(color_at(9.5, 2.5), minimap.wall_colors[0]);
+        assert_eq!(color_at(5.5, 5.5), minimap.wall_colors[0]);
+    }
+}
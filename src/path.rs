@@ -0,0 +1,531 @@
+//! Pathfinding over the cells of a map, for actors that walk with the same collision as the player.
+//!
+//! A cell is walkable when an actor of the given radius standing in its center doesn't collide with anything
+//! ([`Map::collides`]). [`find_path`] finds the shortest path for one actor with A*, [`FlowField`] the next step
+//! towards one target from every cell at once, which is cheaper when many actors chase the same thing.
+
+use std::{cmp::Ordering, collections::BinaryHeap, f64::consts::SQRT_2};
+
+use crate::{
+    map::{Map, OutOfBounds},
+    math::Vec2,
+};
+
+/// When an actor may move diagonally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Diagonals {
+    Never,
+    /// Only if both cells next to the diagonal are walkable. Actors with a radius can't cut corners without
+    /// colliding, so this is the only rule they can actually follow.
+    #[default]
+    NoCornerCutting,
+    /// If at least one of the cells next to the diagonal is walkable, for actors that don't collide with
+    /// corners.
+    CornerCutting,
+}
+
+/// How closed doors are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Doors {
+    /// Closed doors are walls.
+    #[default]
+    Blocked,
+    /// The actor can open doors, entering a closed one costs `cost` in addition to the step.
+    Open { cost: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathSettings {
+    /// Half extent of the actor's collision square, like [`PlayerSettings::radius`](crate::player::PlayerSettings::radius).
+    pub radius: f64,
+    pub diagonals: Diagonals,
+    pub doors: Doors,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.2,
+            diagonals: Diagonals::default(),
+            doors: Doors::default(),
+        }
+    }
+}
+
+/// The cell containing a world position, None outside of the map.
+pub fn cell_of(map: &Map, pos: Vec2) -> Option<(usize, usize)> {
+    let pos = map.wrap(pos);
+    let (x, y) = (pos.x.floor(), pos.y.floor());
+    (x >= 0. && y >= 0. && x < map.width() as f64 && y < map.height() as f64)
+        .then_some((x as usize, y as usize))
+}
+
+/// Center of a cell in world coordinates.
+pub fn center(cell: (usize, usize)) -> Vec2 {
+    Vec2::new(cell.0 as f64 + 0.5, cell.1 as f64 + 0.5)
+}
+
+// Movement rules of one actor on one map
+struct Graph<'a> {
+    map: &'a Map,
+    settings: PathSettings,
+    wraps: bool,
+}
+
+impl<'a> Graph<'a> {
+    fn new(map: &'a Map, settings: PathSettings) -> Self {
+        Self {
+            map,
+            settings,
+            wraps: map.out_of_bounds() == OutOfBounds::Wrap,
+        }
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize {
+        y * self.map.width() + x
+    }
+
+    fn is_closed_door(&self, x: i32, y: i32) -> bool {
+        let (x, y) = self.wrap(x, y);
+        self.map
+            .door_at(x, y)
+            .is_some_and(|door| !self.map.doors()[door].open)
+    }
+
+    fn wrap(&self, x: i32, y: i32) -> (i32, i32) {
+        if self.wraps {
+            (
+                x.rem_euclid(self.map.width() as i32),
+                y.rem_euclid(self.map.height() as i32),
+            )
+        } else {
+            (x, y)
+        }
+    }
+
+    fn walkable(&self, x: i32, y: i32) -> bool {
+        let passable_doors = matches!(self.settings.doors, Doors::Open { .. });
+        let (cx, cy) = (x as f64 + 0.5, y as f64 + 0.5);
+        !self.map.overlaps(cx, cy, self.settings.radius, |x, y| {
+            self.map.is_solid(x, y) && !(passable_doors && self.is_closed_door(x, y))
+        })
+    }
+
+    // Extra cost of stepping into a cell
+    fn entry_cost(&self, (x, y): (usize, usize)) -> f64 {
+        match self.settings.doors {
+            Doors::Open { cost } if self.is_closed_door(x as i32, y as i32) => cost,
+            _ => 0.,
+        }
+    }
+
+    // Walkable cells one step away and the length of the step. Steps are symmetric, if b is a neighbour of a
+    // then a is one of b with the same length.
+    fn neighbours(&self, (x, y): (usize, usize)) -> Vec<((usize, usize), f64)> {
+        let (x, y) = (x as i32, y as i32);
+        let mut neighbours = Vec::with_capacity(8);
+        let mut step = |nx: i32, ny: i32, length: f64| {
+            let (nx, ny) = self.wrap(nx, ny);
+            if self.map.get(nx, ny).is_some() && self.walkable(nx, ny) {
+                neighbours.push(((nx as usize, ny as usize), length));
+            }
+        };
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            step(x + dx, y + dy, 1.);
+        }
+        if self.settings.diagonals == Diagonals::Never {
+            return neighbours;
+        }
+        for (dx, dy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let sides = [(x + dx, y), (x, y + dy)].map(|(x, y)| {
+                let (x, y) = self.wrap(x, y);
+                self.map.get(x, y).is_some() && self.walkable(x, y)
+            });
+            let allowed = match self.settings.diagonals {
+                Diagonals::Never => false,
+                Diagonals::NoCornerCutting => sides[0] && sides[1],
+                Diagonals::CornerCutting => sides[0] || sides[1],
+            };
+            if allowed {
+                step(x + dx, y + dy, SQRT_2);
+            }
+        }
+        neighbours
+    }
+
+    // Lower bound of the cost between two cells
+    fn estimate(&self, a: (usize, usize), b: (usize, usize)) -> f64 {
+        let distance = |a: usize, b: usize, size: usize| {
+            let d = a.abs_diff(b);
+            if self.wraps {
+                d.min(size - d)
+            } else {
+                d
+            }
+        };
+        let dx = distance(a.0, b.0, self.map.width()) as f64;
+        let dy = distance(a.1, b.1, self.map.height()) as f64;
+        match self.settings.diagonals {
+            Diagonals::Never => dx + dy,
+            _ => dx.max(dy) + (SQRT_2 - 1.) * dx.min(dy),
+        }
+    }
+
+    fn contains(&self, (x, y): (usize, usize)) -> bool {
+        x < self.map.width() && y < self.map.height() && self.walkable(x as i32, y as i32)
+    }
+}
+
+// Entry of the open set, ordered so that BinaryHeap pops the lowest priority first
+#[derive(Debug, PartialEq)]
+struct Open {
+    priority: f64,
+    cell: (usize, usize),
+}
+
+impl Eq for Open {}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A shortest path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// Cells from the start to the goal, both included.
+    pub cells: Vec<(usize, usize)>,
+    pub cost: f64,
+}
+
+impl Path {
+    /// Centers of the cells, the points an actor walks through.
+    pub fn waypoints(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.cells.iter().map(|cell| center(*cell))
+    }
+}
+
+/// Shortest path from `start` to `goal` with A*, None if either isn't walkable or there's no way between them.
+pub fn find_path(
+    map: &Map,
+    start: (usize, usize),
+    goal: (usize, usize),
+    settings: PathSettings,
+) -> Option<Path> {
+    let graph = Graph::new(map, settings);
+    if !graph.contains(start) || !graph.contains(goal) {
+        return None;
+    }
+    let mut costs = vec![f64::INFINITY; map.width() * map.height()];
+    let mut came_from = vec![None; map.width() * map.height()];
+    costs[graph.index(start)] = 0.;
+    let mut open = BinaryHeap::from([Open {
+        priority: graph.estimate(start, goal),
+        cell: start,
+    }]);
+
+    while let Some(Open { priority, cell }) = open.pop() {
+        let cost = costs[graph.index(cell)];
+        if cell == goal {
+            let mut cells = vec![goal];
+            while let Some(previous) = came_from[graph.index(cells[cells.len() - 1])] {
+                cells.push(previous);
+            }
+            cells.reverse();
+            return Some(Path { cells, cost });
+        }
+        // Stale entry of a cell that was reached cheaper in the meantime
+        if priority > cost + graph.estimate(cell, goal) {
+            continue;
+        }
+        for (next, step) in graph.neighbours(cell) {
+            let next_cost = cost + step + graph.entry_cost(next);
+            if next_cost < costs[graph.index(next)] {
+                costs[graph.index(next)] = next_cost;
+                came_from[graph.index(next)] = Some(cell);
+                open.push(Open {
+                    priority: next_cost + graph.estimate(next, goal),
+                    cell: next,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Cost of the shortest path from every cell to one target, computed with Dijkstra. Rebuild it when the target
+/// moves to another cell or doors change.
+#[derive(Debug, Clone)]
+pub struct FlowField {
+    width: usize,
+    height: usize,
+    target: (usize, usize),
+    costs: Vec<f64>,
+    // Cell to go to next, None at the target and where it can't be reached from
+    next: Vec<Option<(usize, usize)>>,
+}
+
+impl FlowField {
+    pub fn new(map: &Map, target: (usize, usize), settings: PathSettings) -> Self {
+        let graph = Graph::new(map, settings);
+        let mut field = Self {
+            width: map.width(),
+            height: map.height(),
+            target,
+            costs: vec![f64::INFINITY; map.width() * map.height()],
+            next: vec![None; map.width() * map.height()],
+        };
+        if !graph.contains(target) {
+            return field;
+        }
+
+        field.costs[graph.index(target)] = 0.;
+        let mut open = BinaryHeap::from([Open {
+            priority: 0.,
+            cell: target,
+        }]);
+        // Search backwards from the target, a step from `previous` to `cell` costs the step plus entering `cell`
+        while let Some(Open { priority, cell }) = open.pop() {
+            if priority > field.costs[graph.index(cell)] {
+                continue;
+            }
+            let entry = graph.entry_cost(cell);
+            for (previous, step) in graph.neighbours(cell) {
+                let cost = priority + step + entry;
+                if cost < field.costs[graph.index(previous)] {
+                    field.costs[graph.index(previous)] = cost;
+                    field.next[graph.index(previous)] = Some(cell);
+                    open.push(Open {
+                        priority: cost,
+                        cell: previous,
+                    });
+                }
+            }
+        }
+        field
+    }
+
+    pub fn target(&self) -> (usize, usize) {
+        self.target
+    }
+
+    /// Cost of the shortest path from `(x, y)` to the target, None if there's none.
+    pub fn cost(&self, x: usize, y: usize) -> Option<f64> {
+        let cost = *self
+            .costs
+            .get(y * self.width + x)
+            .filter(|_| x < self.width)?;
+        cost.is_finite().then_some(cost)
+    }
+
+    /// Cell to go to next from `(x, y)`, None at the target and where it can't be reached from.
+    pub fn next(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.next[y * self.width + x]
+    }
+
+    /// Unit vector from `pos` towards the center of the next cell on the way to the target, None where the
+    /// target can't be reached from. At the target it points to the target cell's center, or is zero there.
+    pub fn direction(&self, map: &Map, pos: Vec2) -> Option<Vec2> {
+        let cell = cell_of(map, pos)?;
+        let next = if cell == self.target {
+            cell
+        } else {
+            self.next(cell.0, cell.1)?
+        };
+        // Across a wrapping edge the next center is on the other side of the map, aim at its copy next to pos
+        let mut offset = center(next) - map.wrap(pos);
+        if map.out_of_bounds() == OutOfBounds::Wrap {
+            let (width, height) = (self.width as f64, self.height as f64);
+            offset.x -= (offset.x / width).round() * width;
+            offset.y -= (offset.y / height).round() * height;
+        }
+        Some(offset.normalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parses rows of `#` walls, `D` closed doors and `.` floor
+    fn grid(rows: &[&str]) -> Map {
+        let mut map = Map::new(rows[0].len(), rows.len());
+        let mut doors = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '#' => map.set(x, y, 1),
+                    'D' => doors.push((x, y)),
+                    _ => {}
+                }
+            }
+        }
+        for (x, y) in doors {
+            map.add_door(x, y, 2);
+        }
+        map
+    }
+
+    fn cells(
+        map: &Map,
+        start: (usize, usize),
+        goal: (usize, usize),
+        settings: PathSettings,
+    ) -> Option<Vec<(usize, usize)>> {
+        find_path(map, start, goal, settings).map(|path| path.cells)
+    }
+
+    const ORTHOGONAL: PathSettings = PathSettings {
+        radius: 0.2,
+        diagonals: Diagonals::Never,
+        doors: Doors::Blocked,
+    };
+
+    #[test]
+    fn walks_around_walls() {
+        let map = grid(&[
+            "#####", //
+            "#...#", "#.#.#", "#.#.#", "#####",
+        ]);
+        let path = find_path(&map, (1, 3), (3, 3), ORTHOGONAL).unwrap();
+        assert_eq!(
+            path.cells,
+            [(1, 3), (1, 2), (1, 1), (2, 1), (3, 1), (3, 2), (3, 3)]
+        );
+        assert_eq!(path.cost, 6.);
+        assert_eq!(cells(&map, (1, 3), (2, 2), ORTHOGONAL), None);
+        assert_eq!(cells(&map, (1, 3), (9, 9), ORTHOGONAL), None);
+    }
+
+    #[test]
+    fn corner_cutting() {
+        let map = grid(&[
+            "####", //
+            "#..#", "#.##", "####",
+        ]);
+        let with = |diagonals| PathSettings {
+            diagonals,
+            ..ORTHOGONAL
+        };
+        // Diagonal past the wall at (2, 2)
+        assert_eq!(
+            cells(&map, (1, 2), (2, 1), with(Diagonals::NoCornerCutting)),
+            Some(vec![(1, 2), (1, 1), (2, 1)])
+        );
+        assert_eq!(
+            cells(&map, (1, 2), (2, 1), with(Diagonals::CornerCutting)),
+            Some(vec![(1, 2), (2, 1)])
+        );
+
+        let open = grid(&[
+            "....", //
+            "....", "....",
+        ]);
+        let path = find_path(&open, (0, 0), (3, 2), with(Diagonals::NoCornerCutting)).unwrap();
+        assert_eq!(path.cells.len(), 4);
+        assert!((path.cost - (1. + 2. * SQRT_2)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn radius_keeps_out_of_narrow_gaps() {
+        let map = grid(&[
+            "#######", //
+            "#.....#", "###.###", "#.....#", "#######",
+        ]);
+        assert!(find_path(&map, (1, 1), (1, 3), ORTHOGONAL).is_some());
+        let wide = PathSettings {
+            radius: 0.6,
+            ..ORTHOGONAL
+        };
+        assert_eq!(cells(&map, (1, 1), (1, 3), wide), None);
+        // Every cell on the path is somewhere the actor fits, by the same rule as movement
+        let path = find_path(&map, (1, 1), (5, 3), ORTHOGONAL).unwrap();
+        assert!(path.waypoints().all(|p| !map.collides(p.x, p.y, 0.2)));
+    }
+
+    #[test]
+    fn doors() {
+        let mut map = grid(&[
+            "#####", //
+            "#.D.#", "#####",
+        ]);
+        assert_eq!(cells(&map, (1, 1), (3, 1), ORTHOGONAL), None);
+        let opening = PathSettings {
+            doors: Doors::Open { cost: 5. },
+            ..ORTHOGONAL
+        };
+        assert_eq!(find_path(&map, (1, 1), (3, 1), opening).unwrap().cost, 7.);
+        map.set_door_open(0, true);
+        assert_eq!(
+            find_path(&map, (1, 1), (3, 1), ORTHOGONAL).unwrap().cost,
+            2.
+        );
+        assert_eq!(find_path(&map, (1, 1), (3, 1), opening).unwrap().cost, 2.);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let mut map = grid(&[
+            "#####", //
+            ".#...", "#####",
+        ]);
+        assert_eq!(cells(&map, (0, 1), (4, 1), ORTHOGONAL), None);
+        map.set_out_of_bounds(OutOfBounds::Wrap);
+        assert_eq!(
+            cells(&map, (0, 1), (4, 1), ORTHOGONAL),
+            Some(vec![(0, 1), (4, 1)])
+        );
+        let field = FlowField::new(&map, (2, 1), ORTHOGONAL);
+        assert_eq!(field.cost(0, 1), Some(3.));
+        // The way from (0, 1) leads over the left edge
+        assert_eq!(
+            field.direction(&map, Vec2::new(0.5, 1.5)),
+            Some(Vec2::new(-1., 0.))
+        );
+    }
+
+    #[test]
+    fn flow_field_agrees_with_find_path() {
+        let map = Map::test_level();
+        let settings = PathSettings {
+            doors: Doors::Open { cost: 3. },
+            ..PathSettings::default()
+        };
+        let target = (22, 11);
+        let field = FlowField::new(&map, target, settings);
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let path = find_path(&map, (x, y), target, settings);
+                let cost = field.cost(x, y);
+                assert_eq!(path.is_some(), cost.is_some(), "({x}, {y})");
+                let (Some(path), Some(cost)) = (path, cost) else {
+                    continue;
+                };
+                assert!((path.cost - cost).abs() < 1e-9, "({x}, {y})");
+
+                // Following the field reaches the target for the same cost
+                let (mut cell, mut steps) = ((x, y), 0);
+                while let Some(next) = field.next(cell.0, cell.1) {
+                    cell = next;
+                    steps += 1;
+                    assert!(steps <= map.width() * map.height());
+                }
+                assert_eq!(cell, target);
+            }
+        }
+        assert_eq!(
+            field.direction(&map, Vec2::new(22.5, 11.5)),
+            Some(Vec2::ZERO)
+        );
+    }
+}
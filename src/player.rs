@@ -0,0 +1,273 @@
+use std::f64::consts::PI;
+
+use crate::{
+    camera::Camera,
+    input::{Action, Input},
+    map::Map,
+    math::Vec2,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerSettings {
+    /// Top speed in cells per second.
+    pub walk_speed: f64,
+    pub run_speed: f64,
+    /// How fast the player reaches the requested speed, in cells per second squared.
+    pub acceleration: f64,
+    /// How fast the player stops when there is no movement input, in cells per second squared.
+    pub friction: f64,
+    /// Radians per second when turning with keys.
+    pub turn_speed: f64,
+    /// Half the side of the player's collision box.
+    pub radius: f64,
+    /// Distance covered by one step, the head bobs once per step.
+    pub step_length: f64,
+    /// Maximum vertical camera offset caused by head bob, in wall heights.
+    pub bob_amplitude: f64,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            walk_speed: 2.,
+            run_speed: 5.,
+            acceleration: 20.,
+            friction: 12.,
+            turn_speed: 0.85 * PI,
+            radius: 0.2,
+            step_length: 0.8,
+            bob_amplitude: 0.025,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlayerEvent {
+    /// A foot touched the ground, at the given position.
+    Footstep(Vec2),
+    /// The player moved into another cell, after wrapping around the map.
+    EnterCell { x: i32, y: i32 },
+}
+
+/// Moves a [`Camera`] around a [`Map`] from action input, with acceleration, friction, strafing,
+/// collision and head bob.
+#[derive(Debug, Clone)]
+pub struct PlayerController {
+    pub settings: PlayerSettings,
+    velocity: Vec2,
+    // Advances by PI every step
+    bob_phase: f64,
+    events: Vec<PlayerEvent>,
+}
+
+impl PlayerController {
+    pub fn new(settings: PlayerSettings) -> Self {
+        Self {
+            settings,
+            velocity: Vec2::ZERO,
+            bob_phase: 0.,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.velocity.length()
+    }
+
+    /// Walk cycle phase in radians, one step every PI.
+    pub fn bob_phase(&self) -> f64 {
+        self.bob_phase
+    }
+
+    /// How pronounced the bob is right now, 0 when standing still and 1 at walking speed or faster.
+    pub fn bob_intensity(&self) -> f64 {
+        (self.speed() / self.settings.walk_speed).min(1.)
+    }
+
+    /// Vertical camera offset caused by head bob. Lowest when a foot touches the ground.
+    pub fn bob_offset(&self) -> f64 {
+        -self.settings.bob_amplitude * (1. - self.bob_phase.sin().abs()) * self.bob_intensity()
+    }
+
+    /// Events produced by the last [`PlayerController::update`].
+    pub fn events(&self) -> &[PlayerEvent] {
+        &self.events
+    }
+
+    /// Advances the player by one tick of `dt` seconds. `mouse_turn` is an extra rotation in radians,
+    /// positive to the left.
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        input: &Input,
+        mouse_turn: f64,
+        map: &Map,
+        dt: f64,
+    ) {
+        self.events.clear();
+        let settings = self.settings;
+
+        let turn =
+            input.axis(Action::TurnRight, Action::TurnLeft) * settings.turn_speed * dt + mouse_turn;
+        camera.rotate(turn);
+
+        // plane is perpendicular to dir and points to the right, so strafing moves along it
+        let forward = camera.dir();
+        let right = camera.plane().normalize();
+
+        let wish = forward * input.axis(Action::MoveBackward, Action::MoveForward)
+            + right * input.axis(Action::StrafeLeft, Action::StrafeRight);
+        // Don't move faster diagonally, analog sticks can ask for less than full speed
+        let wish_length = wish.length();
+        let max_speed = if input.is_down(Action::Run) {
+            settings.run_speed
+        } else {
+            settings.walk_speed
+        };
+        let (target, rate) = if wish_length > 0. {
+            (
+                wish * (max_speed / wish_length.max(1.)),
+                settings.acceleration,
+            )
+        } else {
+            (Vec2::ZERO, settings.friction)
+        };
+
+        // Approach the target velocity by at most rate * dt
+        let diff = target - self.velocity;
+        let diff_length = diff.length();
+        let max_change = rate * dt;
+        if diff_length <= max_change {
+            self.velocity = target;
+        } else {
+            self.velocity += diff * (max_change / diff_length);
+        }
+
+        // Move one axis at a time so the player slides along walls instead of sticking to them
+        let cell = |pos: Vec2| {
+            let pos = map.wrap(pos);
+            (pos.x.floor() as i32, pos.y.floor() as i32)
+        };
+        let previous_cell = cell(camera.pos);
+        let pos = &mut camera.pos;
+        let step = self.velocity * dt;
+        if !map.collides(pos.x + step.x, pos.y, settings.radius) {
+            pos.x += step.x;
+        } else {
+            self.velocity.x = 0.;
+        }
+        if !map.collides(pos.x, pos.y + step.y, settings.radius) {
+            pos.y += step.y;
+        } else {
+            self.velocity.y = 0.;
+        }
+        let (x, y) = cell(camera.pos);
+        if (x, y) != previous_cell {
+            self.events.push(PlayerEvent::EnterCell { x, y });
+        }
+
+        let previous_step = (self.bob_phase / PI).floor();
+        let moved = self.speed() * dt;
+        if moved > 0. {
+            self.bob_phase += moved / settings.step_length * PI;
+        } else {
+            // Settle at the top of the cycle when standing still
+            self.bob_phase = (self.bob_phase / PI).floor() * PI + PI / 2.;
+        }
+        if (self.bob_phase / PI).floor() > previous_step {
+            self.events.push(PlayerEvent::Footstep(camera.pos));
+        }
+        camera.z = self.bob_offset();
+    }
+}
+
+impl Default for PlayerController {
+    fn default() -> Self {
+        Self::new(PlayerSettings::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::FRAC_PI_4;
+
+    use super::*;
+    use crate::{input::Bindings, map::OutOfBounds};
+
+    const DT: f64 = 1. / 60.;
+
+    fn forward() -> Input {
+        let mut input = Input::new(Bindings::empty());
+        input.set_analog(Action::MoveForward, 1.);
+        input
+    }
+
+    #[test]
+    fn accelerates_to_walk_speed_with_footsteps() {
+        let map = Map::new(12, 3);
+        let mut camera = Camera::new(Vec2::new(1.5, 1.5), 0., 1.);
+        let mut player = PlayerController::default();
+        let input = forward();
+
+        let mut speed = 0.;
+        let mut footsteps = 0;
+        for _ in 0..120 {
+            player.update(&mut camera, &input, 0., &map, DT);
+            assert!(player.speed() >= speed && player.speed() <= player.settings.walk_speed);
+            speed = player.speed();
+            footsteps += player
+                .events()
+                .iter()
+                .filter(|event| matches!(event, PlayerEvent::Footstep(_)))
+                .count();
+        }
+        assert_eq!(speed, player.settings.walk_speed);
+        let walked = camera.pos.x - 1.5;
+        assert_eq!(footsteps, (walked / player.settings.step_length) as usize);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let mut map = Map::new(8, 20);
+        for y in 0..20 {
+            map.set(4, y, 1);
+        }
+        // Diagonally into the wall at x = 4
+        let mut camera = Camera::new(Vec2::new(3.5, 2.5), FRAC_PI_4, 1.);
+        let mut player = PlayerController::default();
+        let input = forward();
+        let start_y = camera.pos.y;
+        for _ in 0..60 {
+            player.update(&mut camera, &input, 0., &map, DT);
+        }
+        assert_eq!(player.velocity().x, 0.);
+        assert!(player.velocity().y.abs() > 1.);
+        assert!(camera.pos.x <= 4. - player.settings.radius);
+        assert!((camera.pos.y - start_y).abs() > 0.5);
+    }
+
+    #[test]
+    fn one_enter_cell_per_crossing() {
+        let mut map = Map::new(4, 3);
+        map.set_out_of_bounds(OutOfBounds::Wrap);
+        let mut camera = Camera::new(Vec2::new(3.5, 1.5), 0., 1.);
+        let mut player = PlayerController::default();
+        let input = forward();
+
+        let mut entered = Vec::new();
+        for _ in 0..120 {
+            player.update(&mut camera, &input, 0., &map, DT);
+            camera.pos = map.wrap(camera.pos);
+            for event in player.events() {
+                if let PlayerEvent::EnterCell { x, y } = *event {
+                    entered.push((x, y));
+                }
+            }
+        }
+        assert_eq!(entered, [(0, 1), (1, 1), (2, 1), (3, 1)]);
+    }
+}
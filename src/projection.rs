@@ -0,0 +1,162 @@
+use crate::camera::Camera;
+
+/// Maps between camera space and screen pixels, shared by the wall and the floor passes so they agree on where
+/// things end up.
+///
+/// The horizontal field of view comes from the camera (`2 * atan(|plane| / |dir|)`), the vertical one follows
+/// from the surface aspect ratio and the pixel aspect ratio, so changing the resolution never stretches walls.
+/// Rows are sampled at their centers (`y + 0.5`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projection {
+    width: usize,
+    height: usize,
+    // Pixels per unit of height at a distance of 1
+    focal_y: f64,
+    horizon: f64,
+    // Eye height above the floor, in wall heights
+    eye: f64,
+}
+
+/// Where the wall of a column starts and ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallSpan {
+    /// Screen y of the top and bottom edges of the wall, not clamped to the screen.
+    pub top: f64,
+    pub bottom: f64,
+    /// First and last (inclusive) rows whose centers are covered by the wall, `None` if it covers none.
+    pub rows: Option<(usize, usize)>,
+}
+
+impl Projection {
+    /// `pixel_aspect` is the width of a pixel divided by its height as it ends up on the display, 1 for square
+    /// pixels.
+    pub fn new(width: usize, height: usize, pixel_aspect: f64, camera: &Camera) -> Self {
+        // camera_x goes from -1 to 1 across the screen, so half the width covers tan(fov / 2)
+        let focal_x = width as f64 / 2. / (camera.fov / 2.).tan();
+        Self {
+            width,
+            height,
+            focal_y: focal_x * pixel_aspect,
+            horizon: height as f64 / 2.,
+            eye: 0.5 + camera.z,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Position on the camera plane of column `x`, -1 at the left edge of the screen and 1 at the right one.
+    pub fn camera_x(&self, x: usize) -> f64 {
+        2. * x as f64 / self.width as f64 - 1.
+    }
+
+    /// Wall of height 1 standing at the perpendicular distance `distance`.
+    pub fn wall(&self, distance: f64) -> WallSpan {
+        let scale = self.focal_y / distance;
+        let top = self.horizon - (1. - self.eye) * scale;
+        let bottom = self.horizon + self.eye * scale;
+
+        // Row y is covered if top <= y + 0.5 < bottom
+        let first = (top - 0.5).ceil().max(0.);
+        let last = ((bottom - 0.5).ceil() - 1.).min(self.height as f64 - 1.);
+        let rows = (first <= last).then_some((first as usize, last as usize));
+        WallSpan { top, bottom, rows }
+    }
+
+    /// Perpendicular distance of the floor (`is_floor` true) or ceiling seen by row `y`, `None` for the row
+    /// exactly at the horizon.
+    pub fn row_distance(&self, y: usize) -> Option<(f64, bool)> {
+        let from_horizon = y as f64 + 0.5 - self.horizon;
+        if from_horizon > 0. {
+            Some((self.eye * self.focal_y / from_horizon, true))
+        } else if from_horizon < 0. {
+            Some(((1. - self.eye) * self.focal_y / -from_horizon, false))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec2;
+
+    const RESOLUTIONS: [(usize, usize); 6] = [
+        (160, 144),
+        (320, 200),
+        (640, 480),
+        (1920, 1080),
+        (100, 300),
+        (75, 33),
+    ];
+
+    fn camera(z: f64) -> Camera {
+        let mut camera =
+            Camera::from_vectors(Vec2::new(0., 0.), Vec2::new(-1., 0.), Vec2::new(0., 0.66));
+        camera.z = z;
+        camera
+    }
+
+    #[test]
+    fn walls_meet_floor_and_ceiling() {
+        for (width, height) in RESOLUTIONS {
+            for pixel_aspect in [1., 5. / 6., 1.2] {
+                for z in [0., 0.02, -0.03] {
+                    let projection = Projection::new(width, height, pixel_aspect, &camera(z));
+                    for distance in [0.3, 0.8, 1., 2.7, 10., 55.] {
+                        let Some((first, last)) = projection.wall(distance).rows else {
+                            continue;
+                        };
+                        // The row below the wall sees floor in front of the wall, the last wall row would see
+                        // floor behind it
+                        if last + 1 < height {
+                            let (below, is_floor) = projection.row_distance(last + 1).unwrap();
+                            assert!(is_floor);
+                            assert!(below <= distance, "{width}x{height} {distance}: {below}");
+                            if let Some((behind, true)) = projection.row_distance(last) {
+                                assert!(behind > distance, "{width}x{height} {distance}: {behind}");
+                            }
+                        }
+                        if first > 0 {
+                            let (above, is_floor) = projection.row_distance(first - 1).unwrap();
+                            assert!(!is_floor);
+                            assert!(above <= distance, "{width}x{height} {distance}: {above}");
+                            if let Some((behind, false)) = projection.row_distance(first) {
+                                assert!(behind > distance, "{width}x{height} {distance}: {behind}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wall_size_follows_width_not_aspect() {
+        // With square pixels and a fixed horizontal FOV, a wall covers the same number of pixels relative to
+        // the width no matter the height of the surface
+        for (width, height) in RESOLUTIONS {
+            let projection = Projection::new(width, height, 1., &camera(0.));
+            let wall = projection.wall(4.);
+            let relative = (wall.bottom - wall.top) / width as f64;
+            assert!(
+                (relative - 0.5 / 0.66 / 4.).abs() < 1e-12,
+                "{width}x{height}"
+            );
+        }
+    }
+
+    #[test]
+    fn pixel_aspect_scales_walls_vertically() {
+        let square = Projection::new(320, 200, 1., &camera(0.)).wall(2.);
+        let tall = Projection::new(320, 200, 1.2, &camera(0.)).wall(2.);
+        let ratio = (tall.bottom - tall.top) / (square.bottom - square.top);
+        assert!((ratio - 1.2).abs() < 1e-12);
+    }
+}
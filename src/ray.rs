@@ -0,0 +1,127 @@
+use crate::{map::Map, math::Vec2};
+
+/// One cell a [`Ray`] entered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayStep {
+    pub x: i32,
+    pub y: i32,
+    /// 0 if the cell was entered through an x side (vertical grid line), 1 for a y side
+    pub side: u8,
+    /// Distance from the camera plane to where the ray entered the cell, in multiples of the ray direction.
+    pub distance: f64,
+}
+
+/// Walks the grid cells a ray passes through with DDA (Digital Differential Analysis), in order, starting with
+/// the first cell after the one containing the origin.
+///
+/// The iterator never ends on its own, stop it at the first cell you are interested in or use [`cast`], which
+/// also gives up after a maximum distance.
+#[derive(Debug, Clone)]
+pub struct Ray {
+    map_x: i32,
+    map_y: i32,
+    // Direction to step in on each axis, either +1 or -1
+    step_x: i32,
+    step_y: i32,
+    side_dist: Vec2,
+    delta_dist: Vec2,
+}
+
+impl Ray {
+    pub fn new(pos: Vec2, ray_dir: Vec2) -> Self {
+        let (map_x, map_y) = (pos.x.floor() as i32, pos.y.floor() as i32);
+        // Offsets are taken from the unconverted cell coordinates, the conversion saturates for positions far
+        // outside of the i32 range and the ray would start at a huge negative distance otherwise
+        let (cell_x, cell_y) = (pos.x.floor(), pos.y.floor());
+
+        let mut side_dist = Vec2::ZERO;
+
+        // When you try to derive this formula, you will get |ray_dir| / ray_dir.x and |ray_dir| / ray_dir.y
+        // (Where |ray_dir| is the length of the ray_dir vector) When you simplify the entire equation
+        // (including some calculations after this one), you will see that |ray_dir| can be discarded
+        // (because we only really need the ratio between ray_dir.x and .y).
+        let delta_dist = Vec2::new((1. / ray_dir.x).abs(), (1. / ray_dir.y).abs());
+
+        let (step_x, step_y);
+
+        if ray_dir.x < 0. {
+            step_x = -1;
+            side_dist.x = (pos.x - cell_x) * delta_dist.x;
+        } else {
+            step_x = 1;
+            // map_x < pos.x < map_x + 1 and the expression below needs to be positive
+            // and also map_x + 1 - pos.x is the actaul geometrically correct length to use here
+            // since map_x is the left-bottom corner of a square.
+
+            // delta_dist.x is the amount of distance the ray needs to go to reach the closest vertical line
+            // that is 1 unit away from the starting position on the x axis.
+            side_dist.x = (cell_x + 1. - pos.x) * delta_dist.x;
+        }
+
+        if ray_dir.y < 0. {
+            step_y = -1;
+            side_dist.y = (pos.y - cell_y) * delta_dist.y;
+        } else {
+            step_y = 1;
+            // delta_dist.y is the amount of distance the ray needs to go to reach the closest horizontal line
+            // that is 1 unit away from the starting position on the y axis.
+            side_dist.y = (cell_y + 1. - pos.y) * delta_dist.y;
+        }
+
+        Self {
+            map_x,
+            map_y,
+            step_x,
+            step_y,
+            side_dist,
+            delta_dist,
+        }
+    }
+}
+
+impl Iterator for Ray {
+    type Item = RayStep;
+
+    fn next(&mut self) -> Option<RayStep> {
+        // Step towards the closest side
+        let side = if self.side_dist.x < self.side_dist.y {
+            self.side_dist.x += self.delta_dist.x;
+            self.map_x = self.map_x.wrapping_add(self.step_x);
+            0
+        } else {
+            self.side_dist.y += self.delta_dist.y;
+            self.map_y = self.map_y.wrapping_add(self.step_y);
+            1
+        };
+
+        // To avoid the fisheye effect, we calculate the distance between the point and the camera _plane_.
+        // (Hence the name, perp(enducilar)_wall_distance)
+        // You can calculate the actual euclidean distance between the camera and the hit point but that would be
+        // more work and would result in the aforementioned fisheye effect.
+
+        // The equation below can be derived from a bunch of similar triangles and ratios between the hit point
+        // and the camera plane. If you do not want to sit down and derive the same equation, you can imagine the
+        // -delta_dist part as just going a step back to get out of the wall after the last DDA step.
+        let distance = if side == 0 {
+            self.side_dist.x - self.delta_dist.x
+        } else {
+            self.side_dist.y - self.delta_dist.y
+        };
+
+        Some(RayStep {
+            x: self.map_x,
+            y: self.map_y,
+            side,
+            distance,
+        })
+    }
+}
+
+/// First wall of `map` along the ray, `None` if there is none within `max_distance`. This always terminates, even
+/// for maps without a closed border and rays that start outside of the map or have a NaN direction.
+pub fn cast(pos: Vec2, ray_dir: Vec2, map: &Map, max_distance: f64) -> Option<RayStep> {
+    Ray::new(pos, ray_dir)
+        // Every step adds at least 1 / |ray_dir| to the distance, and NaN distances never pass
+        .take_while(|step| step.distance <= max_distance)
+        .find(|step| map.is_wall(step.x, step.y))
+}
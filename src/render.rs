@@ -0,0 +1,301 @@
+use crate::{
+    camera::Camera,
+    consts::{MAX_RAY_DISTANCE, TEXTURE_HEIGHT, TEXTURE_WIDTH},
+    floor::{self, FloorRow, FloorTextures},
+    framebuffer::Framebuffer,
+    map::Map,
+    projection::Projection,
+    ray::{self, RayStep},
+};
+
+// 8355711 is the decimal value of 0b00000000011111110111111101111111 which is the mask we use the divide all 3 values by 2
+pub(crate) fn darken(color: u32) -> u32 {
+    (color >> 1) & 8355711
+}
+
+/// What the ray of a single screen column hit.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct WallColumn {
+    // First and last (inclusive) rows covered by the wall, None if it covers none or the ray hit nothing
+    pub(crate) rows: Option<(usize, usize)>,
+    pub(crate) texture: usize,
+    pub(crate) texture_x: usize,
+    // Texture y coordinate at the first row and how much it advances per row
+    pub(crate) tex_start: f64,
+    pub(crate) tex_step: f64,
+    // 0 if an x side (vertical grid line) was hit, 1 for a y side
+    pub(crate) side: u8,
+    // Perpendicular distance to the wall, only the palette renderer shades by it
+    #[cfg_attr(not(feature = "palette"), allow(dead_code))]
+    pub(crate) distance: f64,
+}
+
+/// Renders the view of `camera` into `framebuffer`. `pixel_aspect` is the width of a pixel divided by its height
+/// on the display, see [`Projection::new`].
+///
+/// Any map can be rendered, what's outside of it depends on [`Map::out_of_bounds`]. Rays give up after
+/// [`MAX_RAY_DISTANCE`] and missing or malformed textures are drawn black.
+///
+/// Rendering happens in two passes: first a ray is cast for every column to find the wall it hits, then every
+/// row is drawn using those results. Rows don't depend on each other, which is what
+/// [`render_parallel`] takes advantage of.
+pub fn render(
+    framebuffer: &mut Framebuffer,
+    camera: &Camera,
+    map: &Map,
+    textures: &[Vec<u32>],
+    pixel_aspect: f64,
+) {
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    if width == 0 || height == 0 {
+        return;
+    }
+    let projection = Projection::new(width, height, pixel_aspect, camera);
+
+    let floor_textures = FloorTextures::validate(textures, map);
+    let columns: Vec<_> = (0..width)
+        .map(|x| cast_column(x, &projection, camera, map))
+        .collect();
+    for (y, row) in framebuffer.pixels_mut().chunks_exact_mut(width).enumerate() {
+        let floor_textures = floor_textures.as_ref();
+        draw_row(
+            y,
+            row,
+            &projection,
+            camera,
+            map,
+            textures,
+            floor_textures,
+            &columns,
+        );
+    }
+}
+
+/// Same as [`render`], but the columns and rows are split into stripes that are rendered on the rayon thread pool.
+/// The output is identical to the serial path.
+#[cfg(feature = "rayon")]
+pub fn render_parallel(
+    framebuffer: &mut Framebuffer,
+    camera: &Camera,
+    map: &Map,
+    textures: &[Vec<u32>],
+    pixel_aspect: f64,
+) {
+    use rayon::prelude::*;
+
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    if width == 0 || height == 0 {
+        return;
+    }
+    let projection = Projection::new(width, height, pixel_aspect, camera);
+
+    let floor_textures = FloorTextures::validate(textures, map);
+    let columns: Vec<_> = (0..width)
+        .into_par_iter()
+        .map(|x| cast_column(x, &projection, camera, map))
+        .collect();
+
+    // One stripe of rows per thread, splitting any finer only adds scheduling overhead
+    let stripe_height = height.div_ceil(rayon::current_num_threads()).max(1);
+    framebuffer
+        .pixels_mut()
+        .par_chunks_mut(width * stripe_height)
+        .enumerate()
+        .for_each(|(stripe, pixels)| {
+            for (i, row) in pixels.chunks_exact_mut(width).enumerate() {
+                let y = stripe * stripe_height + i;
+                let floor_textures = floor_textures.as_ref();
+                draw_row(
+                    y,
+                    row,
+                    &projection,
+                    camera,
+                    map,
+                    textures,
+                    floor_textures,
+                    &columns,
+                );
+            }
+        });
+}
+
+pub(crate) fn cast_column(
+    x: usize,
+    projection: &Projection,
+    camera: &Camera,
+    map: &Map,
+) -> WallColumn {
+    let (pos, dir, plane) = (camera.pos, camera.dir(), camera.plane());
+
+    // https://lodev.org/cgtutor/raycasting.html:
+    // " cameraX is the x-coordinate on the camera plane that the current x-coordinate of the screen represents,
+    // done this way so that the right side of the screen will get coordinate 1, the center of the screen gets
+    // coordinate 0, and the left side of the screen gets coordinate -1. "
+    // So if x is at the left side of the screen camera_x becomes -1 and causes makes ray_dir point to the left.
+    // If x is -0.5, ray_dir will point to the left (left of the direction vector) but only
+    // as much as half of the plane vector.
+    let camera_x = projection.camera_x(x);
+    let ray_dir = dir + plane * camera_x;
+
+    let Some(RayStep {
+        x: map_x,
+        y: map_y,
+        side,
+        distance: perp_wall_dist,
+    }) = ray::cast(pos, ray_dir, map, MAX_RAY_DISTANCE)
+    else {
+        return WallColumn::default();
+    };
+
+    // The floor pass gets its row distances from the same projection, so walls always meet the floor
+    let wall = projection.wall(perp_wall_dist);
+
+    // - 1 so that texture 0 can be used
+    let texture = map.cell(map_x, map_y) as usize - 1;
+
+    let wall_x = {
+        // in my version x and y were flipped, which is probably one of the reasons why it didnt work
+        if side == 0 {
+            pos.y + perp_wall_dist * ray_dir.y
+        } else {
+            pos.x + perp_wall_dist * ray_dir.x
+        }
+    };
+    let wall_x = wall_x - wall_x.floor(); // basically what i did previously
+
+    // The min only matters for a camera at an infinite position, where wall_x is NaN
+    let texture_x = ((wall_x * (TEXTURE_WIDTH as f64)) as u32).min(TEXTURE_WIDTH - 1);
+    let texture_x = {
+        if side == 0 && ray_dir.x > 0. || side == 1 && ray_dir.y < 0. {
+            TEXTURE_WIDTH - texture_x - 1
+        } else {
+            texture_x
+        }
+    };
+
+    let tex_step = TEXTURE_HEIGHT as f64 / (wall.bottom - wall.top);
+    let first_row = wall.rows.map_or(0, |(first, _)| first);
+    let tex_start = (first_row as f64 + 0.5 - wall.top) * tex_step;
+
+    WallColumn {
+        rows: wall.rows,
+        texture,
+        texture_x: texture_x as usize,
+        tex_start,
+        tex_step,
+        side,
+        distance: perp_wall_dist,
+    }
+}
+
+/// Pixel `index` of `texture`, black if there is no such pixel.
+pub(crate) fn texel(textures: &[Vec<u32>], texture: usize, index: usize) -> u32 {
+    textures
+        .get(texture)
+        .and_then(|texture| texture.get(index))
+        .copied()
+        .unwrap_or(0)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_row(
+    y: usize,
+    row: &mut [u32],
+    projection: &Projection,
+    camera: &Camera,
+    map: &Map,
+    textures: &[Vec<u32>],
+    floor_textures: Option<&FloorTextures>,
+    columns: &[WallColumn],
+) {
+    match FloorRow::new(y, projection, camera) {
+        Some(floor_row) => match floor_textures {
+            Some(floor_textures) => floor::draw_span_fast(&floor_row, row, floor_textures),
+            None => floor::draw_span(&floor_row, row, map, textures),
+        },
+        None => row.fill(0),
+    }
+
+    for (x, column) in columns.iter().enumerate() {
+        let Some((first, last)) = column.rows else {
+            continue;
+        };
+        if y < first || y > last {
+            continue;
+        }
+        // Computed from the row instead of accumulated so every row can be drawn on its own
+        let tex_pos = column.tex_start + (y - first) as f64 * column.tex_step;
+        let texture_y = tex_pos as usize & (TEXTURE_HEIGHT as usize - 1);
+        let color = texel(
+            textures,
+            column.texture,
+            TEXTURE_HEIGHT as usize * texture_y + column.texture_x,
+        );
+        row[x] = if column.side == 1 {
+            darken(color)
+        } else {
+            color
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{map::OutOfBounds, math::Vec2, texture::test_textures};
+
+    fn out_of_bounds() -> impl Strategy<Value = OutOfBounds> {
+        prop_oneof![
+            Just(OutOfBounds::Wall),
+            Just(OutOfBounds::Void),
+            Just(OutOfBounds::Wrap),
+        ]
+    }
+
+    fn map() -> impl Strategy<Value = Map> {
+        (0usize..6, 0usize..6, out_of_bounds()).prop_flat_map(|(width, height, out_of_bounds)| {
+            prop::collection::vec(any::<i8>(), width * height).prop_map(move |cells| {
+                let mut map = Map::new(width, height);
+                for (i, cell) in cells.into_iter().enumerate() {
+                    map.set(i % width, i / width, cell);
+                }
+                map.set_out_of_bounds(out_of_bounds);
+                map
+            })
+        })
+    }
+
+    fn coordinate() -> impl Strategy<Value = f64> {
+        prop_oneof![
+            -10f64..16.,
+            Just(0.),
+            Just(3.),
+            Just(-1e12),
+            Just(f64::INFINITY),
+            Just(f64::NAN),
+        ]
+    }
+
+    proptest! {
+        // Any cell value, texture id, camera position and out of bounds policy, including maps without a border
+        #[test]
+        fn never_panics(
+            map in map(),
+            x in coordinate(),
+            y in coordinate(),
+            yaw in -10f64..10.,
+            all_textures in any::<bool>(),
+        ) {
+            // Generating whole textures would store them in the regressions file, so only pick how many
+            let mut textures = test_textures();
+            if !all_textures {
+                textures.truncate(3);
+            }
+            let camera = Camera::new(Vec2::new(x, y), yaw, 1.2);
+            let mut framebuffer = Framebuffer::new(16, 12);
+            render(&mut framebuffer, &camera, &map, &textures, 1.);
+        }
+    }
+}
@@ -0,0 +1,610 @@
+//! Saved games.
+//!
+//! A save only holds what changed while playing, so it's restored on top of the level it was captured from.
+//! The first line is the version of the format, the rest uses the statements of [`crate::level`]:
+//! ```text
+//! rayguy-save 2
+//! size 24 24
+//! camera 22 11.5 3.141592653589793 1.1519173063162575
+//! tile 3 4 -2
+//! door 0 2 open
+//! entity barrel 2.5 1.5 0.3
+//! trigger 0 fired outside
+//! timer play_time 12.5
+//! var visits int 3
+//! ```
+//! `tile` lists the cells that differ from the level apart from doors, `door INDEX CELL open|closed` every door
+//! and `entity` every entity. `var NAME bool|int|float|text VALUE` holds the variables of the level's scripts.
+//! Entity kinds, timer and variable names and text values can't contain spaces or `#`.
+//!
+//! Saves of older versions are migrated when they're parsed, newer ones are rejected.
+
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::{
+    camera::Camera,
+    level::{arguments, is_word, number},
+    map::{Entity, Map},
+    math::Vec2,
+    trigger::Triggers,
+};
+
+/// Version of the format [`SaveGame`] writes.
+pub const VERSION: u32 = 2;
+
+const HEADER: &str = "rayguy-save";
+
+// Upgrades the lines of a version `i + 1` save to version `i + 2`. Whenever the format changes, bump VERSION and
+// add the step from the previous version here, old saves go through every step up to VERSION.
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [add_vars];
+
+type Migration = fn(Vec<String>) -> Result<Vec<String>, String>;
+
+// Version 2 added `var`, version 1 saves have no variables
+fn add_vars(lines: Vec<String>) -> Result<Vec<String>, String> {
+    Ok(lines)
+}
+
+// Runs the lines of a `version` save through the steps of `migrations` from there on, `migrations[0]` upgrades
+// version 1
+fn migrate(
+    mut lines: Vec<String>,
+    version: u32,
+    migrations: &[Migration],
+) -> Result<Vec<String>, SaveError> {
+    let first = version as usize - 1;
+    for (from, migrate) in (version..).zip(migrations.get(first..).unwrap_or_default()) {
+        lines = migrate(lines).map_err(|message| SaveError::Migration { from, message })?;
+    }
+    Ok(lines)
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    /// Lines of migrated saves are counted after migrating.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The save was written by a newer version of the game.
+    Version(u32),
+    Migration {
+        from: u32,
+        message: String,
+    },
+    /// The save wasn't captured from the level it's restored on.
+    Mismatch(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "couldn't read save: {e}"),
+            SaveError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            SaveError::Version(version) => write!(
+                f,
+                "save version {version} is newer than the supported version {VERSION}"
+            ),
+            SaveError::Migration { from, message } => {
+                write!(f, "couldn't migrate save from version {from}: {message}")
+            }
+            SaveError::Mismatch(message) => write!(f, "save doesn't match the level, {message}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TriggerState {
+    pub inside: bool,
+    pub fired: bool,
+}
+
+/// Value of a script variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+/// Snapshot of a game in progress.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveGame {
+    pub width: usize,
+    pub height: usize,
+    /// Head bob isn't saved, `z` is always 0.
+    pub camera: Camera,
+    /// `(x, y, cell)` of the cells that differ from the level, door cells aside.
+    pub tiles: Vec<(usize, usize, i8)>,
+    /// `(cell, open)` of every door.
+    pub doors: Vec<(i8, bool)>,
+    pub entities: Vec<Entity>,
+    pub triggers: Vec<TriggerState>,
+    /// Game defined timers in seconds.
+    pub timers: BTreeMap<String, f64>,
+    /// Variables of the level's scripts.
+    pub vars: BTreeMap<String, Value>,
+}
+
+impl SaveGame {
+    /// Captures `map`, which started out as `level`. Fails if the save couldn't be parsed again: entity kinds,
+    /// timer and variable names and text values have to be non-empty and free of spaces and `#`.
+    pub fn capture(
+        level: &Map,
+        map: &Map,
+        camera: &Camera,
+        triggers: &Triggers,
+        timers: BTreeMap<String, f64>,
+        vars: BTreeMap<String, Value>,
+    ) -> Result<Self, String> {
+        if let Some(entity) = map.entities.iter().find(|entity| !is_word(&entity.kind)) {
+            return Err(format!("the entity kind `{}` can't be saved", entity.kind));
+        }
+        if let Some(name) = timers.keys().find(|name| !is_word(name)) {
+            return Err(format!("the timer `{name}` can't be saved"));
+        }
+        let text = |value: &Value| matches!(value, Value::Text(text) if !is_word(text));
+        if let Some(name) = vars
+            .iter()
+            .find_map(|(name, value)| (!is_word(name) || text(value)).then_some(name))
+        {
+            return Err(format!("the variable `{name}` can't be saved"));
+        }
+        let mut tiles = Vec::new();
+        for y in 0..map.height() {
+            for x in 0..map.width() {
+                let (x32, y32) = (x as i32, y as i32);
+                let cell = map.cell(x32, y32);
+                if map.door_at(x32, y32).is_none() && level.get(x32, y32) != Some(cell) {
+                    tiles.push((x, y, cell));
+                }
+            }
+        }
+        Ok(Self {
+            width: map.width(),
+            height: map.height(),
+            camera: Camera::new(camera.pos, camera.yaw, camera.fov),
+            tiles,
+            doors: map
+                .doors()
+                .iter()
+                .map(|door| (door.cell, door.open))
+                .collect(),
+            entities: map.entities.clone(),
+            triggers: (0..map.triggers.len())
+                .map(|index| TriggerState {
+                    inside: triggers.is_inside(index),
+                    fired: triggers.has_fired(index),
+                })
+                .collect(),
+            timers,
+            vars,
+        })
+    }
+
+    /// `level` with the saved changes applied and the state of its triggers.
+    pub fn restore(&self, level: &Map) -> Result<(Map, Triggers), SaveError> {
+        if (self.width, self.height) != (level.width(), level.height()) {
+            return Err(SaveError::Mismatch(format!(
+                "the save is {}x{}, the level {}x{}",
+                self.width,
+                self.height,
+                level.width(),
+                level.height()
+            )));
+        }
+        if self.doors.len() != level.doors().len() {
+            return Err(SaveError::Mismatch(format!(
+                "the save has {} doors, the level {}",
+                self.doors.len(),
+                level.doors().len()
+            )));
+        }
+        if self.triggers.len() > level.triggers.len() {
+            return Err(SaveError::Mismatch(format!(
+                "the save has {} triggers, the level {}",
+                self.triggers.len(),
+                level.triggers.len()
+            )));
+        }
+
+        let mut map = level.clone();
+        for &(x, y, cell) in &self.tiles {
+            if x >= map.width() || y >= map.height() {
+                return Err(SaveError::Mismatch(format!(
+                    "tile ({x}, {y}) is outside of the map"
+                )));
+            }
+            map.set(x, y, cell);
+        }
+        for (index, &(cell, open)) in self.doors.iter().enumerate() {
+            map.set_door_cell(index, cell);
+            map.set_door_open(index, open);
+        }
+        map.entities = self.entities.clone();
+
+        let mut triggers = Triggers::new();
+        for (index, state) in self.triggers.iter().enumerate() {
+            triggers.set_state(index, state.inside, state.fired);
+        }
+        Ok((map, triggers))
+    }
+
+    pub fn parse(src: &str) -> Result<Self, SaveError> {
+        let mut lines = src.lines();
+        let header = lines.next().unwrap_or_default();
+        let version = match header.split_whitespace().collect::<Vec<_>>()[..] {
+            [HEADER, version] => number::<u32>(version).ok().filter(|&v| v > 0),
+            _ => None,
+        }
+        .ok_or_else(|| SaveError::Parse {
+            line: 1,
+            message: format!("expected `{HEADER} VERSION`, got `{header}`"),
+        })?;
+        if version > VERSION {
+            return Err(SaveError::Version(version));
+        }
+        let lines = migrate(lines.map(str::to_string).collect(), version, &MIGRATIONS)?;
+
+        let mut size = None;
+        let mut camera = None;
+        let mut save = SaveGame {
+            width: 0,
+            height: 0,
+            camera: Camera::new(Vec2::default(), 0., 0.),
+            tiles: Vec::new(),
+            doors: Vec::new(),
+            entities: Vec::new(),
+            triggers: Vec::new(),
+            timers: BTreeMap::new(),
+            vars: BTreeMap::new(),
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            // The header is line 1
+            let error = |message: String| SaveError::Parse {
+                line: i + 2,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<_> = line.split_whitespace().collect();
+            let (keyword, words) = (words[0], &words[1..]);
+            match keyword {
+                "size" => {
+                    let [width, height] = arguments(keyword, words).map_err(error)?;
+                    size = Some((
+                        number(width).map_err(error)?,
+                        number(height).map_err(error)?,
+                    ));
+                }
+                "camera" => {
+                    let [x, y, yaw, fov] = arguments(keyword, words).map_err(error)?;
+                    camera = Some(Camera::new(
+                        Vec2::new(number(x).map_err(error)?, number(y).map_err(error)?),
+                        number(yaw).map_err(error)?,
+                        number(fov).map_err(error)?,
+                    ));
+                }
+                "tile" => {
+                    let [x, y, cell] = arguments(keyword, words).map_err(error)?;
+                    save.tiles.push((
+                        number(x).map_err(error)?,
+                        number(y).map_err(error)?,
+                        number(cell).map_err(error)?,
+                    ));
+                }
+                "door" => {
+                    let [index, cell, state] = arguments(keyword, words).map_err(error)?;
+                    let index: usize = number(index).map_err(error)?;
+                    if index != save.doors.len() {
+                        return Err(error(format!(
+                            "expected door {}, got {index}",
+                            save.doors.len()
+                        )));
+                    }
+                    let cell: i8 = number(cell).map_err(error)?;
+                    if cell <= 0 {
+                        return Err(error(format!("doors need a wall cell, got {cell}")));
+                    }
+                    let open = match state {
+                        "open" => true,
+                        "closed" => false,
+                        _ => {
+                            return Err(error(format!(
+                                "expected `open` or `closed`, got `{state}`"
+                            )))
+                        }
+                    };
+                    save.doors.push((cell, open));
+                }
+                "entity" => {
+                    let [kind, x, y, radius] = arguments(keyword, words).map_err(error)?;
+                    save.entities.push(Entity {
+                        kind: kind.to_string(),
+                        pos: Vec2::new(number(x).map_err(error)?, number(y).map_err(error)?),
+                        radius: number(radius).map_err(error)?,
+                    });
+                }
+                "trigger" => {
+                    let [index, fired, inside] = arguments(keyword, words).map_err(error)?;
+                    let index: usize = number(index).map_err(error)?;
+                    if index != save.triggers.len() {
+                        return Err(error(format!(
+                            "expected trigger {}, got {index}",
+                            save.triggers.len()
+                        )));
+                    }
+                    let fired = match fired {
+                        "fired" => true,
+                        "unfired" => false,
+                        _ => {
+                            return Err(error(format!(
+                                "expected `fired` or `unfired`, got `{fired}`"
+                            )))
+                        }
+                    };
+                    let inside = match inside {
+                        "inside" => true,
+                        "outside" => false,
+                        _ => {
+                            return Err(error(format!(
+                                "expected `inside` or `outside`, got `{inside}`"
+                            )))
+                        }
+                    };
+                    save.triggers.push(TriggerState { inside, fired });
+                }
+                "timer" => {
+                    let [name, seconds] = arguments(keyword, words).map_err(error)?;
+                    save.timers
+                        .insert(name.to_string(), number(seconds).map_err(error)?);
+                }
+                "var" => {
+                    let [name, kind, value] = arguments(keyword, words).map_err(error)?;
+                    let value = match kind {
+                        "bool" => Value::Bool(match value {
+                            "true" => true,
+                            "false" => false,
+                            _ => {
+                                return Err(error(format!(
+                                    "expected `true` or `false`, got `{value}`"
+                                )))
+                            }
+                        }),
+                        "int" => Value::Int(number(value).map_err(error)?),
+                        "float" => Value::Float(number(value).map_err(error)?),
+                        "text" => Value::Text(value.to_string()),
+                        _ => return Err(error(format!("unknown variable type `{kind}`"))),
+                    };
+                    save.vars.insert(name.to_string(), value);
+                }
+                _ => return Err(error(format!("unknown statement `{keyword}`"))),
+            }
+        }
+
+        let error = |message: &str| SaveError::Parse {
+            line: lines.len() + 1,
+            message: message.to_string(),
+        };
+        (save.width, save.height) = size.ok_or_else(|| error("missing `size`"))?;
+        save.camera = camera.ok_or_else(|| error("missing `camera`"))?;
+        Ok(save)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SaveError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for SaveGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER} {VERSION}")?;
+        writeln!(f, "size {} {}", self.width, self.height)?;
+        let Camera { pos, yaw, fov, .. } = self.camera;
+        writeln!(f, "camera {} {} {yaw} {fov}", pos.x, pos.y)?;
+        for (x, y, cell) in &self.tiles {
+            writeln!(f, "tile {x} {y} {cell}")?;
+        }
+        for (index, (cell, open)) in self.doors.iter().enumerate() {
+            let state = if *open { "open" } else { "closed" };
+            writeln!(f, "door {index} {cell} {state}")?;
+        }
+        for entity in &self.entities {
+            writeln!(
+                f,
+                "entity {} {} {} {}",
+                entity.kind, entity.pos.x, entity.pos.y, entity.radius
+            )?;
+        }
+        for (index, state) in self.triggers.iter().enumerate() {
+            let fired = if state.fired { "fired" } else { "unfired" };
+            let inside = if state.inside { "inside" } else { "outside" };
+            writeln!(f, "trigger {index} {fired} {inside}")?;
+        }
+        for (name, seconds) in &self.timers {
+            writeln!(f, "timer {name} {seconds}")?;
+        }
+        for (name, value) in &self.vars {
+            match value {
+                Value::Bool(value) => writeln!(f, "var {name} bool {value}")?,
+                Value::Int(value) => writeln!(f, "var {name} int {value}")?,
+                Value::Float(value) => writeln!(f, "var {name} float {value}")?,
+                Value::Text(value) => writeln!(f, "var {name} text {value}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trigger::{Area, Condition, Mode, Trigger};
+
+    fn level() -> Map {
+        let mut map = Map::new(6, 3);
+        for x in 0..6 {
+            map.set(x, 0, 1);
+            map.set(x, 2, 1);
+        }
+        map.add_door(3, 1, 2);
+        map.entities.push(Entity {
+            kind: "barrel".to_string(),
+            pos: Vec2::new(1.5, 1.5),
+            radius: 0.3,
+        });
+        map.triggers.push(Trigger {
+            name: "hall".to_string(),
+            area: Area::cell(4, 1),
+            mode: Mode::Once,
+            condition: Condition::Always,
+            action: "end_level".to_string(),
+        });
+        map
+    }
+
+    #[test]
+    fn capture_and_restore() {
+        let level = level();
+        let mut map = level.clone();
+        map.set_door_open(0, true);
+        map.set(1, 0, -3);
+        map.entities[0].pos = Vec2::new(2.5, 1.5);
+        let mut triggers = Triggers::new();
+        triggers.enter(&map, 4, 1);
+        triggers.drain().for_each(drop);
+        let mut camera = Camera::new(Vec2::new(4.5, 1.5), 0.5, 1.2);
+        camera.z = 0.1;
+        let timers = BTreeMap::from([("play_time".to_string(), 12.25)]);
+        let vars = BTreeMap::from([
+            ("seen".to_string(), Value::Bool(true)),
+            ("visits".to_string(), Value::Int(-3)),
+            ("speed".to_string(), Value::Float(0.1)),
+            ("name".to_string(), Value::Text("bob".to_string())),
+        ]);
+
+        let save = SaveGame::capture(
+            &level,
+            &map,
+            &camera,
+            &triggers,
+            timers.clone(),
+            vars.clone(),
+        )
+        .unwrap();
+        assert_eq!(save.tiles, [(1, 0, -3)]);
+        let save = SaveGame::parse(&save.to_string()).unwrap();
+        assert_eq!(save.camera, Camera::new(camera.pos, camera.yaw, camera.fov));
+        assert_eq!(save.timers["play_time"], 12.25);
+        assert_eq!(save.vars, vars);
+
+        let (restored, restored_triggers) = save.restore(&level).unwrap();
+        assert_eq!(restored, map);
+        assert!(restored_triggers.has_fired(0) && restored_triggers.is_inside(0));
+        assert!(save.restore(&Map::new(6, 3)).is_err());
+
+        // Nothing is captured that the save couldn't parse again
+        let capture = |map: &Map, timers, vars| {
+            SaveGame::capture(&level, map, &camera, &triggers, timers, vars)
+        };
+        let mut spaced = map.clone();
+        spaced.entities[0].kind = "bad kind".to_string();
+        assert!(capture(&spaced, timers.clone(), vars.clone()).is_err());
+        spaced.entities[0].kind = String::new();
+        assert!(capture(&spaced, timers.clone(), vars.clone()).is_err());
+        let spaced_timer = BTreeMap::from([("play time".to_string(), 1.)]);
+        assert!(capture(&map, spaced_timer, vars.clone()).is_err());
+        for (name, value) in [
+            ("#", Value::Int(1)),
+            ("name", Value::Text("bob smith".to_string())),
+        ] {
+            let vars = BTreeMap::from([(name.to_string(), value)]);
+            assert!(capture(&map, timers.clone(), vars).is_err());
+        }
+    }
+
+    #[test]
+    fn migrations() {
+        // A fake format whose version 2 renamed `wall` to `tile` and version 3 dropped `old`
+        fn rename(lines: Vec<String>) -> Result<Vec<String>, String> {
+            Ok(lines
+                .iter()
+                .map(|line| line.replace("wall", "tile"))
+                .collect())
+        }
+        fn drop_old(lines: Vec<String>) -> Result<Vec<String>, String> {
+            if lines.iter().any(|line| line == "broken") {
+                return Err("broken line".to_string());
+            }
+            Ok(lines.into_iter().filter(|line| line != "old").collect())
+        }
+        let steps: [Migration; 2] = [rename, drop_old];
+        let lines = |lines: &[&str]| {
+            lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let v1 = lines(&["wall 1 2 3", "old"]);
+        assert_eq!(migrate(v1.clone(), 1, &steps).unwrap(), ["tile 1 2 3"]);
+        assert_eq!(
+            migrate(v1.clone(), 1, &steps[..1]).unwrap(),
+            ["tile 1 2 3", "old"]
+        );
+        assert_eq!(migrate(v1.clone(), 2, &steps).unwrap(), ["wall 1 2 3"]);
+        assert_eq!(migrate(v1.clone(), 3, &steps).unwrap(), v1);
+        assert!(matches!(
+            migrate(lines(&["broken"]), 1, &steps),
+            Err(SaveError::Migration { from: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn versions() {
+        let src = "rayguy-save 1\nsize 2 2\ncamera 1 1 0 1\ntimer play_time 3\n";
+        assert_eq!(SaveGame::parse(src).unwrap().timers["play_time"], 3.);
+        assert!(matches!(
+            SaveGame::parse("rayguy-save 3\nsize 2 2\ncamera 1 1 0 1\n"),
+            Err(SaveError::Version(3))
+        ));
+        for src in ["size 2 2\n", "rayguy-save 0\n", ""] {
+            assert!(matches!(
+                SaveGame::parse(src),
+                Err(SaveError::Parse { line: 1, .. })
+            ));
+        }
+        assert!(matches!(
+            SaveGame::parse("rayguy-save 1\nsize 2 2\n"),
+            Err(SaveError::Parse { line: 2, .. })
+        ));
+        // Version 1 saves are migrated
+        let v1 = "rayguy-save 1\nsize 2 2\ncamera 1 1 0 1\n";
+        assert!(SaveGame::parse(v1).unwrap().vars.is_empty());
+        for var in ["var a", "var a int 1.5", "var a bool yes", "var a list x"] {
+            let src = format!("rayguy-save 2\nsize 2 2\n{var}\ncamera 1 1 0 1\n");
+            assert!(
+                matches!(SaveGame::parse(&src), Err(SaveError::Parse { line: 3, .. })),
+                "{var}"
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::framebuffer::Framebuffer;
+
+/// How the internal resolution is fit into the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleMode {
+    /// Largest whole multiple of the internal resolution that fits, so every pixel has the same size.
+    /// Falls back to `Letterbox` when the window is smaller than the internal resolution.
+    Integer,
+    /// As large as possible while keeping the aspect ratio, with black bars on the sides.
+    #[default]
+    Letterbox,
+    /// Fill the whole window, distorting the image if the aspect ratios differ.
+    Stretch,
+}
+
+impl ScaleMode {
+    pub fn next(self) -> Self {
+        match self {
+            ScaleMode::Integer => ScaleMode::Letterbox,
+            ScaleMode::Letterbox => ScaleMode::Stretch,
+            ScaleMode::Stretch => ScaleMode::Integer,
+        }
+    }
+
+    /// Where a `source` sized image ends up in a `target` sized window.
+    pub fn viewport(self, source: (usize, usize), target: (usize, usize)) -> Viewport {
+        let (source_width, source_height) = source;
+        let (target_width, target_height) = target;
+        if source_width == 0 || source_height == 0 {
+            return Viewport::default();
+        }
+
+        let (width, height) = match self {
+            ScaleMode::Stretch => target,
+            ScaleMode::Integer
+                if target_width >= source_width && target_height >= source_height =>
+            {
+                let scale = (target_width / source_width).min(target_height / source_height);
+                (source_width * scale, source_height * scale)
+            }
+            ScaleMode::Integer | ScaleMode::Letterbox => {
+                // Compare target_width / target_height with source_width / source_height without dividing
+                if target_width * source_height > target_height * source_width {
+                    (source_width * target_height / source_height, target_height)
+                } else {
+                    (target_width, source_height * target_width / source_width)
+                }
+            }
+        };
+
+        Viewport {
+            x: (target_width - width) / 2,
+            y: (target_height - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+/// Rectangle of the window the image is drawn to, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    /// Maps a window position to a position on a `source` sized image, `None` if it's outside of the viewport.
+    pub fn to_source(&self, source: (usize, usize), x: usize, y: usize) -> Option<(usize, usize)> {
+        if x < self.x || y < self.y || x >= self.x + self.width || y >= self.y + self.height {
+            return None;
+        }
+        Some((
+            (x - self.x) * source.0 / self.width,
+            (y - self.y) * source.1 / self.height,
+        ))
+    }
+
+    /// Width divided by height of a `source` pixel once it's scaled into this viewport, 1 unless the image is
+    /// stretched. The renderer uses this to undo the distortion.
+    pub fn pixel_aspect(&self, source: (usize, usize)) -> f64 {
+        if self.width == 0 || self.height == 0 {
+            return 1.;
+        }
+        (self.width as f64 / source.0 as f64) / (self.height as f64 / source.1 as f64)
+    }
+}
+
+/// Copies `framebuffer` into a `width` x `height` window buffer with nearest neighbour scaling.
+/// Everything outside of the viewport is cleared to black.
+pub fn present(
+    framebuffer: &Framebuffer,
+    mode: ScaleMode,
+    target: &mut [u32],
+    width: usize,
+    height: usize,
+) {
+    if width == 0 {
+        return;
+    }
+    let source = (framebuffer.width(), framebuffer.height());
+    let viewport = mode.viewport(source, (width, height));
+
+    // Which source column every target column of the viewport shows, computed once instead of per pixel
+    let columns: Vec<usize> = (0..viewport.width)
+        .map(|x| x * source.0 / viewport.width)
+        .collect();
+
+    for (y, row) in target.chunks_exact_mut(width).take(height).enumerate() {
+        if y < viewport.y || y >= viewport.y + viewport.height {
+            row.fill(0);
+            continue;
+        }
+        let source_y = (y - viewport.y) * source.1 / viewport.height;
+        let source_row = &framebuffer.pixels()[source_y * source.0..(source_y + 1) * source.0];
+
+        row[..viewport.x].fill(0);
+        for (pixel, source_x) in row[viewport.x..viewport.x + viewport.width]
+            .iter_mut()
+            .zip(&columns)
+        {
+            *pixel = source_row[*source_x];
+        }
+        row[viewport.x + viewport.width..].fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: (usize, usize) = (160, 144);
+    const TARGETS: [(usize, usize); 8] = [
+        (0, 0),
+        (0, 100),
+        (100, 0),
+        (1, 1),
+        (100, 100),
+        (160, 144),
+        (333, 222),
+        (700, 600),
+    ];
+
+    fn viewport(x: usize, y: usize, width: usize, height: usize) -> Viewport {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn viewports() {
+        use ScaleMode::*;
+        for (mode, target, expected) in [
+            (Integer, (640, 576), viewport(0, 0, 640, 576)),
+            (Integer, (700, 600), viewport(30, 12, 640, 576)),
+            // Smaller than the source falls back to letterboxing
+            (Integer, (100, 100), viewport(0, 5, 100, 90)),
+            (Integer, (0, 0), viewport(0, 0, 0, 0)),
+            (Letterbox, (800, 576), viewport(80, 0, 640, 576)),
+            (Letterbox, (320, 400), viewport(0, 56, 320, 288)),
+            (Letterbox, (100, 0), viewport(50, 0, 0, 0)),
+            (Letterbox, (0, 0), viewport(0, 0, 0, 0)),
+            (Stretch, (300, 100), viewport(0, 0, 300, 100)),
+            (Stretch, (0, 0), viewport(0, 0, 0, 0)),
+        ] {
+            assert_eq!(
+                mode.viewport(SOURCE, target),
+                expected,
+                "{mode:?} {target:?}"
+            );
+        }
+        assert_eq!(
+            Letterbox.viewport((0, 144), (640, 480)),
+            Viewport::default()
+        );
+        assert_eq!(
+            Stretch.viewport(SOURCE, (300, 100)).pixel_aspect(SOURCE),
+            (300. / 160.) / (100. / 144.)
+        );
+    }
+
+    #[test]
+    fn to_source_inverts_upscaling() {
+        for mode in [ScaleMode::Integer, ScaleMode::Letterbox, ScaleMode::Stretch] {
+            for target in [(333, 222), (700, 600), (1920, 1080)] {
+                let v = mode.viewport(SOURCE, target);
+                assert_eq!(v.to_source(SOURCE, v.x + v.width, v.y), None);
+                if v.x > 0 {
+                    assert_eq!(v.to_source(SOURCE, v.x - 1, v.y), None);
+                }
+                // The first window pixel showing every source pixel maps back to it
+                for source_x in 0..SOURCE.0 {
+                    let x = v.x + (source_x * v.width).div_ceil(SOURCE.0);
+                    assert_eq!(v.to_source(SOURCE, x, v.y), Some((source_x, 0)));
+                }
+                for source_y in 0..SOURCE.1 {
+                    let y = v.y + (source_y * v.height).div_ceil(SOURCE.1);
+                    assert_eq!(v.to_source(SOURCE, v.x, y), Some((0, source_y)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn present_matches_to_source() {
+        let mut framebuffer = Framebuffer::new(SOURCE.0, SOURCE.1);
+        for y in 0..SOURCE.1 {
+            for x in 0..SOURCE.0 {
+                framebuffer.set(x, y, (y * SOURCE.0 + x + 1) as u32);
+            }
+        }
+        for mode in [ScaleMode::Integer, ScaleMode::Letterbox, ScaleMode::Stretch] {
+            for (width, height) in TARGETS {
+                let mut target = vec![u32::MAX; width * height];
+                present(&framebuffer, mode, &mut target, width, height);
+                let viewport = mode.viewport(SOURCE, (width, height));
+                for (i, pixel) in target.iter().enumerate() {
+                    let expected = viewport
+                        .to_source(SOURCE, i % width, i / width)
+                        .map_or(0, |(x, y)| framebuffer.get(x, y));
+                    assert_eq!(*pixel, expected, "{mode:?} {width}x{height} pixel {i}");
+                }
+            }
+        }
+    }
+}
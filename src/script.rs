@@ -0,0 +1,563 @@
+//! Level scripts written in [Rhai](https://rhai.rs), enabled with the `scripting` feature.
+//!
+//! Levels list their scripts with `script PATH`, see [`crate::level`]. The top level of every script runs when
+//! the level starts, after that the game calls these functions when a script defines them:
+//! - `on_trigger(name, action)` when a trigger fires, with the trigger's name and action
+//! - `on_tick(dt)` every simulation tick, `dt` in seconds
+//!
+//! Scripts only see the level through the functions below and can't load files or modules. Every call runs a
+//! limited number of operations, so an endless loop ends with an error instead of freezing the game.
+//!
+//! | Function | |
+//! |---|---|
+//! | `message(text)`, `print(text)` | Shows text on the HUD |
+//! | `tile(x, y)`, `set_tile(x, y, cell)` | Cells with the same values as in level files, reading outside of the map follows its out of bounds policy |
+//! | `door_count()`, `is_door_open(door)`, `open_door(door)`, `close_door(door)` | |
+//! | `entity_count()`, `entity_kind(entity)`, `entity_x(entity)`, `entity_y(entity)` | |
+//! | `spawn_entity(kind, x, y, radius)` | Adds an entity and returns its index, kinds can't be empty or contain spaces or `#` |
+//! | `move_entity(entity, x, y)`, `remove_entity(entity)` | Removing shifts the entities after it down by one |
+//! | `player_x()`, `player_y()` | |
+//! | `set_var(name, value)`, `get_var(name)` | Values kept between calls and shared by all scripts, `()` if not set |
+//!
+//! Positions are floats, write `3.0` instead of `3`. Variables are saved with the game, which only works while
+//! they hold booleans, numbers or strings without spaces.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope,
+    AST, FLOAT, INT,
+};
+
+use crate::{
+    level::is_word,
+    map::{Entity, Map},
+    math::Vec2,
+    save::Value,
+};
+
+// Limits of a single call into a script
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 4096;
+const MAX_COLLECTION_SIZE: usize = 4096;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { script: String, message: String },
+    Runtime { script: String, message: String },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io { path, error } => {
+                write!(f, "couldn't read script {}: {error}", path.display())
+            }
+            ScriptError::Parse { script, message } => write!(f, "{script}: {message}"),
+            ScriptError::Runtime { script, message } => write!(f, "{script}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// What the API functions work on. The map is only here while a script runs.
+#[derive(Default)]
+struct State {
+    map: Option<Map>,
+    player: Vec2,
+    messages: Vec<String>,
+    vars: HashMap<String, Dynamic>,
+}
+
+fn with_map<T>(
+    state: &RefCell<State>,
+    f: impl FnOnce(&mut Map) -> ScriptResult<T>,
+) -> ScriptResult<T> {
+    match state.borrow_mut().map.as_mut() {
+        Some(map) => f(map),
+        None => Err("the level isn't loaded".into()),
+    }
+}
+
+// Checks an index from a script against the length of what it indexes
+fn index(value: INT, len: usize, what: &str) -> ScriptResult<usize> {
+    usize::try_from(value)
+        .ok()
+        .filter(|i| *i < len)
+        .ok_or_else(|| format!("{what} {value} doesn't exist").into())
+}
+
+fn coordinate(value: INT) -> i32 {
+    value.clamp(i32::MIN as INT, i32::MAX as INT) as i32
+}
+
+/// The scripts of a level, with the engine that runs them.
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<(String, AST)>,
+    state: Rc<RefCell<State>>,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scripts {
+    /// No scripts yet, with an engine restricted to the API in the module docs.
+    pub fn new() -> Self {
+        let state = Rc::new(RefCell::new(State::default()));
+        let mut engine = Engine::new();
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE);
+
+        let s = state.clone();
+        engine.on_print(move |text| s.borrow_mut().messages.push(text.to_string()));
+        let s = state.clone();
+        engine.register_fn("message", move |text: &str| {
+            s.borrow_mut().messages.push(text.to_string())
+        });
+
+        let s = state.clone();
+        engine.register_fn("tile", move |x: INT, y: INT| {
+            with_map(&s, |map| Ok(map.cell(coordinate(x), coordinate(y)) as INT))
+        });
+        let s = state.clone();
+        engine.register_fn("set_tile", move |x: INT, y: INT, cell: INT| {
+            with_map(&s, |map| {
+                let x = index(x, map.width(), "column")?;
+                let y = index(y, map.height(), "row")?;
+                let cell = i8::try_from(cell).map_err(|_| format!("{cell} isn't a valid cell"))?;
+                map.set(x, y, cell);
+                Ok(())
+            })
+        });
+
+        let s = state.clone();
+        engine.register_fn("door_count", move || {
+            with_map(&s, |map| Ok(map.doors().len() as INT))
+        });
+        let s = state.clone();
+        engine.register_fn("is_door_open", move |door: INT| {
+            with_map(&s, |map| {
+                Ok(map.doors()[index(door, map.doors().len(), "door")?].open)
+            })
+        });
+        for (name, open) in [("open_door", true), ("close_door", false)] {
+            let s = state.clone();
+            engine.register_fn(name, move |door: INT| {
+                with_map(&s, |map| {
+                    map.set_door_open(index(door, map.doors().len(), "door")?, open);
+                    Ok(())
+                })
+            });
+        }
+
+        let s = state.clone();
+        engine.register_fn("entity_count", move || {
+            with_map(&s, |map| Ok(map.entities.len() as INT))
+        });
+        let s = state.clone();
+        engine.register_fn("entity_kind", move |entity: INT| {
+            with_map(&s, |map| {
+                Ok(map.entities[index(entity, map.entities.len(), "entity")?]
+                    .kind
+                    .clone())
+            })
+        });
+        let s = state.clone();
+        engine.register_fn("entity_x", move |entity: INT| {
+            with_map(&s, |map| {
+                Ok(map.entities[index(entity, map.entities.len(), "entity")?]
+                    .pos
+                    .x)
+            })
+        });
+        let s = state.clone();
+        engine.register_fn("entity_y", move |entity: INT| {
+            with_map(&s, |map| {
+                Ok(map.entities[index(entity, map.entities.len(), "entity")?]
+                    .pos
+                    .y)
+            })
+        });
+        let s = state.clone();
+        engine.register_fn(
+            "spawn_entity",
+            move |kind: &str, x: FLOAT, y: FLOAT, radius: FLOAT| {
+                with_map(&s, |map| {
+                    if !is_word(kind) {
+                        return Err(format!("can't spawn an entity of kind `{kind}`").into());
+                    }
+                    map.entities.push(Entity {
+                        kind: kind.to_string(),
+                        pos: Vec2::new(x, y),
+                        radius,
+                    });
+                    Ok(map.entities.len() as INT - 1)
+                })
+            },
+        );
+        let s = state.clone();
+        engine.register_fn("move_entity", move |entity: INT, x: FLOAT, y: FLOAT| {
+            with_map(&s, |map| {
+                let entity = index(entity, map.entities.len(), "entity")?;
+                map.entities[entity].pos = Vec2::new(x, y);
+                Ok(())
+            })
+        });
+        let s = state.clone();
+        engine.register_fn("remove_entity", move |entity: INT| {
+            with_map(&s, |map| {
+                map.entities
+                    .remove(index(entity, map.entities.len(), "entity")?);
+                Ok(())
+            })
+        });
+
+        let s = state.clone();
+        engine.register_fn("player_x", move || s.borrow().player.x);
+        let s = state.clone();
+        engine.register_fn("player_y", move || s.borrow().player.y);
+
+        let s = state.clone();
+        engine.register_fn("set_var", move |name: &str, value: Dynamic| {
+            s.borrow_mut().vars.insert(name.to_string(), value);
+        });
+        let s = state.clone();
+        engine.register_fn("get_var", move |name: &str| {
+            s.borrow().vars.get(name).cloned().unwrap_or(Dynamic::UNIT)
+        });
+
+        Self {
+            engine,
+            scripts: Vec::new(),
+            state,
+        }
+    }
+
+    /// Loads the scripts of `map`, whose level file is in `dir`.
+    pub fn for_map(map: &Map, dir: impl AsRef<Path>) -> Result<Self, ScriptError> {
+        let mut scripts = Self::new();
+        for script in &map.scripts {
+            scripts.load(dir.as_ref().join(script))?;
+        }
+        Ok(scripts)
+    }
+
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), ScriptError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path).map_err(|error| ScriptError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        self.add(path.display().to_string(), &src)
+    }
+
+    /// Compiles `src`, `name` is used in errors.
+    pub fn add(&mut self, name: impl Into<String>, src: &str) -> Result<(), ScriptError> {
+        let name = name.into();
+        match self.engine.compile(src) {
+            Ok(ast) => {
+                self.scripts.push((name, ast));
+                Ok(())
+            }
+            Err(e) => Err(ScriptError::Parse {
+                script: name,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Runs the top level of every script.
+    pub fn start(&mut self, map: &mut Map, player: Vec2) -> Result<(), ScriptError> {
+        self.run(map, player, |engine, ast| {
+            engine.run_ast_with_scope(&mut Scope::new(), ast)
+        })
+    }
+
+    /// Calls `on_trigger` for the trigger at `index` in [`Map::triggers`].
+    pub fn trigger(
+        &mut self,
+        map: &mut Map,
+        player: Vec2,
+        index: usize,
+    ) -> Result<(), ScriptError> {
+        let trigger = &map.triggers[index];
+        let args = vec![trigger.name.clone().into(), trigger.action.clone().into()];
+        self.call(map, player, "on_trigger", args)
+    }
+
+    /// Calls `on_tick`.
+    pub fn tick(&mut self, map: &mut Map, player: Vec2, dt: f64) -> Result<(), ScriptError> {
+        self.call(map, player, "on_tick", vec![dt.into()])
+    }
+
+    /// Variables set with `set_var`, for saving. Fails if a save can't hold one of them: values other than
+    /// booleans, integers, floats and strings, and names or strings that are empty or contain spaces or `#`.
+    pub fn vars(&self) -> Result<BTreeMap<String, Value>, String> {
+        let mut vars = BTreeMap::new();
+        for (name, value) in &self.state.borrow().vars {
+            let saved = if let Ok(value) = value.as_bool() {
+                Some(Value::Bool(value))
+            } else if let Ok(value) = value.as_int() {
+                Some(Value::Int(value))
+            } else if let Ok(value) = value.as_float() {
+                Some(Value::Float(value))
+            } else {
+                value
+                    .clone()
+                    .into_string()
+                    .ok()
+                    .filter(|text| is_word(text))
+                    .map(Value::Text)
+            };
+            match saved {
+                Some(value) if is_word(name) => {
+                    vars.insert(name.clone(), value);
+                }
+                _ => {
+                    return Err(format!(
+                        "the script variable `{name}` ({}) can't be saved",
+                        value.type_name()
+                    ))
+                }
+            }
+        }
+        Ok(vars)
+    }
+
+    /// Replaces the variables with saved ones.
+    pub fn set_vars(&mut self, vars: &BTreeMap<String, Value>) {
+        self.state.borrow_mut().vars = vars
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Bool(value) => Dynamic::from_bool(*value),
+                    Value::Int(value) => Dynamic::from_int(*value),
+                    Value::Float(value) => Dynamic::from_float(*value),
+                    Value::Text(value) => value.clone().into(),
+                };
+                (name.clone(), value)
+            })
+            .collect();
+    }
+
+    /// Messages printed by scripts since the last call, oldest first.
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.state.borrow_mut().messages)
+    }
+
+    // Calls a function in every script that defines it
+    fn call(
+        &mut self,
+        map: &mut Map,
+        player: Vec2,
+        function: &str,
+        args: Vec<Dynamic>,
+    ) -> Result<(), ScriptError> {
+        self.run(map, player, |engine, ast| {
+            let defined = ast
+                .iter_functions()
+                .any(|f| f.name == function && f.params.len() == args.len());
+            if !defined {
+                return Ok(());
+            }
+            // The top level already ran when the level started
+            let options = CallFnOptions::new().eval_ast(false);
+            engine
+                .call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut Scope::new(),
+                    ast,
+                    function,
+                    args.clone(),
+                )
+                .map(drop)
+        })
+    }
+
+    // Lends the map to the API while `f` runs for every script, stops at the first error
+    fn run(
+        &mut self,
+        map: &mut Map,
+        player: Vec2,
+        mut f: impl FnMut(&Engine, &AST) -> ScriptResult<()>,
+    ) -> Result<(), ScriptError> {
+        {
+            let mut state = self.state.borrow_mut();
+            state.map = Some(std::mem::replace(map, Map::new(0, 0)));
+            state.player = player;
+        }
+        let result = self.scripts.iter().try_for_each(|(name, ast)| {
+            f(&self.engine, ast).map_err(|e| ScriptError::Runtime {
+                script: name.clone(),
+                message: e.to_string(),
+            })
+        });
+        *map = self.state.borrow_mut().map.take().unwrap();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trigger::{Area, Condition, Mode, Trigger};
+
+    // 6x3 room with a door at (3, 1) and a trigger at (1, 1)
+    fn level() -> Map {
+        let mut map = Map::new(6, 3);
+        map.set(3, 0, 1);
+        map.set(3, 2, 1);
+        map.add_door(3, 1, 2);
+        map.triggers.push(Trigger {
+            name: "switch".to_string(),
+            area: Area::cell(1, 1),
+            mode: Mode::Once,
+            condition: Condition::Always,
+            action: "open the vault".to_string(),
+        });
+        map
+    }
+
+    fn scripts(src: &str) -> Scripts {
+        let mut scripts = Scripts::new();
+        scripts.add("test", src).unwrap();
+        scripts
+    }
+
+    #[test]
+    fn reacts_to_triggers() {
+        let mut map = level();
+        let mut scripts = scripts(
+            r#"
+            message("Find the switch");
+            fn on_trigger(name, action) {
+                if name == "switch" {
+                    open_door(0);
+                    set_tile(5, 2, 3);
+                    print(action);
+                }
+            }
+            "#,
+        );
+        scripts.start(&mut map, Vec2::ZERO).unwrap();
+        assert_eq!(scripts.take_messages(), ["Find the switch"]);
+        assert!(!map.doors()[0].open);
+
+        scripts.trigger(&mut map, Vec2::ZERO, 0).unwrap();
+        assert!(map.doors()[0].open);
+        assert_eq!(map.get(5, 2), Some(3));
+        assert_eq!(scripts.take_messages(), ["open the vault"]);
+    }
+
+    #[test]
+    fn moves_entities() {
+        let mut map = level();
+        let mut scripts = scripts(
+            r#"
+            spawn_entity("bat", 4.5, 1.5, 0.25);
+            fn on_tick(dt) {
+                // Chase the player along x
+                let x = entity_x(0);
+                let step = if player_x() < x { -dt } else { dt };
+                move_entity(0, x + step, entity_y(0));
+                set_var("ticks", (get_var("ticks") ?? 0) + 1);
+            }
+            "#,
+        );
+        scripts.start(&mut map, Vec2::ZERO).unwrap();
+        for _ in 0..4 {
+            scripts.tick(&mut map, Vec2::new(1.5, 1.5), 0.25).unwrap();
+        }
+        assert_eq!(map.entities[0].kind, "bat");
+        assert_eq!(map.entities[0].pos, Vec2::new(3.5, 1.5));
+        assert_eq!(scripts.state.borrow().vars["ticks"].as_int().unwrap(), 4);
+    }
+
+    #[test]
+    fn saves_vars() {
+        let mut map = level();
+        let mut playing = scripts(
+            r#"
+            set_var("open", true);
+            set_var("visits", 3);
+            set_var("speed", 1.5);
+            set_var("name", "bob");
+            "#,
+        );
+        playing.start(&mut map, Vec2::ZERO).unwrap();
+        let vars = playing.vars().unwrap();
+        assert_eq!(vars["visits"], Value::Int(3));
+        assert_eq!(vars["name"], Value::Text("bob".to_string()));
+
+        let mut loaded = scripts(
+            r#"
+            fn on_tick(dt) {
+                set_var("speed", get_var("speed") * 2.0);
+            }
+            "#,
+        );
+        loaded.set_vars(&vars);
+        assert_eq!(loaded.vars().unwrap(), vars);
+        loaded.tick(&mut map, Vec2::ZERO, 0.1).unwrap();
+        assert_eq!(loaded.vars().unwrap()["speed"], Value::Float(3.));
+
+        for src in [
+            r#"set_var("list", [1]);"#,
+            r#"set_var("text", "a b");"#,
+            r#"set_var("a b", 1);"#,
+        ] {
+            let mut scripts = scripts(src);
+            scripts.start(&mut map, Vec2::ZERO).unwrap();
+            assert!(scripts.vars().is_err(), "{src}");
+        }
+    }
+
+    #[test]
+    fn sandboxed() {
+        let mut map = level();
+        for src in [
+            "loop {}",
+            r#"import "other" as other;"#,
+            "open_door(1);",
+            "set_tile(6, 0, 1);",
+            "set_tile(0, 0, 200);",
+            r#"spawn_entity("", 1.5, 1.5, 0.25);"#,
+            r#"spawn_entity("bad kind", 1.5, 1.5, 0.25);"#,
+            r#"spawn_entity("bat#2", 1.5, 1.5, 0.25);"#,
+        ] {
+            let result = scripts(src).start(&mut map, Vec2::ZERO);
+            assert!(matches!(result, Err(ScriptError::Runtime { .. })), "{src}");
+        }
+        // The level survives a failing script
+        assert_eq!((map.width(), map.doors().len()), (6, 1));
+        assert!(map.entities.is_empty());
+        assert!(matches!(
+            Scripts::new().add("broken", "fn ("),
+            Err(ScriptError::Parse { .. })
+        ));
+    }
+}
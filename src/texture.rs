@@ -0,0 +1,29 @@
+use crate::consts::{TEXTURE_HEIGHT, TEXTURE_WIDTH};
+
+/// Generates the placeholder textures used by the demo, `TEXTURE_WIDTH * TEXTURE_HEIGHT` 0RGB pixels each.
+pub fn test_textures() -> Vec<Vec<u32>> {
+    // TEMPORARY CODE TO GENERATE TEXTURES TODO remove later (textures should be loaded from files)
+    let mut texture = vec![vec![0u32; (TEXTURE_WIDTH * TEXTURE_HEIGHT) as usize]; 8];
+    for x in 0..TEXTURE_WIDTH {
+        for y in 0..TEXTURE_HEIGHT {
+            let xor_color = (x * 256 / TEXTURE_WIDTH) ^ (y * 256 / TEXTURE_HEIGHT);
+            //int xcolor = x * 256 / texWidth;
+            let y_color = y * 256 / TEXTURE_HEIGHT;
+            let xy_color = y * 128 / TEXTURE_HEIGHT + x * 128 / TEXTURE_WIDTH;
+            texture[0][(TEXTURE_WIDTH * y + x) as usize] =
+                65536 * 254 * ((x != y) && x != (TEXTURE_WIDTH - y)) as u32; //flat red texture with black cross
+            texture[1][(TEXTURE_WIDTH * y + x) as usize] =
+                xy_color + 256 * xy_color + 65536 * xy_color; //sloped greyscale
+            texture[2][(TEXTURE_WIDTH * y + x) as usize] = 256 * xy_color + 65536 * xy_color; //sloped yellow gradient
+            texture[3][(TEXTURE_WIDTH * y + x) as usize] =
+                xor_color + 256 * xor_color + 65536 * xor_color; //xor greyscale
+            texture[4][(TEXTURE_WIDTH * y + x) as usize] = 256 * xor_color; //xor green
+            texture[5][(TEXTURE_WIDTH * y + x) as usize] =
+                65536 * 192 * ((x % 16 != 0) && (y % 16 != 0)) as u32; //red bricks
+            texture[6][(TEXTURE_WIDTH * y + x) as usize] = 65536 * y_color; //red gradient
+            texture[7][(TEXTURE_WIDTH * y + x) as usize] = 128 + 256 * 128 + 65536 * 128;
+            //flat grey texture
+        }
+    }
+    texture
+}
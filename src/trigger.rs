@@ -0,0 +1,280 @@
+//! Trigger zones, areas of a map that make something happen when the player walks into them.
+//!
+//! [`PlayerController`](crate::player::PlayerController) reports every cell the player enters as
+//! [`PlayerEvent::EnterCell`], [`Triggers`] turns those into [`TriggerEvent`]s for the triggers that fire and
+//! queues them until the game drains them. What a trigger does is up to the game, its `action` is only passed
+//! along.
+
+use std::collections::VecDeque;
+
+use crate::{map::Map, player::PlayerEvent};
+
+/// Rectangle of cells, a single cell is 1 x 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Area {
+    pub fn cell(x: usize, y: usize) -> Self {
+        Self {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        // Offsets from the corner instead of the far edges, those can overflow for huge areas
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return false;
+        };
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+}
+
+/// How often a trigger fires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// The first time the player enters it.
+    #[default]
+    Once,
+    /// Every time the player enters it from outside.
+    Repeat,
+}
+
+/// What has to be true for a trigger to fire, checked when the player enters it. A trigger that was entered
+/// while its condition didn't hold only fires after the player leaves and comes back.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Condition {
+    #[default]
+    Always,
+    DoorOpen(usize),
+    DoorClosed(usize),
+    /// A trigger with this name has fired before.
+    Fired(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trigger {
+    /// Used by [`Condition::Fired`] of other triggers, doesn't have to be unique.
+    pub name: String,
+    pub area: Area,
+    pub mode: Mode,
+    pub condition: Condition,
+    /// What happens, up to the game like [`Entity::kind`](crate::map::Entity::kind).
+    pub action: String,
+}
+
+/// A trigger fired because the player entered the cell `(x, y)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEvent {
+    /// Index into [`Map::triggers`].
+    pub trigger: usize,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Which triggers the player is in and which have fired, plus the queue of events that weren't handled yet.
+#[derive(Debug, Clone, Default)]
+pub struct Triggers {
+    inside: Vec<bool>,
+    fired: Vec<bool>,
+    events: VecDeque<TriggerEvent>,
+}
+
+impl Triggers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the trigger at `index` has fired at least once.
+    pub fn has_fired(&self, index: usize) -> bool {
+        self.fired.get(index).copied().unwrap_or(false)
+    }
+
+    /// Whether the player was in the trigger at `index` the last time a cell was entered.
+    pub fn is_inside(&self, index: usize) -> bool {
+        self.inside.get(index).copied().unwrap_or(false)
+    }
+
+    /// Overwrites the state of the trigger at `index`, used to restore saved games.
+    pub fn set_state(&mut self, index: usize, inside: bool, fired: bool) {
+        if index >= self.inside.len() {
+            self.inside.resize(index + 1, false);
+            self.fired.resize(index + 1, false);
+        }
+        self.inside[index] = inside;
+        self.fired[index] = fired;
+    }
+
+    /// Queues events for the cells entered in `events`, see [`Triggers::enter`].
+    pub fn handle(&mut self, map: &Map, events: &[PlayerEvent]) {
+        for event in events {
+            if let PlayerEvent::EnterCell { x, y } = *event {
+                self.enter(map, x, y);
+            }
+        }
+    }
+
+    /// The player is now in the cell `(x, y)`. Triggers are checked in order, so a [`Condition::Fired`] sees
+    /// triggers before it that fire for the same cell.
+    pub fn enter(&mut self, map: &Map, x: i32, y: i32) {
+        // Games can add triggers while playing
+        self.inside.resize(map.triggers.len(), false);
+        self.fired.resize(map.triggers.len(), false);
+
+        for (index, trigger) in map.triggers.iter().enumerate() {
+            let inside = trigger.area.contains(x, y);
+            let entered = inside && !self.inside[index];
+            self.inside[index] = inside;
+            if !entered || (trigger.mode == Mode::Once && self.fired[index]) {
+                continue;
+            }
+            let holds = match &trigger.condition {
+                Condition::Always => true,
+                Condition::DoorOpen(door) => map.doors().get(*door).is_some_and(|door| door.open),
+                Condition::DoorClosed(door) => {
+                    map.doors().get(*door).is_some_and(|door| !door.open)
+                }
+                Condition::Fired(name) => map
+                    .triggers
+                    .iter()
+                    .zip(&self.fired)
+                    .any(|(other, fired)| *fired && other.name == *name),
+            };
+            if holds {
+                self.fired[index] = true;
+                self.events.push_back(TriggerEvent {
+                    trigger: index,
+                    x,
+                    y,
+                });
+            }
+        }
+    }
+
+    /// Removes and returns the queued events, oldest first.
+    pub fn drain(&mut self) -> impl Iterator<Item = TriggerEvent> + '_ {
+        self.events.drain(..)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(name: &str, area: Area, mode: Mode, condition: Condition) -> Trigger {
+        Trigger {
+            name: name.to_string(),
+            area,
+            mode,
+            condition,
+            action: String::new(),
+        }
+    }
+
+    // Walks through `cells` and returns the indices of the triggers that fired
+    fn walk(map: &Map, triggers: &mut Triggers, cells: &[(i32, i32)]) -> Vec<usize> {
+        let events: Vec<_> = cells
+            .iter()
+            .map(|&(x, y)| PlayerEvent::EnterCell { x, y })
+            .collect();
+        triggers.handle(map, &events);
+        triggers.drain().map(|event| event.trigger).collect()
+    }
+
+    #[test]
+    fn area_contains() {
+        let area = Area {
+            x: 2,
+            y: 1,
+            width: 3,
+            height: 2,
+        };
+        assert!(area.contains(2, 1) && area.contains(4, 2));
+        assert!(!area.contains(1, 1) && !area.contains(5, 1) && !area.contains(2, 3));
+        assert!(!area.contains(-1, -1));
+        let huge = Area {
+            x: 1,
+            y: usize::MAX,
+            width: usize::MAX,
+            height: usize::MAX,
+        };
+        assert!(!huge.contains(i32::MAX, 0));
+        let huge = Area { y: 1, ..huge };
+        assert!(huge.contains(i32::MAX, i32::MAX) && !huge.contains(0, 1));
+    }
+
+    #[test]
+    fn once_and_repeat() {
+        let mut map = Map::new(8, 8);
+        let room = Area {
+            x: 2,
+            y: 2,
+            width: 3,
+            height: 2,
+        };
+        map.triggers = vec![
+            trigger("room", room, Mode::Once, Condition::Always),
+            trigger("room", room, Mode::Repeat, Condition::Always),
+            trigger("cell", Area::cell(6, 6), Mode::Repeat, Condition::Always),
+        ];
+        let mut triggers = Triggers::new();
+        // Moving inside the room doesn't enter it again
+        assert_eq!(
+            walk(&map, &mut triggers, &[(1, 2), (2, 2), (3, 2), (3, 3)]),
+            [0, 1]
+        );
+        assert_eq!(walk(&map, &mut triggers, &[(5, 3), (4, 3)]), [1]);
+        assert_eq!(walk(&map, &mut triggers, &[(5, 6), (6, 6), (6, 6)]), [2]);
+        assert!(triggers.has_fired(0) && !triggers.has_fired(3));
+    }
+
+    #[test]
+    fn conditions() {
+        let mut map = Map::new(8, 3);
+        map.set(3, 0, 1);
+        map.set(3, 2, 1);
+        let door = map.add_door(3, 1, 2);
+        map.triggers = vec![
+            trigger(
+                "locked",
+                Area::cell(2, 1),
+                Mode::Repeat,
+                Condition::DoorClosed(door),
+            ),
+            trigger("key", Area::cell(1, 1), Mode::Once, Condition::Always),
+            trigger(
+                "vault",
+                Area::cell(2, 1),
+                Mode::Repeat,
+                Condition::Fired("key".to_string()),
+            ),
+            trigger(
+                "hall",
+                Area::cell(5, 1),
+                Mode::Once,
+                Condition::DoorOpen(door),
+            ),
+            trigger(
+                "broken",
+                Area::cell(6, 1),
+                Mode::Once,
+                Condition::DoorOpen(7),
+            ),
+        ];
+        let mut triggers = Triggers::new();
+        assert_eq!(walk(&map, &mut triggers, &[(2, 1)]), [0]);
+        assert_eq!(walk(&map, &mut triggers, &[(1, 1), (2, 1)]), [1, 0, 2]);
+        map.set_door_open(door, true);
+        assert_eq!(
+            walk(&map, &mut triggers, &[(3, 1), (4, 1), (5, 1), (6, 1)]),
+            [3]
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Checks for mistakes in maps that would only show up while playing.
+
+use std::{collections::VecDeque, fmt};
+
+use crate::{
+    map::{Map, OutOfBounds},
+    trigger::Condition,
+};
+
+/// What's wrong with a map, see [`Map::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// A border cell that isn't a wall, rays and with [`OutOfBounds::Void`] also the view escape through it.
+    OpenBorder,
+    /// A wall, floor, ceiling or door refers to a texture that doesn't exist.
+    MissingTexture { texture: usize },
+    /// Ceilings use texture 7 minus the floor texture, so floor textures above 7 have none.
+    NoCeilingTexture { floor: usize },
+    /// The player spawn is outside of the map or in a wall.
+    SpawnBlocked,
+    /// An entity overlaps a wall.
+    EntityBlocked { entity: usize },
+    /// An entity can't be reached from the player spawn, even with every door open.
+    Unreachable { entity: usize },
+    /// A door without walls on two opposite sides, it would be visible as a lone pillar.
+    DoorWithoutFrame { door: usize },
+    /// Two doors on the same cell.
+    DuplicateDoor { door: usize, other: usize },
+    /// Two entities overlap.
+    OverlappingEntities { entity: usize, other: usize },
+    /// A trigger covers no cells or reaches outside of the map.
+    TriggerOutside { trigger: usize },
+    /// A trigger's condition refers to a door or trigger name that doesn't exist, it never fires.
+    TriggerCondition { trigger: usize },
+}
+
+/// A problem with the cell it is at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub x: i32,
+    pub y: i32,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {}): ", self.x, self.y)?;
+        match &self.problem {
+            Problem::OpenBorder => write!(f, "open border, put a wall here"),
+            Problem::MissingTexture { texture } => write!(f, "texture {texture} doesn't exist"),
+            Problem::NoCeilingTexture { floor } => {
+                write!(
+                    f,
+                    "floor texture {floor} has no ceiling texture, use 7 or less"
+                )
+            }
+            Problem::SpawnBlocked => write!(f, "the player spawns inside a wall"),
+            Problem::EntityBlocked { entity } => write!(f, "entity {entity} overlaps a wall"),
+            Problem::Unreachable { entity } => {
+                write!(f, "entity {entity} can't be reached from the player spawn")
+            }
+            Problem::DoorWithoutFrame { door } => {
+                write!(f, "door {door} needs walls on two opposite sides")
+            }
+            Problem::DuplicateDoor { door, other } => {
+                write!(f, "doors {door} and {other} are on the same cell")
+            }
+            Problem::OverlappingEntities { entity, other } => {
+                write!(f, "entities {entity} and {other} overlap")
+            }
+            Problem::TriggerOutside { trigger } => {
+                write!(f, "trigger {trigger} isn't inside of the map")
+            }
+            Problem::TriggerCondition { trigger } => {
+                write!(f, "the condition of trigger {trigger} can never hold")
+            }
+        }
+    }
+}
+
+// Cell of a world position, for diagnostics
+fn cell_of(x: f64, y: f64) -> (i32, i32) {
+    (x.floor() as i32, y.floor() as i32)
+}
+
+impl Map {
+    /// Everything that's likely a mistake in the map, for a renderer with `texture_count` textures. An empty list
+    /// means the map is fine.
+    pub fn validate(&self, texture_count: usize) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |x: i32, y: i32, problem| diagnostics.push(Diagnostic { x, y, problem });
+        let (width, height) = (self.width() as i32, self.height() as i32);
+        let cells = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)));
+        // Doors count as open here, they can be
+        let is_open = |x: i32, y: i32| self.door_at(x, y).is_some() || !self.is_wall(x, y);
+
+        if self.out_of_bounds() != OutOfBounds::Wrap {
+            for (x, y) in cells.clone() {
+                let on_border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                if on_border && is_open(x, y) {
+                    report(x, y, Problem::OpenBorder);
+                }
+            }
+        }
+
+        for (x, y) in cells.clone() {
+            let cell = self.cell(x, y);
+            if cell > 0 && cell as usize > texture_count {
+                report(
+                    x,
+                    y,
+                    Problem::MissingTexture {
+                        texture: cell as usize - 1,
+                    },
+                );
+            } else if cell < 0 {
+                let floor = cell.unsigned_abs() as usize;
+                if floor >= texture_count {
+                    report(x, y, Problem::MissingTexture { texture: floor });
+                }
+                if floor > 7 {
+                    report(x, y, Problem::NoCeilingTexture { floor });
+                } else if 7 - floor >= texture_count {
+                    report(x, y, Problem::MissingTexture { texture: 7 - floor });
+                }
+            }
+        }
+
+        for (index, door) in self.doors().iter().enumerate() {
+            let (x, y) = (door.x as i32, door.y as i32);
+            // Closed doors were already checked with the other walls
+            if door.open && door.cell as usize > texture_count {
+                report(
+                    x,
+                    y,
+                    Problem::MissingTexture {
+                        texture: door.cell as usize - 1,
+                    },
+                );
+            }
+            let is_frame = |x, y| self.door_at(x, y).is_none() && self.is_wall(x, y);
+            let framed = (is_frame(x - 1, y) && is_frame(x + 1, y))
+                || (is_frame(x, y - 1) && is_frame(x, y + 1));
+            if !framed {
+                report(x, y, Problem::DoorWithoutFrame { door: index });
+            }
+            if let Some(other) = self.door_at(x, y).filter(|other| *other != index) {
+                if other < index {
+                    report(
+                        x,
+                        y,
+                        Problem::DuplicateDoor {
+                            door: other,
+                            other: index,
+                        },
+                    );
+                }
+            }
+        }
+
+        let spawn = self.spawn.pos;
+        let (spawn_x, spawn_y) = cell_of(spawn.x, spawn.y);
+        let spawn_open = self.get(spawn_x, spawn_y).is_some() && is_open(spawn_x, spawn_y);
+        if !spawn_open {
+            report(spawn_x, spawn_y, Problem::SpawnBlocked);
+        }
+
+        // Cells the player can walk to from the spawn
+        let mut reachable = vec![false; self.width() * self.height()];
+        if spawn_open {
+            let index = |x: i32, y: i32| y as usize * self.width() + x as usize;
+            let mut queue = VecDeque::from([(spawn_x, spawn_y)]);
+            reachable[index(spawn_x, spawn_y)] = true;
+            while let Some((x, y)) = queue.pop_front() {
+                for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                    // With a wrapping map, walking off one side leads to the other
+                    let (nx, ny) = if self.out_of_bounds() == OutOfBounds::Wrap {
+                        (nx.rem_euclid(width), ny.rem_euclid(height))
+                    } else {
+                        (nx, ny)
+                    };
+                    if self.get(nx, ny).is_some() && is_open(nx, ny) && !reachable[index(nx, ny)] {
+                        reachable[index(nx, ny)] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+        }
+
+        for (index, entity) in self.entities.iter().enumerate() {
+            let (x, y) = cell_of(entity.pos.x, entity.pos.y);
+            if self.collides(entity.pos.x, entity.pos.y, entity.radius) {
+                report(x, y, Problem::EntityBlocked { entity: index });
+            }
+            let in_map = self.get(x, y).is_some();
+            if spawn_open && !(in_map && reachable[y as usize * self.width() + x as usize]) {
+                report(x, y, Problem::Unreachable { entity: index });
+            }
+            for (other, other_entity) in self.entities.iter().enumerate().skip(index + 1) {
+                if entity.pos.distance(other_entity.pos) < entity.radius + other_entity.radius {
+                    report(
+                        x,
+                        y,
+                        Problem::OverlappingEntities {
+                            entity: index,
+                            other,
+                        },
+                    );
+                }
+            }
+        }
+
+        for (index, trigger) in self.triggers.iter().enumerate() {
+            let area = trigger.area;
+            let (x, y) = (
+                area.x.min(i32::MAX as usize) as i32,
+                area.y.min(i32::MAX as usize) as i32,
+            );
+            let inside = area.width > 0
+                && area.height > 0
+                && area
+                    .x
+                    .checked_add(area.width)
+                    .is_some_and(|right| right <= self.width())
+                && area
+                    .y
+                    .checked_add(area.height)
+                    .is_some_and(|bottom| bottom <= self.height());
+            if !inside {
+                report(x, y, Problem::TriggerOutside { trigger: index });
+            }
+            let possible = match &trigger.condition {
+                Condition::Always => true,
+                Condition::DoorOpen(door) | Condition::DoorClosed(door) => {
+                    *door < self.doors().len()
+                }
+                Condition::Fired(name) => self.triggers.iter().any(|other| other.name == *name),
+            };
+            if !possible {
+                report(x, y, Problem::TriggerCondition { trigger: index });
+            }
+        }
+
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        map::Entity,
+        math::Vec2,
+        texture::test_textures,
+        trigger::{Area, Mode, Trigger},
+    };
+
+    fn problems(map: &Map) -> Vec<(i32, i32, Problem)> {
+        map.validate(test_textures().len())
+            .into_iter()
+            .map(|d| (d.x, d.y, d.problem))
+            .collect()
+    }
+
+    // 7x5 room with a closed border, the player spawns at (1, 2)
+    fn room() -> Map {
+        let mut map = Map::new(7, 5);
+        for x in 0..7 {
+            map.set(x, 0, 1);
+            map.set(x, 4, 1);
+        }
+        for y in 0..5 {
+            map.set(0, y, 1);
+            map.set(6, y, 1);
+        }
+        map.spawn.pos = Vec2::new(1.5, 2.5);
+        map
+    }
+
+    fn entity(x: f64, y: f64) -> Entity {
+        Entity {
+            kind: "barrel".to_string(),
+            pos: Vec2::new(x, y),
+            radius: 0.3,
+        }
+    }
+
+    #[test]
+    fn test_level_is_valid() {
+        assert_eq!(problems(&Map::test_level()), []);
+    }
+
+    #[test]
+    fn open_border() {
+        let mut map = room();
+        map.set(6, 3, 0);
+        assert_eq!(problems(&map), [(6, 3, Problem::OpenBorder)]);
+        map.set_out_of_bounds(OutOfBounds::Wrap);
+        assert_eq!(problems(&map), []);
+    }
+
+    #[test]
+    fn missing_textures() {
+        let mut map = room();
+        map.set(3, 1, 9);
+        map.set(4, 1, -8);
+        map.set(5, 1, -9);
+        assert_eq!(
+            problems(&map),
+            [
+                (3, 1, Problem::MissingTexture { texture: 8 }),
+                (4, 1, Problem::MissingTexture { texture: 8 }),
+                (4, 1, Problem::NoCeilingTexture { floor: 8 }),
+                (5, 1, Problem::MissingTexture { texture: 9 }),
+                (5, 1, Problem::NoCeilingTexture { floor: 9 }),
+            ]
+        );
+
+        // Ceilings are texture 7 minus the floor texture
+        let mut map = room();
+        map.set(1, 1, -1);
+        map.set(2, 1, -4);
+        map.set(3, 1, -6);
+        let problems: Vec<_> = map
+            .validate(5)
+            .into_iter()
+            .map(|d| (d.x, d.y, d.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (1, 1, Problem::MissingTexture { texture: 6 }),
+                (3, 1, Problem::MissingTexture { texture: 6 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn spawn_and_reachability() {
+        let mut map = room();
+        // Wall off the right part of the room with a door in it
+        for y in 1..4 {
+            map.set(4, y, 2);
+        }
+        let door = map.add_door(4, 2, 3);
+        map.entities.push(entity(5.5, 2.5));
+        assert_eq!(problems(&map), []);
+
+        map.remove_door(door);
+        assert_eq!(problems(&map), [(5, 2, Problem::Unreachable { entity: 0 })]);
+
+        map.spawn.pos = Vec2::new(4.5, 1.5);
+        assert_eq!(problems(&map), [(4, 1, Problem::SpawnBlocked)]);
+    }
+
+    #[test]
+    fn doors_need_frames() {
+        let mut map = room();
+        map.set(4, 1, 2);
+        map.set(4, 3, 2);
+        map.add_door(4, 2, 3);
+        map.add_door(2, 2, 3);
+        map.add_door(4, 2, 3);
+        assert_eq!(
+            problems(&map),
+            [
+                (2, 2, Problem::DoorWithoutFrame { door: 1 }),
+                (4, 2, Problem::DuplicateDoor { door: 0, other: 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn entities() {
+        let mut map = room();
+        map.entities
+            .extend([entity(2.5, 2.5), entity(2.9, 2.5), entity(5.2, 1.1)]);
+        assert_eq!(
+            problems(&map),
+            [
+                (
+                    2,
+                    2,
+                    Problem::OverlappingEntities {
+                        entity: 0,
+                        other: 1
+                    }
+                ),
+                (5, 1, Problem::EntityBlocked { entity: 2 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn triggers() {
+        let mut map = room();
+        let trigger = |x, y, width, condition| Trigger {
+            name: "t".to_string(),
+            area: Area {
+                x,
+                y,
+                width,
+                height: 1,
+            },
+            mode: Mode::Once,
+            condition,
+            action: String::new(),
+        };
+        map.triggers = vec![
+            trigger(1, 1, 5, Condition::Fired("t".to_string())),
+            trigger(6, 3, 2, Condition::Always),
+            trigger(2, 2, 0, Condition::DoorOpen(0)),
+            trigger(1, 1, usize::MAX, Condition::Always),
+            trigger(usize::MAX, 1, 2, Condition::Always),
+        ];
+        assert_eq!(
+            problems(&map),
+            [
+                (6, 3, Problem::TriggerOutside { trigger: 1 }),
+                (2, 2, Problem::TriggerOutside { trigger: 2 }),
+                (2, 2, Problem::TriggerCondition { trigger: 2 }),
+                (1, 1, Problem::TriggerOutside { trigger: 3 }),
+                (i32::MAX, 1, Problem::TriggerOutside { trigger: 4 }),
+            ]
+        );
+    }
+}
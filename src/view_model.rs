@@ -0,0 +1,404 @@
+//! First-person weapon drawn at the bottom of the screen after the 3D pass.
+//!
+//! The view model is a set of same-sized frames and one [`Animation`] per [`ViewModelState`]. Game code
+//! changes the state (for example with [`ViewModel::fire`]), the animation of the state then plays by itself
+//! and hands over to the next state when it's done. While idle, the weapon sways with the walk cycle of the
+//! [`PlayerController`].
+
+use std::{collections::HashMap, f64::consts::PI};
+
+use crate::{framebuffer::Framebuffer, player::PlayerController};
+
+/// Pixels of this color are left out when drawing frames.
+pub const TRANSPARENT: u32 = 0xff00ff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ViewModelState {
+    Hidden,
+    /// Coming up from the bottom of the screen.
+    Raising,
+    Idle,
+    Firing,
+    /// Going down to the bottom of the screen, `Hidden` afterwards.
+    Lowering,
+}
+
+/// Frames shown in a state, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    /// Indices into the frames of the view model.
+    pub frames: Vec<usize>,
+    /// Seconds every frame is shown for.
+    pub frame_time: f64,
+    /// State to switch to after the last frame, `None` to loop.
+    pub next: Option<ViewModelState>,
+}
+
+impl Animation {
+    fn duration(&self) -> f64 {
+        self.frames.len() as f64 * self.frame_time
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewModelSettings {
+    /// Height of the view model as a fraction of the screen height.
+    pub scale: f64,
+    /// Horizontal sway while walking, as a fraction of the screen width.
+    pub bob_x: f64,
+    /// How far the view model dips on every step, as a fraction of the screen height.
+    pub bob_y: f64,
+}
+
+impl Default for ViewModelSettings {
+    fn default() -> Self {
+        Self {
+            scale: 0.5,
+            bob_x: 0.04,
+            bob_y: 0.04,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ViewModel {
+    pub settings: ViewModelSettings,
+    frame_width: usize,
+    frames: Vec<Vec<u32>>,
+    animations: HashMap<ViewModelState, Animation>,
+    state: ViewModelState,
+    // Seconds since the current state was entered
+    time: f64,
+    bob_phase: f64,
+    bob_intensity: f64,
+}
+
+impl ViewModel {
+    /// `frames` are `frame_width` pixels wide 0RGB images, all of the same size. States without an animation
+    /// show the first frame, frames shorter than a row aren't drawn.
+    pub fn new(
+        frame_width: usize,
+        frames: Vec<Vec<u32>>,
+        animations: HashMap<ViewModelState, Animation>,
+    ) -> Self {
+        Self {
+            settings: ViewModelSettings::default(),
+            frame_width,
+            frames,
+            animations,
+            state: ViewModelState::Raising,
+            time: 0.,
+            bob_phase: 0.,
+            bob_intensity: 0.,
+        }
+    }
+
+    /// Placeholder pistol used by the demo.
+    pub fn test_weapon() -> Self {
+        let frames = vec![
+            pistol_frame(0, false),
+            pistol_frame(0, true),
+            pistol_frame(4, false),
+            pistol_frame(2, false),
+        ];
+        let animations = HashMap::from([
+            (
+                ViewModelState::Idle,
+                Animation {
+                    frames: vec![0],
+                    frame_time: 1.,
+                    next: None,
+                },
+            ),
+            (
+                ViewModelState::Firing,
+                Animation {
+                    frames: vec![1, 2, 3, 0],
+                    frame_time: 0.06,
+                    next: Some(ViewModelState::Idle),
+                },
+            ),
+            (
+                ViewModelState::Raising,
+                Animation {
+                    frames: vec![0],
+                    frame_time: 0.3,
+                    next: Some(ViewModelState::Idle),
+                },
+            ),
+            (
+                ViewModelState::Lowering,
+                Animation {
+                    frames: vec![0],
+                    frame_time: 0.3,
+                    next: Some(ViewModelState::Hidden),
+                },
+            ),
+        ]);
+        Self::new(PISTOL_SIZE, frames, animations)
+    }
+
+    pub fn state(&self) -> ViewModelState {
+        self.state
+    }
+
+    /// Switches to `state` and starts its animation from the beginning.
+    pub fn set_state(&mut self, state: ViewModelState) {
+        self.state = state;
+        self.time = 0.;
+    }
+
+    /// Starts the fire animation if the weapon is ready, returns whether it fired.
+    pub fn fire(&mut self) -> bool {
+        let ready = self.state == ViewModelState::Idle;
+        if ready {
+            self.set_state(ViewModelState::Firing);
+        }
+        ready
+    }
+
+    /// Advances the animation by `dt` seconds and picks up the walk cycle of `player`.
+    pub fn update(&mut self, dt: f64, player: &PlayerController) {
+        self.bob_phase = player.bob_phase();
+        self.bob_intensity = player.bob_intensity();
+
+        self.time += dt;
+        if let Some(animation) = self.animations.get(&self.state) {
+            if let Some(next) = animation.next {
+                if self.time >= animation.duration() {
+                    self.set_state(next);
+                }
+            }
+        }
+    }
+
+    /// Draws the current frame at the bottom center of `framebuffer`.
+    pub fn draw(&self, framebuffer: &mut Framebuffer) {
+        if self.state == ViewModelState::Hidden || self.frame_width == 0 {
+            return;
+        }
+        let Some(frame) = self.frames.get(self.current_frame()) else {
+            return;
+        };
+        let (width, height) = (framebuffer.width() as f64, framebuffer.height() as f64);
+        let frame_height = frame.len() / self.frame_width;
+        if frame_height == 0 {
+            return;
+        }
+        let draw_height = (height * self.settings.scale).round();
+        let draw_width = (draw_height * self.frame_width as f64 / frame_height as f64).round();
+
+        // Sways left and right once every two steps and dips whenever a foot touches the ground,
+        // in sync with the head bob
+        let intensity = if self.state == ViewModelState::Idle {
+            self.bob_intensity
+        } else {
+            0.
+        };
+        let sway = self.bob_phase.cos() * self.settings.bob_x * width * intensity;
+        let dip = (1. - self.bob_phase.sin().abs()) * self.settings.bob_y * height * intensity;
+
+        // Slides in and out from the bottom of the screen
+        let progress = self
+            .animations
+            .get(&self.state)
+            .map_or(1., |a| (self.time / a.duration()).min(1.));
+        let slide = match self.state {
+            ViewModelState::Raising => (1. - progress) * draw_height,
+            ViewModelState::Lowering => progress * draw_height,
+            _ => 0.,
+        };
+
+        let x = (width - draw_width) / 2. + sway;
+        // The bob can push the frame down, so it starts a bit below the bottom edge to never show a gap
+        let y = height - draw_height + self.settings.bob_y * height + dip + slide;
+        framebuffer.blit_scaled(
+            x.round() as i32,
+            y.round() as i32,
+            (draw_width as usize, draw_height as usize),
+            frame,
+            self.frame_width,
+            Some(TRANSPARENT),
+        );
+    }
+
+    fn current_frame(&self) -> usize {
+        let Some(animation) = self.animations.get(&self.state) else {
+            return 0;
+        };
+        if animation.frames.is_empty() {
+            return 0;
+        }
+        let index = (self.time / animation.frame_time) as usize;
+        let index = match animation.next {
+            None => index % animation.frames.len(),
+            Some(_) => index.min(animation.frames.len() - 1),
+        };
+        animation.frames[index]
+    }
+}
+
+const PISTOL_SIZE: usize = 64;
+
+// Draws a pistol held in a hand, pushed down by `recoil` pixels, optionally with a muzzle flash
+fn pistol_frame(recoil: usize, flash: bool) -> Vec<u32> {
+    let mut frame = vec![TRANSPARENT; PISTOL_SIZE * PISTOL_SIZE];
+    let mut fill = |x0: usize, y0: usize, x1: usize, y1: usize, color: u32| {
+        for y in (y0 + recoil).min(PISTOL_SIZE)..(y1 + recoil).min(PISTOL_SIZE) {
+            frame[y * PISTOL_SIZE + x0..y * PISTOL_SIZE + x1].fill(color);
+        }
+    };
+
+    // Hand
+    fill(22, 44, 42, 64, 0xc08060);
+    fill(20, 48, 22, 64, 0xa06848);
+    fill(42, 48, 44, 64, 0xa06848);
+    // Slide and barrel, lighter on the left side to look round
+    fill(26, 20, 38, 46, 0x505058);
+    fill(26, 20, 29, 46, 0x707078);
+    fill(35, 20, 38, 46, 0x38383e);
+    // Front sight
+    fill(31, 17, 33, 20, 0x303034);
+
+    if flash {
+        // Star shaped muzzle flash, not affected by recoil since it's drawn at the moment of the shot
+        for y in 0..16 {
+            for x in 0..PISTOL_SIZE {
+                let (dx, dy) = (x as f64 - 31.5, y as f64 - 10.);
+                let distance = (dx * dx + dy * dy).sqrt();
+                let angle = dy.atan2(dx);
+                let radius = 6. + 3. * (angle * 4. + PI / 4.).cos();
+                if distance < radius * 0.6 {
+                    frame[y * PISTOL_SIZE + x] = 0xffffc0;
+                } else if distance < radius {
+                    frame[y * PISTOL_SIZE + x] = 0xffa020;
+                }
+            }
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x2 frames of a single color each, frame i is colored i + 1
+    fn view_model() -> ViewModel {
+        let frames = (1..=4).map(|color| vec![color; 4]).collect();
+        let animation = |frames: Vec<usize>, frame_time: f64, next| Animation {
+            frames,
+            frame_time,
+            next,
+        };
+        let animations = HashMap::from([
+            (ViewModelState::Idle, animation(vec![0, 3], 0.5, None)),
+            (
+                ViewModelState::Firing,
+                animation(vec![1, 2, 3], 0.1, Some(ViewModelState::Idle)),
+            ),
+            (
+                ViewModelState::Raising,
+                animation(vec![0], 0.3, Some(ViewModelState::Idle)),
+            ),
+            (
+                ViewModelState::Lowering,
+                animation(vec![0], 0.3, Some(ViewModelState::Hidden)),
+            ),
+        ]);
+        ViewModel::new(2, frames, animations)
+    }
+
+    fn drawn_pixels(view_model: &ViewModel) -> usize {
+        let mut framebuffer = Framebuffer::new(40, 30);
+        view_model.draw(&mut framebuffer);
+        framebuffer
+            .pixels()
+            .iter()
+            .filter(|&&pixel| pixel != 0)
+            .count()
+    }
+
+    #[test]
+    fn states_hand_over() {
+        let player = PlayerController::default();
+        let mut view_model = view_model();
+        assert_eq!(view_model.state(), ViewModelState::Raising);
+        assert!(!view_model.fire());
+        view_model.update(0.25, &player);
+        assert_eq!(view_model.state(), ViewModelState::Raising);
+        view_model.update(0.1, &player);
+        assert_eq!(view_model.state(), ViewModelState::Idle);
+
+        assert!(view_model.fire());
+        assert_eq!(view_model.state(), ViewModelState::Firing);
+        // Not again until the shot is done
+        assert!(!view_model.fire());
+        view_model.update(0.25, &player);
+        assert!(!view_model.fire());
+        view_model.update(0.1, &player);
+        assert_eq!(view_model.state(), ViewModelState::Idle);
+
+        view_model.set_state(ViewModelState::Lowering);
+        assert!(!view_model.fire());
+        view_model.update(0.3, &player);
+        assert_eq!(view_model.state(), ViewModelState::Hidden);
+        assert!(!view_model.fire());
+        // Hidden has no animation and stays
+        view_model.update(10., &player);
+        assert_eq!(view_model.state(), ViewModelState::Hidden);
+    }
+
+    #[test]
+    fn frames_over_the_duration() {
+        let mut view_model = view_model();
+        view_model.set_state(ViewModelState::Firing);
+        for (time, frame) in [(0., 1), (0.05, 1), (0.15, 2), (0.25, 3), (10., 3)] {
+            view_model.time = time;
+            assert_eq!(view_model.current_frame(), frame, "firing at {time}");
+        }
+        // Looping animations start over
+        view_model.set_state(ViewModelState::Idle);
+        for (time, frame) in [(0., 0), (0.6, 3), (1.1, 0), (1.6, 3)] {
+            view_model.time = time;
+            assert_eq!(view_model.current_frame(), frame, "idle at {time}");
+        }
+        // States without an animation show the first frame
+        view_model.set_state(ViewModelState::Hidden);
+        assert_eq!(view_model.current_frame(), 0);
+    }
+
+    #[test]
+    fn slides_in_and_out() {
+        let mut view_model = view_model();
+        view_model.set_state(ViewModelState::Idle);
+        let full = drawn_pixels(&view_model);
+        assert!(full > 0);
+
+        view_model.set_state(ViewModelState::Hidden);
+        assert_eq!(drawn_pixels(&view_model), 0);
+
+        // Starts fully out of view, then rises
+        view_model.set_state(ViewModelState::Raising);
+        assert_eq!(drawn_pixels(&view_model), 0);
+        view_model.time = 0.15;
+        let halfway = drawn_pixels(&view_model);
+        assert!(halfway > 0 && halfway < full, "{halfway}");
+        view_model.time = 0.3;
+        assert_eq!(drawn_pixels(&view_model), full);
+
+        view_model.set_state(ViewModelState::Lowering);
+        assert_eq!(drawn_pixels(&view_model), full);
+        view_model.time = 0.15;
+        assert_eq!(drawn_pixels(&view_model), halfway);
+        view_model.time = 0.3;
+        assert_eq!(drawn_pixels(&view_model), 0);
+    }
+
+    #[test]
+    fn frames_without_a_row_draw_nothing() {
+        let mut view_model = ViewModel::new(4, vec![vec![1, 2, 3]], HashMap::new());
+        view_model.set_state(ViewModelState::Idle);
+        assert_eq!(drawn_pixels(&view_model), 0);
+    }
+}
@@ -0,0 +1,197 @@
+//! Renders fixed camera poses at the Game Boy resolution and compares them with the reference images in
+//! `tests/golden`.
+//!
+//! After an intended change of the output, regenerate the references with
+//! `BLESS=1 cargo test --test golden` and check the new images before committing them.
+
+use std::{env, fs, path::PathBuf};
+
+use rayguy::{
+    camera::Camera,
+    capture,
+    consts::{SURFACE_HEIGHT, SURFACE_WIDTH},
+    framebuffer::Framebuffer,
+    map::{Map, OutOfBounds},
+    math::Vec2,
+    render,
+    texture::test_textures,
+};
+
+// Channels may differ by this much before a pixel counts as changed, to allow for floating point differences
+// between platforms
+const CHANNEL_TOLERANCE: u32 = 8;
+// Fraction of pixels that may change, single texels at the edges of cells can flip on rounding
+const PIXEL_TOLERANCE: f64 = 0.002;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.ppm"))
+}
+
+// Camera looking in the direction `angle` (radians, 0 is +x) with a camera plane of length 0.66
+fn camera(x: f64, y: f64, angle: f64) -> Camera {
+    Camera::new(Vec2::new(x, y), angle, 2. * 0.66f64.atan())
+}
+
+fn render_frame(map: &Map, camera: &Camera, pixel_aspect: f64) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(SURFACE_WIDTH as usize, SURFACE_HEIGHT as usize);
+    render::render(
+        &mut framebuffer,
+        camera,
+        map,
+        &test_textures(),
+        pixel_aspect,
+    );
+    framebuffer
+}
+
+fn channels_differ(a: u32, b: u32) -> bool {
+    [16, 8, 0]
+        .iter()
+        .any(|shift| ((a >> shift) & 0xff).abs_diff((b >> shift) & 0xff) > CHANNEL_TOLERANCE)
+}
+
+fn check(name: &str, map: &Map, camera: &Camera, pixel_aspect: f64) {
+    let actual = render_frame(map, camera, pixel_aspect);
+    let path = golden_path(name);
+
+    if env::var_os("BLESS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save_ppm(&path).unwrap();
+        return;
+    }
+
+    let expected = capture::read_ppm(
+        &mut fs::File::open(&path)
+            .unwrap_or_else(|e| panic!("{}: {e}, run with BLESS=1 to create it", path.display())),
+    )
+    .unwrap();
+
+    // Keep the output around so it can be compared with the reference by eye
+    let actual_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.ppm"));
+    actual.save_ppm(&actual_path).unwrap();
+
+    assert_eq!(
+        (actual.width(), actual.height()),
+        (expected.width(), expected.height()),
+        "{name}: size changed"
+    );
+    let changed = actual
+        .pixels()
+        .iter()
+        .zip(expected.pixels())
+        .filter(|(a, b)| channels_differ(**a, **b))
+        .count();
+    let allowed = (actual.pixels().len() as f64 * PIXEL_TOLERANCE) as usize;
+    assert!(
+        changed <= allowed,
+        "{name}: {changed} pixels changed (at most {allowed} allowed), the output is in {}",
+        actual_path.display()
+    );
+}
+
+// Open room with every wall texture, custom floors and a pillar
+fn room() -> Map {
+    let mut map = Map::new(10, 8);
+    for x in 0..10 {
+        map.set(x, 0, 1 + (x % 8) as i8);
+        map.set(x, 7, 8 - (x % 8) as i8);
+    }
+    for y in 0..8 {
+        map.set(0, y, 6);
+        map.set(9, y, 3);
+    }
+    for (x, y, cell) in [(2, 2, -1), (3, 2, -3), (2, 3, -5), (6, 5, -6), (7, 5, -7)] {
+        map.set(x, y, cell);
+    }
+    map.set(5, 3, 5);
+    map
+}
+
+#[test]
+fn test_level_start() {
+    check(
+        "test_level_start",
+        &Map::test_level(),
+        &camera(22., 11.5, std::f64::consts::PI),
+        1.,
+    );
+}
+
+#[test]
+fn test_level_room() {
+    check(
+        "test_level_room",
+        &Map::test_level(),
+        &camera(19.5, 5.5, 2.3),
+        1.,
+    );
+}
+
+#[test]
+fn test_level_custom_floors() {
+    check(
+        "test_level_custom_floors",
+        &Map::test_level(),
+        &camera(3.5, 4.5, 0.3),
+        1.,
+    );
+}
+
+#[test]
+fn test_level_corridor_stretched() {
+    check(
+        "test_level_corridor_stretched",
+        &Map::test_level(),
+        &camera(11.5, 11.5, 0.),
+        0.8,
+    );
+}
+
+#[test]
+fn test_level_head_bob() {
+    let mut camera = camera(22., 11.5, std::f64::consts::PI);
+    camera.z = -0.02;
+    check("test_level_head_bob", &Map::test_level(), &camera, 1.);
+}
+
+#[test]
+fn room_diagonal() {
+    check("room_diagonal", &room(), &camera(1.5, 1.5, 0.6), 1.);
+}
+
+#[test]
+fn open_edges() {
+    // Without walls on its border, the outside of the map is drawn as solid walls of texture 0
+    check("open_edges", &Map::new(5, 5), &camera(2.5, 2.5, 1.), 1.);
+}
+
+#[test]
+fn open_edges_void() {
+    let mut map = room();
+    map.set(0, 4, 0);
+    map.set(0, 5, 0);
+    map.set_out_of_bounds(OutOfBounds::Void);
+    check("open_edges_void", &map, &camera(1.5, 4.5, 3.), 1.);
+}
+
+#[test]
+fn open_edges_wrap() {
+    let mut map = room();
+    map.set(0, 4, 0);
+    map.set(9, 4, 0);
+    map.set_out_of_bounds(OutOfBounds::Wrap);
+    check("open_edges_wrap", &map, &camera(1.5, 4.5, 3.), 1.);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn parallel_matches_serial() {
+    let map = Map::test_level();
+    let camera = camera(19.5, 5.5, 2.3);
+    let mut parallel = Framebuffer::new(SURFACE_WIDTH as usize, SURFACE_HEIGHT as usize);
+    render::render_parallel(&mut parallel, &camera, &map, &test_textures(), 1.);
+    assert_eq!(parallel, render_frame(&map, &camera, 1.));
+}